@@ -0,0 +1,76 @@
+// Throughput of the SharedCounter and SharedMap implementations from concurrency::counters as
+// the number of threads grows.
+//
+// Run with `cargo bench --bench counters`.
+
+use std::thread;
+use std::time::Instant;
+
+use rust_concepts::concurrency::counters::{
+    AtomicCounter, MutexCounter, MutexMap, RwLockMap, SharedCounter, SharedMap, ShardedCounter,
+    ShardedMap,
+};
+
+const THREAD_COUNTS: [usize; 7] = [1, 2, 4, 8, 16, 32, 64];
+const OPS_PER_THREAD: usize = 100_000;
+const KEYS: [&str; 8] = ["a", "b", "c", "d", "e", "f", "g", "h"];
+
+// Millions of operations per second with `threads` threads each calling `op` OPS_PER_THREAD
+// times.
+fn throughput(threads: usize, op: impl Fn(usize) + Sync) -> f64 {
+    let start = Instant::now();
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                for i in 0..OPS_PER_THREAD {
+                    op(i);
+                }
+            });
+        }
+    });
+
+    (threads * OPS_PER_THREAD) as f64 / start.elapsed().as_secs_f64() / 1_000_000.0
+}
+
+fn header(title: &str, names: &[&str]) {
+    println!("\n{} (million ops/s)", title);
+    print!("{:>8}", "threads");
+    for name in names {
+        print!("{:>12}", name);
+    }
+    println!();
+}
+
+fn main() {
+    header("counters", &["mutex", "atomic", "sharded"]);
+    for threads in THREAD_COUNTS {
+        let counters: [Box<dyn SharedCounter>; 3] = [
+            Box::new(MutexCounter::default()),
+            Box::new(AtomicCounter::default()),
+            Box::new(ShardedCounter::default()),
+        ];
+
+        print!("{:>8}", threads);
+        for counter in &counters {
+            print!("{:>12.2}", throughput(threads, |_| counter.add(1)));
+            assert_eq!(counter.get(), (threads * OPS_PER_THREAD) as u64);
+        }
+        println!();
+    }
+
+    header("maps", &["mutex", "rwlock", "sharded"]);
+    for threads in THREAD_COUNTS {
+        let maps: [Box<dyn SharedMap>; 3] = [
+            Box::new(MutexMap::default()),
+            Box::new(RwLockMap::default()),
+            Box::new(ShardedMap::default()),
+        ];
+
+        print!("{:>8}", threads);
+        for map in &maps {
+            print!("{:>12.2}", throughput(threads, |i| map.add(KEYS[i % KEYS.len()], 1)));
+        }
+        println!();
+    }
+}
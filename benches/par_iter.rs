@@ -0,0 +1,70 @@
+// Compares the parallel adaptors from concurrency::par_iter with the equivalent sequential
+// iterator pipelines, the way iterators.rs writes them.
+//
+// Run with `cargo bench --bench par_iter`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use rust_concepts::concurrency::par_iter::ParallelSlice;
+
+const RUNS: u32 = 10;
+
+// Enough arithmetic per item that the work, not the scheduling, dominates.
+fn heavy(x: u64) -> u64 {
+    (0..200).fold(x, |acc, i| acc.wrapping_mul(31).wrapping_add(i) % 1_000_003)
+}
+
+fn time<T>(f: impl Fn() -> T) -> Duration {
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        black_box(f());
+    }
+    start.elapsed() / RUNS
+}
+
+fn report(name: &str, sequential: Duration, parallel: Duration) {
+    println!(
+        "{:<12} sequential {:>10.2?}   parallel {:>10.2?}   speedup {:.2}x",
+        name,
+        sequential,
+        parallel,
+        sequential.as_secs_f64() / parallel.as_secs_f64()
+    );
+}
+
+fn main() {
+    let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    println!("{} threads, {} runs each", threads, RUNS);
+
+    let v: Vec<u64> = (0..200_000).collect();
+
+    report(
+        "map",
+        time(|| v.iter().map(|x| heavy(*x)).collect::<Vec<_>>()),
+        time(|| v.par_map(|x| heavy(*x))),
+    );
+
+    report(
+        "filter",
+        time(|| v.iter().filter(|x| heavy(**x).is_multiple_of(2)).collect::<Vec<_>>()),
+        time(|| v.par_filter(|x| heavy(*x).is_multiple_of(2))),
+    );
+
+    let mapped: Vec<u64> = v.iter().map(|x| heavy(*x)).collect();
+    report(
+        "reduce",
+        time(|| mapped.iter().fold(0u64, |a, b| a.wrapping_add(*b))),
+        time(|| mapped.par_reduce(0, |a, b| a.wrapping_add(b))),
+    );
+
+    report(
+        "for_each",
+        time(|| v.iter().for_each(|x| {
+            black_box(heavy(*x));
+        })),
+        time(|| v.par_for_each(|x| {
+            black_box(heavy(*x));
+        })),
+    );
+}
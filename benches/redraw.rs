@@ -0,0 +1,109 @@
+// The cost of updating a screen of hundreds of components, drawing the whole frame each time
+// (Screen::render) against RetainedScreen from object_oriented::redraw, which only redraws the
+// components that changed and writes the cells that differ.
+//
+// Run with `cargo bench --bench redraw`.
+
+use std::time::{Duration, Instant};
+
+use rust_concepts::object_oriented::redraw::RetainedScreen;
+use rust_concepts::object_oriented::widgets::{Label, ProgressBar, Style};
+use rust_concepts::object_oriented::{Draw, Screen};
+
+const COMPONENTS: [usize; 3] = [100, 300, 1000];
+const FRAMES: usize = 200;
+// How many progress bars move between two frames.
+const CHANGES_PER_FRAME: usize = 3;
+
+fn bar(progress: f64) -> Box<dyn Draw> {
+    Box::new(ProgressBar {
+        width: 60,
+        progress,
+        style: Style::default(),
+    })
+}
+
+// Alternating labels and progress bars.
+fn screen(components: usize) -> Screen {
+    Screen {
+        components: (0..components)
+            .map(|i| -> Box<dyn Draw> {
+                if i % 2 == 0 {
+                    Box::new(Label {
+                        text: format!("task {}", i / 2),
+                        style: Style::default(),
+                    })
+                } else {
+                    bar(0.0)
+                }
+            })
+            .collect(),
+    }
+}
+
+// The bars changed in `frame`, and how far along they are.
+fn changes(components: usize, frame: usize) -> impl Iterator<Item = (usize, f64)> {
+    (0..CHANGES_PER_FRAME).map(move |n| {
+        let index = ((frame * 7 + n * 31) % (components / 2)) * 2 + 1;
+        (index, (frame % 100) as f64 / 100.0)
+    })
+}
+
+fn full(components: usize) -> (Duration, usize) {
+    let mut screen = screen(components);
+    let mut bytes = 0;
+    let start = Instant::now();
+
+    for frame in 0..FRAMES {
+        for (index, progress) in changes(components, frame) {
+            screen.components[index] = bar(progress);
+        }
+        let mut out = vec![];
+        screen.render(&mut out).unwrap();
+        bytes += out.len();
+    }
+
+    (start.elapsed(), bytes)
+}
+
+fn retained(components: usize) -> (Duration, usize) {
+    let mut screen = RetainedScreen::new(screen(components));
+    screen.render(&mut vec![]).unwrap();
+    let mut bytes = 0;
+    let start = Instant::now();
+
+    for frame in 0..FRAMES {
+        for (index, progress) in changes(components, frame) {
+            *screen.component_mut(index) = bar(progress);
+        }
+        bytes += screen.render(&mut vec![]).unwrap().bytes_written;
+    }
+
+    (start.elapsed(), bytes)
+}
+
+fn main() {
+    println!(
+        "{} frames, {} progress bars changing per frame\n",
+        FRAMES, CHANGES_PER_FRAME
+    );
+    println!(
+        "{:>10} {:>14} {:>14} {:>14} {:>14}",
+        "components", "full us/frame", "full B/frame", "dirty us/frame", "dirty B/frame"
+    );
+
+    for components in COMPONENTS {
+        let (full_time, full_bytes) = full(components);
+        let (dirty_time, dirty_bytes) = retained(components);
+        let per_frame = |time: Duration| time.as_secs_f64() * 1_000_000.0 / FRAMES as f64;
+
+        println!(
+            "{:>10} {:>14.1} {:>14} {:>14.1} {:>14}",
+            components,
+            per_frame(full_time),
+            full_bytes / FRAMES,
+            per_frame(dirty_time),
+            dirty_bytes / FRAMES
+        );
+    }
+}
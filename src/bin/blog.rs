@@ -0,0 +1,305 @@
+// A command line front end for the blog Post workflow in object_oriented2_state_pattern. Posts
+// live in a PostStore directory, so each command loads the post it works on, applies one step
+// and saves it again.
+//
+// Steps the workflow doesn't allow (approving a draft, editing a post under review, the same
+// reviewer approving twice) are reported as errors with a hint, and the post is left as it was.
+
+use std::env;
+use std::fmt;
+use std::io::{self, Write};
+use std::process;
+
+use rust_concepts::object_oriented2_state_pattern::store::PostStore;
+use rust_concepts::object_oriented2_state_pattern::{Post, WorkflowError, REQUIRED_APPROVALS};
+
+const USAGE: &str = "\
+usage: blog [--store DIR] [--as NAME] <command> [arguments]
+
+commands:
+    new [TEXT...]            start a draft, optionally with some text
+    edit ID TEXT...          add a line of text to a draft
+    request-review ID        send a draft for review
+    approve ID               approve a post under review
+    reject ID                send a post under review back to draft
+    show ID                  print a post with its history
+    list [STATE]             list posts, optionally only those in draft,
+                             pending-review or published
+
+options:
+    --store DIR    where posts are kept (default: $BLOG_DIR, or ./posts)
+    --as NAME      who is acting (default: $USER)";
+
+#[derive(Debug)]
+enum CliError {
+    Usage(String),
+    Io(io::Error),
+    Workflow { id: u64, error: WorkflowError },
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::Usage(message) => write!(f, "{}", message),
+            CliError::Io(error) => write!(f, "{}", error),
+            CliError::Workflow { id, error } => write!(f, "post {}: {}", id, error),
+        }
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(error: io::Error) -> CliError {
+        CliError::Io(error)
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if let Err(e) = run(&args, &mut io::stdout()) {
+        eprintln!("blog: {}", e);
+        if let CliError::Usage(_) = e {
+            eprintln!("\n{}", USAGE);
+            process::exit(2);
+        }
+        process::exit(1);
+    }
+}
+
+fn run<W: Write>(args: &[String], out: &mut W) -> Result<(), CliError> {
+    let mut store_dir = env::var("BLOG_DIR").unwrap_or_else(|_| String::from("posts"));
+    let mut actor = env::var("USER").unwrap_or_else(|_| String::from("anonymous"));
+    let mut args = args;
+
+    loop {
+        match args {
+            [flag, value, rest @ ..] if flag == "--store" => {
+                store_dir = value.clone();
+                args = rest;
+            }
+            [flag, value, rest @ ..] if flag == "--as" => {
+                actor = value.clone();
+                args = rest;
+            }
+            [flag, ..] if flag == "--help" || flag == "-h" => {
+                writeln!(out, "{}", USAGE)?;
+                return Ok(());
+            }
+            _ => break,
+        }
+    }
+
+    let store = PostStore::new(store_dir);
+    let (command, args) = match args {
+        [command, rest @ ..] => (command.as_str(), rest),
+        [] => return Err(CliError::Usage(String::from("no command given"))),
+    };
+
+    match command {
+        "new" => {
+            let mut post = Post::new();
+            post.add_text(&args.join(" ")).unwrap();
+            let id = store.create(&post)?;
+            writeln!(out, "created post {}", id)?;
+        }
+        "edit" => {
+            let (id, text) = match args {
+                [id, text @ ..] if !text.is_empty() => (parse_id(id)?, text.join(" ")),
+                _ => return Err(CliError::Usage(String::from("edit needs a post id and some text"))),
+            };
+            let mut post = store.load(id)?;
+            let line = if post.text().is_empty() { text } else { format!("\n{}", text) };
+            post.add_text(&line).map_err(|error| CliError::Workflow { id, error })?;
+            store.save(id, &post)?;
+            writeln!(out, "post {} edited", id)?;
+        }
+        "request-review" | "approve" | "reject" => {
+            let id = single_id(command, args)?;
+            let mut post = store.load(id)?;
+            let from = post.state();
+
+            let result = match command {
+                "request-review" => post.request_review(&actor),
+                "approve" => post.approve(&actor),
+                _ => post.reject(&actor),
+            };
+            result.map_err(|error| CliError::Workflow { id, error })?;
+            store.save(id, &post)?;
+
+            if post.state() == from {
+                writeln!(
+                    out,
+                    "post {}: approved by {} ({} of {})",
+                    id,
+                    actor,
+                    post.approvals(),
+                    REQUIRED_APPROVALS
+                )?;
+            } else {
+                writeln!(out, "post {}: {} -> {}", id, from, post.state())?;
+            }
+        }
+        "show" => {
+            let id = single_id(command, args)?;
+            show(out, id, &store.load(id)?)?;
+        }
+        "list" => {
+            let state = match args {
+                [] => None,
+                [state] => Some(parse_state(state)?),
+                _ => return Err(CliError::Usage(String::from("list takes at most one state"))),
+            };
+            for (id, post) in store.list(state)? {
+                match post {
+                    Ok(post) => {
+                        let first_line = post.text().lines().next().unwrap_or("");
+                        writeln!(out, "{:>4}  {:<13}  {}", id, post.state(), first_line)?;
+                    }
+                    Err(e) => writeln!(out, "{:>4}  {:<13}  {}", id, "unreadable", e)?,
+                }
+            }
+        }
+        _ => return Err(CliError::Usage(format!("unknown command `{}`", command))),
+    }
+
+    Ok(())
+}
+
+fn show<W: Write>(out: &mut W, id: u64, post: &Post) -> io::Result<()> {
+    write!(out, "post {} ({}", id, post.state())?;
+    if !post.approved_by().is_empty() {
+        write!(out, ", approved by {}", post.approved_by().join(", "))?;
+    }
+    writeln!(out, ")\n")?;
+    writeln!(out, "{}\n", post.text())?;
+
+    writeln!(out, "history:")?;
+    for entry in post.history() {
+        writeln!(out, "    {}", entry)?;
+    }
+    Ok(())
+}
+
+fn parse_id(id: &str) -> Result<u64, CliError> {
+    id.parse()
+        .map_err(|_| CliError::Usage(format!("`{}` is not a post id", id)))
+}
+
+fn single_id(command: &str, args: &[String]) -> Result<u64, CliError> {
+    match args {
+        [id] => parse_id(id),
+        _ => Err(CliError::Usage(format!("{} needs exactly one post id", command))),
+    }
+}
+
+fn parse_state(state: &str) -> Result<&'static str, CliError> {
+    match state.to_lowercase().replace(['-', '_'], "").as_str() {
+        "draft" => Ok("Draft"),
+        "pendingreview" => Ok("PendingReview"),
+        "published" => Ok("Published"),
+        _ => Err(CliError::Usage(format!("unknown state `{}`", state))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    struct Blog {
+        dir: PathBuf,
+    }
+
+    impl Blog {
+        fn new(name: &str) -> Blog {
+            let dir = env::temp_dir().join(format!("blog_cli_{}_{}", name, process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            Blog { dir }
+        }
+
+        // Runs one command as `actor` and returns what it printed.
+        fn run(&self, actor: &str, command: &str) -> Result<String, CliError> {
+            let mut args = vec![
+                String::from("--store"),
+                self.dir.display().to_string(),
+                String::from("--as"),
+                String::from(actor),
+            ];
+            args.extend(command.split(' ').map(String::from));
+
+            let mut out = vec![];
+            run(&args, &mut out)?;
+            Ok(String::from_utf8(out).unwrap())
+        }
+    }
+
+    impl Drop for Blog {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn a_post_goes_through_review() {
+        let blog = Blog::new("review");
+
+        assert_eq!(blog.run("alice", "new I ate a salad").unwrap(), "created post 1\n");
+        assert_eq!(blog.run("alice", "edit 1 It was great").unwrap(), "post 1 edited\n");
+        assert_eq!(
+            blog.run("alice", "request-review 1").unwrap(),
+            "post 1: Draft -> PendingReview\n"
+        );
+        assert_eq!(blog.run("bob", "approve 1").unwrap(), "post 1: approved by bob (1 of 2)\n");
+        assert_eq!(
+            blog.run("carol", "approve 1").unwrap(),
+            "post 1: PendingReview -> Published\n"
+        );
+
+        let shown = blog.run("dave", "show 1").unwrap();
+        assert!(shown.starts_with("post 1 (Published)\n\nI ate a salad\nIt was great\n\nhistory:\n"));
+        assert_eq!(shown.lines().filter(|l| l.starts_with("    ")).count(), 3);
+    }
+
+    #[test]
+    fn illegal_steps_are_errors_and_change_nothing() {
+        let blog = Blog::new("illegal");
+        blog.run("alice", "new draft").unwrap();
+
+        let error = blog.run("bob", "approve 1").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "post 1: can't approve a post in Draft: request a review first"
+        );
+
+        blog.run("alice", "request-review 1").unwrap();
+        blog.run("bob", "approve 1").unwrap();
+        assert!(blog.run("bob", "approve 1").unwrap_err().to_string().contains("already approved"));
+        assert!(blog.run("alice", "edit 1 more").unwrap_err().to_string().contains("reject it back"));
+        assert!(blog.run("alice", "show 1").unwrap().starts_with("post 1 (PendingReview, approved by bob)"));
+
+        assert!(matches!(blog.run("alice", "show 7"), Err(CliError::Io(_))));
+        assert!(matches!(blog.run("alice", "approve one"), Err(CliError::Usage(_))));
+        assert!(matches!(blog.run("alice", "publish 1"), Err(CliError::Usage(_))));
+    }
+
+    #[test]
+    fn list_filters_by_state() {
+        let blog = Blog::new("list");
+        blog.run("alice", "new first").unwrap();
+        blog.run("alice", "new second").unwrap();
+        blog.run("alice", "request-review 2").unwrap();
+
+        assert_eq!(
+            blog.run("alice", "list").unwrap(),
+            "   1  Draft          first\n   2  PendingReview  second\n"
+        );
+        assert_eq!(blog.run("alice", "list pending-review").unwrap(), "   2  PendingReview  second\n");
+        assert_eq!(blog.run("alice", "list published").unwrap(), "");
+
+        fs::write(blog.dir.join("3.post"), "state Draft\n").unwrap();
+        let listed = blog.run("alice", "list published").unwrap();
+        assert!(listed.starts_with("   3  unreadable     "), "{}", listed);
+        assert!(listed.ends_with("3.post: line 2: missing `content` line\n"), "{}", listed);
+    }
+}
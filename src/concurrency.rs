@@ -1,8 +1,16 @@
-use std::alloc::System;
+pub mod actor;
+pub mod channel;
+pub mod counters;
+pub mod par_iter;
+pub mod thread_pool;
+pub mod tracked_mutex;
+
 use std::sync::{Arc, mpsc, Mutex};
 use std::thread;
 use std::time::Duration;
 
+use crate::concurrency::thread_pool::ThreadPool;
+
 pub fn run(){
     let handle = thread::spawn(||{
         for i in 1..10 {
@@ -63,11 +71,14 @@ pub fn run(){
     }
 
     // Creating Multiple Producers by Cloning the Transmitter
+    // The producers run on a ThreadPool instead of one thread::spawn each. The pool needs a worker
+    // per producer here, otherwise the second producer would wait for the first to finish.
+    let pool = ThreadPool::new(2);
     let (tx, rx) = mpsc::channel();
 
     let tx1 = tx.clone();
 
-    thread::spawn(move || {
+    pool.execute(move || {
         let vals = vec![
             String::from("hi"),
             String::from("from"),
@@ -81,7 +92,7 @@ pub fn run(){
         }
     });
 
-    thread::spawn(move || {
+    pool.execute(move || {
         let vals = vec![
             String::from("more"),
             String::from("messages"),
@@ -118,7 +129,7 @@ pub fn run(){
 
     for _ in 0..10{
         let counter = Arc::clone(&counter);
-        let handle = thread::spawn(move || {
+        let handle = pool.execute(move || {
             let mut num = counter.lock().unwrap();
 
             *num += 1;
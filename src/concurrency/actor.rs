@@ -0,0 +1,367 @@
+// Actors: message passing with the threads and channels managed for you.
+//
+// Each actor owns its state and runs on its own thread, handling the messages from its mailbox
+// (an mpsc channel) one at a time, so the state never needs a Mutex. Other code talks to it
+// through an ActorRef, either fire-and-forget with `send` or request/response with `ask`, where
+// the message carries a Reply the actor answers through.
+//
+// The thread running an actor also supervises it: if `handle` panics, the broken instance is
+// thrown away and a fresh one is built from the actor's factory, up to the restart limit in its
+// SupervisorStrategy. The message that caused the panic is lost; the rest of the mailbox is kept.
+// Once the limit is reached the actor is stopped for good and its mailbox is dropped. `stopped` is
+// never called on an instance that panicked, since its state can't be trusted any more.
+//
+// ActorSystem::shutdown stops every actor after it has handled the messages already in its
+// mailbox, and waits for their threads to finish.
+
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+pub trait Actor: Send + 'static {
+    type Message: Send + 'static;
+
+    fn handle(&mut self, message: Self::Message);
+
+    fn started(&mut self) {}
+
+    fn stopped(&mut self) {}
+}
+
+enum Envelope<M> {
+    Message(M),
+    Stop,
+}
+
+pub struct ActorRef<M> {
+    sender: mpsc::Sender<Envelope<M>>,
+}
+
+impl<M> Clone for ActorRef<M> {
+    fn clone(&self) -> ActorRef<M> {
+        ActorRef {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<M: Send + 'static> ActorRef<M> {
+    pub fn send(&self, message: M) -> Result<(), ActorError> {
+        self.sender
+            .send(Envelope::Message(message))
+            .map_err(|_| ActorError::Stopped)
+    }
+
+    /// Sends the message built by `make` and waits up to `timeout` for the actor to answer
+    /// through the Reply it was given.
+    pub fn ask<R, F>(&self, make: F, timeout: Duration) -> Result<R, ActorError>
+    where
+        F: FnOnce(Reply<R>) -> M,
+    {
+        let (sender, receiver) = mpsc::channel();
+        self.send(make(Reply { sender }))?;
+
+        receiver.recv_timeout(timeout).map_err(|e| match e {
+            mpsc::RecvTimeoutError::Timeout => ActorError::Timeout,
+            mpsc::RecvTimeoutError::Disconnected => ActorError::NoReply,
+        })
+    }
+}
+
+/// The way back to whoever sent an `ask`.
+pub struct Reply<R> {
+    sender: mpsc::Sender<R>,
+}
+
+impl<R> Reply<R> {
+    pub fn send(self, value: R) {
+        // The asker may have timed out and gone away already.
+        let _ = self.sender.send(value);
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ActorError {
+    /// The actor has stopped, so its mailbox no longer accepts messages.
+    Stopped,
+    /// The actor did not answer an `ask` in time.
+    Timeout,
+    /// The actor dropped the Reply without answering, usually because it panicked.
+    NoReply,
+}
+
+impl fmt::Display for ActorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ActorError::Stopped => write!(f, "the actor has stopped"),
+            ActorError::Timeout => write!(f, "the actor did not reply in time"),
+            ActorError::NoReply => write!(f, "the actor dropped the request without replying"),
+        }
+    }
+}
+
+impl std::error::Error for ActorError {}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SupervisorStrategy {
+    /// How many times a panicking actor is rebuilt before it is stopped for good.
+    pub max_restarts: usize,
+}
+
+impl Default for SupervisorStrategy {
+    fn default() -> SupervisorStrategy {
+        SupervisorStrategy { max_restarts: 3 }
+    }
+}
+
+struct Running {
+    name: String,
+    stop: Box<dyn Fn() + Send>,
+    restarts: Arc<AtomicUsize>,
+    thread: thread::JoinHandle<()>,
+}
+
+pub struct ActorSystem {
+    actors: Vec<Running>,
+}
+
+impl ActorSystem {
+    pub fn new() -> ActorSystem {
+        ActorSystem { actors: vec![] }
+    }
+
+    pub fn spawn<A, F>(&mut self, name: &str, factory: F) -> ActorRef<A::Message>
+    where
+        A: Actor,
+        F: Fn() -> A + Send + 'static,
+    {
+        self.spawn_supervised(name, SupervisorStrategy::default(), factory)
+    }
+
+    pub fn spawn_supervised<A, F>(
+        &mut self,
+        name: &str,
+        strategy: SupervisorStrategy,
+        factory: F,
+    ) -> ActorRef<A::Message>
+    where
+        A: Actor,
+        F: Fn() -> A + Send + 'static,
+    {
+        let (sender, mailbox) = mpsc::channel();
+        let restarts = Arc::new(AtomicUsize::new(0));
+
+        let thread = {
+            let restarts = Arc::clone(&restarts);
+            thread::Builder::new()
+                .name(format!("actor-{}", name))
+                .spawn(move || supervise(factory, mailbox, strategy, restarts))
+                .expect("failed to spawn an actor thread")
+        };
+
+        let stopper = sender.clone();
+        self.actors.push(Running {
+            name: String::from(name),
+            stop: Box::new(move || {
+                let _ = stopper.send(Envelope::Stop);
+            }),
+            restarts,
+            thread,
+        });
+
+        ActorRef { sender }
+    }
+
+    /// How many times the named actor has been restarted after a panic.
+    pub fn restarts(&self, name: &str) -> Option<usize> {
+        self.actors
+            .iter()
+            .find(|actor| actor.name == name)
+            .map(|actor| actor.restarts.load(Ordering::SeqCst))
+    }
+
+    /// Stops every actor once it has handled the messages already in its mailbox, then waits for
+    /// all of them to finish.
+    pub fn shutdown(mut self) {
+        self.stop_all();
+    }
+
+    fn stop_all(&mut self) {
+        for actor in &self.actors {
+            (actor.stop)();
+        }
+        for actor in self.actors.drain(..) {
+            // The supervisor catches the actor's panics, so the thread itself ends cleanly.
+            let _ = actor.thread.join();
+        }
+    }
+}
+
+impl Default for ActorSystem {
+    fn default() -> ActorSystem {
+        ActorSystem::new()
+    }
+}
+
+impl Drop for ActorSystem {
+    fn drop(&mut self) {
+        self.stop_all();
+    }
+}
+
+fn supervise<A, F>(
+    factory: F,
+    mailbox: mpsc::Receiver<Envelope<A::Message>>,
+    strategy: SupervisorStrategy,
+    restarts: Arc<AtomicUsize>,
+) where
+    A: Actor,
+    F: Fn() -> A,
+{
+    let mut actor = factory();
+    actor.started();
+
+    // The system keeps a sender for every actor, so the mailbox only closes after a Stop.
+    while let Ok(Envelope::Message(message)) = mailbox.recv() {
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| actor.handle(message)));
+
+        if outcome.is_err() {
+            if restarts.load(Ordering::SeqCst) >= strategy.max_restarts {
+                // Gives up without `stopped`: this instance just panicked.
+                return;
+            }
+            restarts.fetch_add(1, Ordering::SeqCst);
+            actor = factory();
+            actor.started();
+        }
+    }
+
+    actor.stopped();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    enum CounterMessage {
+        Add(i32),
+        Get(Reply<i32>),
+        Fail,
+        FailAsked(Reply<i32>),
+    }
+
+    struct Counter {
+        total: i32,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Actor for Counter {
+        type Message = CounterMessage;
+
+        fn handle(&mut self, message: CounterMessage) {
+            match message {
+                CounterMessage::Add(n) => self.total += n,
+                CounterMessage::Get(reply) => reply.send(self.total),
+                CounterMessage::Fail | CounterMessage::FailAsked(_) => panic!("counter failed"),
+            }
+        }
+
+        fn stopped(&mut self) {
+            self.log.lock().unwrap().push(format!("stopped at {}", self.total));
+        }
+    }
+
+    fn counter(log: &Arc<Mutex<Vec<String>>>) -> impl Fn() -> Counter + Send + 'static {
+        let log = Arc::clone(log);
+        move || Counter {
+            total: 0,
+            log: Arc::clone(&log),
+        }
+    }
+
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn ask_gets_the_actor_state() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let mut system = ActorSystem::new();
+        let counter = system.spawn("counter", counter(&log));
+
+        for n in 1..=4 {
+            counter.send(CounterMessage::Add(n)).unwrap();
+        }
+
+        assert_eq!(counter.ask(CounterMessage::Get, TIMEOUT), Ok(10));
+    }
+
+    #[test]
+    fn panicking_actor_is_restarted_with_fresh_state() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let mut system = ActorSystem::new();
+        let counter = system.spawn("counter", counter(&log));
+
+        counter.send(CounterMessage::Add(5)).unwrap();
+        counter.send(CounterMessage::Fail).unwrap();
+        counter.send(CounterMessage::Add(2)).unwrap();
+
+        assert_eq!(counter.ask(CounterMessage::Get, TIMEOUT), Ok(2));
+        assert_eq!(system.restarts("counter"), Some(1));
+    }
+
+    #[test]
+    fn actor_stops_after_too_many_restarts() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let mut system = ActorSystem::new();
+        let strategy = SupervisorStrategy { max_restarts: 1 };
+        let counter = system.spawn_supervised("counter", strategy, counter(&log));
+
+        counter.send(CounterMessage::Fail).unwrap();
+        // The mailbox is still open when this is sent, since it can only close after handling it.
+        assert_eq!(counter.ask(CounterMessage::FailAsked, TIMEOUT), Err(ActorError::NoReply));
+
+        system.shutdown();
+        assert_eq!(counter.send(CounterMessage::Add(1)), Err(ActorError::Stopped));
+        assert!(log.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn ask_times_out_when_the_actor_is_busy() {
+        struct Sleepy;
+
+        impl Actor for Sleepy {
+            type Message = Reply<()>;
+
+            fn handle(&mut self, reply: Reply<()>) {
+                thread::sleep(Duration::from_millis(200));
+                reply.send(());
+            }
+        }
+
+        let mut system = ActorSystem::new();
+        let sleepy = system.spawn("sleepy", || Sleepy);
+
+        assert_eq!(
+            sleepy.ask(|reply| reply, Duration::from_millis(10)),
+            Err(ActorError::Timeout)
+        );
+    }
+
+    #[test]
+    fn shutdown_handles_queued_messages_first() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let mut system = ActorSystem::new();
+        let counter = system.spawn("counter", counter(&log));
+
+        for _ in 0..100 {
+            counter.send(CounterMessage::Add(1)).unwrap();
+        }
+        system.shutdown();
+
+        assert_eq!(*log.lock().unwrap(), vec![String::from("stopped at 100")]);
+        assert_eq!(counter.send(CounterMessage::Add(1)), Err(ActorError::Stopped));
+    }
+}
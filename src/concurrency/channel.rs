@@ -0,0 +1,702 @@
+// Channels built from a Mutex-protected queue and two Condvars, as an alternative to mpsc when
+// the producer needs to slow down for the consumer.
+//
+// A bounded channel holds at most `capacity` messages. Once it is full, `send` blocks until a
+// receiver makes room (backpressure), `try_send` fails straight away and `send_timeout` gives
+// up after a while. Senders and receivers can both be cloned; each message goes to exactly one
+// receiver. A channel is disconnected when it is closed explicitly or when every handle on the
+// other side has been dropped, but receivers still get whatever was queued before that.
+//
+// `select` waits on several receivers at once and `Broadcaster` delivers every message to every
+// subscriber.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+pub fn bounded<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "a bounded channel needs room for at least one message");
+    new_channel(Some(capacity))
+}
+
+pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
+    new_channel(None)
+}
+
+fn new_channel<T>(capacity: Option<usize>) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::new(),
+            capacity,
+            senders: 1,
+            receivers: 1,
+            closed: false,
+            selectors: vec![],
+        }),
+        not_empty: Condvar::new(),
+        not_full: Condvar::new(),
+    });
+
+    (
+        Sender {
+            shared: Arc::clone(&shared),
+        },
+        Receiver { shared },
+    )
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+}
+
+struct State<T> {
+    queue: VecDeque<T>,
+    capacity: Option<usize>,
+    senders: usize,
+    receivers: usize,
+    closed: bool,
+    // Threads blocked in `select` on this channel, woken on every send and disconnect.
+    selectors: Vec<Arc<Signal>>,
+}
+
+impl<T> State<T> {
+    fn is_full(&self) -> bool {
+        self.capacity.is_some_and(|capacity| self.queue.len() >= capacity)
+    }
+
+    fn send_disconnected(&self) -> bool {
+        self.closed || self.receivers == 0
+    }
+
+    fn recv_disconnected(&self) -> bool {
+        self.queue.is_empty() && (self.closed || self.senders == 0)
+    }
+
+    fn notify_selectors(&self) {
+        for signal in &self.selectors {
+            signal.notify();
+        }
+    }
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        // The lock is never held while user code runs, so it can't be poisoned by a panic that
+        // leaves the queue half updated; carry on with the inner state.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn disconnect(&self) {
+        let state = self.lock();
+        self.not_empty.notify_all();
+        self.not_full.notify_all();
+        state.notify_selectors();
+    }
+
+    fn push(&self, mut state: MutexGuard<'_, State<T>>, message: T) {
+        state.queue.push_back(message);
+        state.notify_selectors();
+        drop(state);
+        self.not_empty.notify_one();
+    }
+
+    fn pop(&self, mut state: MutexGuard<'_, State<T>>) -> Option<T> {
+        let message = state.queue.pop_front();
+        drop(state);
+        if message.is_some() {
+            self.not_full.notify_one();
+        }
+        message
+    }
+}
+
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends a message, waiting for room if the channel is full.
+    pub fn send(&self, message: T) -> Result<(), SendError<T>> {
+        let mut state = self.shared.lock();
+
+        loop {
+            if state.send_disconnected() {
+                return Err(SendError(message));
+            }
+            if !state.is_full() {
+                self.shared.push(state, message);
+                return Ok(());
+            }
+            state = self.shared.not_full.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    pub fn try_send(&self, message: T) -> Result<(), TrySendError<T>> {
+        let state = self.shared.lock();
+
+        if state.send_disconnected() {
+            Err(TrySendError::Disconnected(message))
+        } else if state.is_full() {
+            Err(TrySendError::Full(message))
+        } else {
+            self.shared.push(state, message);
+            Ok(())
+        }
+    }
+
+    pub fn send_timeout(&self, message: T, timeout: Duration) -> Result<(), SendTimeoutError<T>> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.lock();
+
+        loop {
+            if state.send_disconnected() {
+                return Err(SendTimeoutError::Disconnected(message));
+            }
+            if !state.is_full() {
+                self.shared.push(state, message);
+                return Ok(());
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(SendTimeoutError::Timeout(message));
+            }
+            state = self
+                .shared
+                .not_full
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    /// Closes the channel for everybody. Messages already queued can still be received.
+    pub fn close(&self) {
+        self.shared.lock().closed = true;
+        self.shared.disconnect();
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Sender<T> {
+        self.shared.lock().senders += 1;
+        Sender {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let last = {
+            let mut state = self.shared.lock();
+            state.senders -= 1;
+            state.senders == 0
+        };
+        if last {
+            self.shared.disconnect();
+        }
+    }
+}
+
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Waits for the next message. Fails once the channel is empty and disconnected.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let mut state = self.shared.lock();
+
+        loop {
+            if !state.queue.is_empty() {
+                return self.shared.pop(state).ok_or(RecvError);
+            }
+            if state.recv_disconnected() {
+                return Err(RecvError);
+            }
+            state = self.shared.not_empty.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let state = self.shared.lock();
+
+        if state.recv_disconnected() {
+            Err(TryRecvError::Disconnected)
+        } else {
+            self.shared.pop(state).ok_or(TryRecvError::Empty)
+        }
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.lock();
+
+        loop {
+            if !state.queue.is_empty() {
+                return self.shared.pop(state).ok_or(RecvTimeoutError::Disconnected);
+            }
+            if state.recv_disconnected() {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            state = self
+                .shared
+                .not_empty
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    /// Closes the channel for everybody. Messages already queued can still be received.
+    pub fn close(&self) {
+        self.shared.lock().closed = true;
+        self.shared.disconnect();
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { receiver: self }
+    }
+
+    fn register(&self, signal: &Arc<Signal>) {
+        self.shared.lock().selectors.push(Arc::clone(signal));
+    }
+
+    fn unregister(&self, signal: &Arc<Signal>) {
+        self.shared
+            .lock()
+            .selectors
+            .retain(|registered| !Arc::ptr_eq(registered, signal));
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Receiver<T> {
+        self.shared.lock().receivers += 1;
+        Receiver {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let last = {
+            let mut state = self.shared.lock();
+            state.receivers -= 1;
+            state.receivers == 0
+        };
+        if last {
+            self.shared.disconnect();
+        }
+    }
+}
+
+pub struct Iter<'a, T> {
+    receiver: &'a Receiver<T>,
+}
+
+impl<T> Iterator for Iter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Receiver<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+// A one-shot wake-up flag shared between a selecting thread and the channels it waits on.
+struct Signal {
+    ready: Mutex<bool>,
+    cond: Condvar,
+}
+
+impl Signal {
+    fn notify(&self) {
+        *self.ready.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        self.cond.notify_all();
+    }
+
+    // Returns false if the deadline passed before anybody called notify.
+    fn wait(&self, deadline: Option<Instant>) -> bool {
+        let mut ready = self.ready.lock().unwrap_or_else(|e| e.into_inner());
+
+        while !*ready {
+            match deadline {
+                None => ready = self.cond.wait(ready).unwrap_or_else(|e| e.into_inner()),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return false;
+                    }
+                    ready = self
+                        .cond
+                        .wait_timeout(ready, deadline - now)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0;
+                }
+            }
+        }
+
+        *ready = false;
+        true
+    }
+}
+
+/// Waits until one of the receivers has a message and returns it with the receiver's index.
+/// Receivers earlier in the slice win when several are ready. Fails once every receiver is
+/// disconnected.
+pub fn select<T>(receivers: &[&Receiver<T>]) -> Result<(usize, T), RecvError> {
+    select_until(receivers, None).map_err(|_| RecvError)
+}
+
+pub fn select_timeout<T>(
+    receivers: &[&Receiver<T>],
+    timeout: Duration,
+) -> Result<(usize, T), RecvTimeoutError> {
+    select_until(receivers, Some(Instant::now() + timeout))
+}
+
+fn select_until<T>(
+    receivers: &[&Receiver<T>],
+    deadline: Option<Instant>,
+) -> Result<(usize, T), RecvTimeoutError> {
+    let signal = Arc::new(Signal {
+        ready: Mutex::new(false),
+        cond: Condvar::new(),
+    });
+
+    // Register before the first poll so a message sent in between still wakes us up.
+    for receiver in receivers {
+        receiver.register(&signal);
+    }
+
+    let result = loop {
+        let mut disconnected = 0;
+        let mut found = None;
+
+        for (index, receiver) in receivers.iter().enumerate() {
+            match receiver.try_recv() {
+                Ok(message) => {
+                    found = Some((index, message));
+                    break;
+                }
+                Err(TryRecvError::Disconnected) => disconnected += 1,
+                Err(TryRecvError::Empty) => {}
+            }
+        }
+
+        if let Some(found) = found {
+            break Ok(found);
+        }
+        if disconnected == receivers.len() {
+            break Err(RecvTimeoutError::Disconnected);
+        }
+        if !signal.wait(deadline) {
+            break Err(RecvTimeoutError::Timeout);
+        }
+    };
+
+    for receiver in receivers {
+        receiver.unregister(&signal);
+    }
+
+    result
+}
+
+/// Delivers a clone of every message to every subscriber. Each subscriber has its own bounded
+/// queue, so one slow subscriber holds the sender back rather than missing messages.
+pub struct Broadcaster<T> {
+    subscribers: Mutex<Vec<Sender<T>>>,
+    capacity: usize,
+}
+
+impl<T: Clone> Broadcaster<T> {
+    pub fn new(capacity: usize) -> Broadcaster<T> {
+        assert!(capacity > 0, "a broadcast channel needs room for at least one message");
+        Broadcaster {
+            subscribers: Mutex::new(vec![]),
+            capacity,
+        }
+    }
+
+    /// Subscribers only see messages sent after they subscribed.
+    pub fn subscribe(&self) -> Receiver<T> {
+        let (sender, receiver) = bounded(self.capacity);
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(sender);
+        receiver
+    }
+
+    /// Sends the message to every live subscriber and returns how many received it.
+    /// Subscribers whose receivers were dropped are forgotten.
+    pub fn send(&self, message: T) -> Result<usize, SendError<T>> {
+        let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+
+        subscribers.retain(|subscriber| subscriber.send(message.clone()).is_ok());
+
+        if subscribers.is_empty() {
+            Err(SendError(message))
+        } else {
+            Ok(subscribers.len())
+        }
+    }
+
+    /// Closes every subscriber's channel. They can still drain what was already sent.
+    pub fn close(&self) {
+        let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+        for subscriber in subscribers.drain(..) {
+            subscriber.close();
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TrySendError<T> {
+    Full(T),
+    Disconnected(T),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SendTimeoutError<T> {
+    Timeout(T),
+    Disconnected(T),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct RecvError;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TryRecvError {
+    Empty,
+    Disconnected,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RecvTimeoutError {
+    Timeout,
+    Disconnected,
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "sending on a disconnected channel")
+    }
+}
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TrySendError::Full(_) => write!(f, "sending on a full channel"),
+            TrySendError::Disconnected(_) => write!(f, "sending on a disconnected channel"),
+        }
+    }
+}
+
+impl<T> fmt::Display for SendTimeoutError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SendTimeoutError::Timeout(_) => write!(f, "timed out waiting for room in the channel"),
+            SendTimeoutError::Disconnected(_) => write!(f, "sending on a disconnected channel"),
+        }
+    }
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "receiving on an empty and disconnected channel")
+    }
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "receiving on an empty channel"),
+            TryRecvError::Disconnected => write!(f, "receiving on an empty and disconnected channel"),
+        }
+    }
+}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecvTimeoutError::Timeout => write!(f, "timed out waiting on the channel"),
+            RecvTimeoutError::Disconnected => write!(f, "receiving on an empty and disconnected channel"),
+        }
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for SendError<T> {}
+impl<T: fmt::Debug> std::error::Error for TrySendError<T> {}
+impl<T: fmt::Debug> std::error::Error for SendTimeoutError<T> {}
+impl std::error::Error for RecvError {}
+impl std::error::Error for TryRecvError {}
+impl std::error::Error for RecvTimeoutError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    #[test]
+    fn multiple_producers_keep_their_own_order() {
+        let (tx, rx) = bounded(2);
+        let tx1 = tx.clone();
+
+        let first = thread::spawn(move || {
+            for val in ["hi", "from", "the", "thread"] {
+                tx1.send(val).unwrap();
+            }
+        });
+        let second = thread::spawn(move || {
+            for val in ["more", "messages", "for", "you"] {
+                tx.send(val).unwrap();
+            }
+        });
+
+        let received: Vec<&str> = rx.iter().collect();
+        first.join().unwrap();
+        second.join().unwrap();
+
+        let from_first: Vec<&str> = received
+            .iter()
+            .copied()
+            .filter(|val| ["hi", "from", "the", "thread"].contains(val))
+            .collect();
+        let from_second: Vec<&str> = received
+            .iter()
+            .copied()
+            .filter(|val| ["more", "messages", "for", "you"].contains(val))
+            .collect();
+
+        assert_eq!(received.len(), 8);
+        assert_eq!(from_first, vec!["hi", "from", "the", "thread"]);
+        assert_eq!(from_second, vec!["more", "messages", "for", "you"]);
+    }
+
+    #[test]
+    fn full_channel_blocks_the_sender() {
+        let (tx, rx) = bounded(1);
+        let sent = Arc::new(AtomicUsize::new(0));
+
+        let producer = {
+            let sent = Arc::clone(&sent);
+            thread::spawn(move || {
+                for i in 0..3 {
+                    tx.send(i).unwrap();
+                    sent.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+        };
+
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(sent.load(Ordering::SeqCst), 1);
+
+        assert_eq!(rx.iter().collect::<Vec<_>>(), vec![0, 1, 2]);
+        producer.join().unwrap();
+    }
+
+    #[test]
+    fn try_send_and_timeouts_fail_when_full_or_empty() {
+        let (tx, rx) = bounded(1);
+
+        assert_eq!(tx.try_send(1), Ok(()));
+        assert_eq!(tx.try_send(2), Err(TrySendError::Full(2)));
+        assert_eq!(
+            tx.send_timeout(3, Duration::from_millis(10)),
+            Err(SendTimeoutError::Timeout(3))
+        );
+
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Timeout)
+        );
+    }
+
+    #[test]
+    fn closed_channel_drains_then_disconnects() {
+        let (tx, rx) = unbounded();
+        tx.send("queued").unwrap();
+
+        rx.close();
+
+        assert_eq!(tx.send("late"), Err(SendError("late")));
+        assert_eq!(rx.recv(), Ok("queued"));
+        assert_eq!(rx.recv(), Err(RecvError));
+    }
+
+    #[test]
+    fn dropping_every_sender_disconnects() {
+        let (tx, rx) = bounded::<i32>(4);
+        let tx1 = tx.clone();
+        drop(tx);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+
+        drop(tx1);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn select_returns_whichever_receiver_is_ready() {
+        let (tx_a, rx_a) = bounded(1);
+        let (tx_b, rx_b) = bounded(1);
+
+        let sender = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            tx_b.send("from b").unwrap();
+        });
+
+        assert_eq!(select(&[&rx_a, &rx_b]), Ok((1, "from b")));
+        sender.join().unwrap();
+
+        assert_eq!(
+            select_timeout(&[&rx_a, &rx_b], Duration::from_millis(10)),
+            Err(RecvTimeoutError::Timeout)
+        );
+
+        tx_a.send("from a").unwrap();
+        drop(tx_a);
+        assert_eq!(select(&[&rx_a, &rx_b]), Ok((0, "from a")));
+        assert_eq!(select(&[&rx_a, &rx_b]), Err(RecvError));
+    }
+
+    #[test]
+    fn broadcast_reaches_every_subscriber() {
+        let broadcaster = Broadcaster::new(4);
+        let first = broadcaster.subscribe();
+        let second = broadcaster.subscribe();
+
+        assert_eq!(broadcaster.send(1), Ok(2));
+        drop(second);
+        assert_eq!(broadcaster.send(2), Ok(1));
+        broadcaster.close();
+
+        assert_eq!(first.iter().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(broadcaster.send(3), Err(SendError(3)));
+    }
+}
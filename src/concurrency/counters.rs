@@ -0,0 +1,284 @@
+// Alternatives to the Arc<Mutex<i32>> counter from concurrency::run, where every increment
+// waits for the one before it.
+//
+// SharedCounter is one number many threads add to; SharedMap is a set of named counters. Each
+// has a Mutex version to compare against, plus:
+//
+// - AtomicCounter: a single AtomicU64, so an increment is one CPU instruction and never blocks.
+// - RwLockMap: a RwLock around a map of atomics. Adding to a key that already exists only takes
+//   the read lock, which many threads can hold together; only new keys need the write lock.
+// - ShardedCounter / ShardedMap: one shard per thread (threads share shards when there are more
+//   threads than shards). Writers almost never meet, at the price of a merge step that sums
+//   every shard when the value is read.
+//
+// benches/counters.rs measures them at 1 to 64 threads.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::thread;
+
+pub trait SharedCounter: Send + Sync {
+    fn add(&self, n: u64);
+    fn get(&self) -> u64;
+}
+
+pub trait SharedMap: Send + Sync {
+    fn add(&self, key: &str, n: u64);
+    fn get(&self, key: &str) -> u64;
+    fn snapshot(&self) -> HashMap<String, u64>;
+}
+
+#[derive(Default)]
+pub struct MutexCounter {
+    value: Mutex<u64>,
+}
+
+impl SharedCounter for MutexCounter {
+    fn add(&self, n: u64) {
+        *self.value.lock().unwrap() += n;
+    }
+
+    fn get(&self) -> u64 {
+        *self.value.lock().unwrap()
+    }
+}
+
+#[derive(Default)]
+pub struct AtomicCounter {
+    value: AtomicU64,
+}
+
+impl SharedCounter for AtomicCounter {
+    fn add(&self, n: u64) {
+        // Nothing else is published through the counter, so no ordering with other memory is
+        // needed; the addition itself is still atomic.
+        self.value.fetch_add(n, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+// Each shard sits on its own cache line, otherwise neighbouring shards would still fight over
+// the same line even though they are different atomics.
+#[repr(align(64))]
+#[derive(Default)]
+struct Padded<T>(T);
+
+pub struct ShardedCounter {
+    shards: Vec<Padded<AtomicU64>>,
+}
+
+impl ShardedCounter {
+    pub fn new(shards: usize) -> ShardedCounter {
+        ShardedCounter {
+            shards: (0..shards.max(1)).map(|_| Padded::default()).collect(),
+        }
+    }
+}
+
+impl Default for ShardedCounter {
+    fn default() -> ShardedCounter {
+        ShardedCounter::new(default_shards())
+    }
+}
+
+impl SharedCounter for ShardedCounter {
+    fn add(&self, n: u64) {
+        self.shards[shard_index(self.shards.len())].0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> u64 {
+        self.shards.iter().map(|shard| shard.0.load(Ordering::Relaxed)).sum()
+    }
+}
+
+#[derive(Default)]
+pub struct MutexMap {
+    values: Mutex<HashMap<String, u64>>,
+}
+
+impl SharedMap for MutexMap {
+    fn add(&self, key: &str, n: u64) {
+        *self.values.lock().unwrap().entry(String::from(key)).or_insert(0) += n;
+    }
+
+    fn get(&self, key: &str) -> u64 {
+        self.values.lock().unwrap().get(key).copied().unwrap_or(0)
+    }
+
+    fn snapshot(&self) -> HashMap<String, u64> {
+        self.values.lock().unwrap().clone()
+    }
+}
+
+#[derive(Default)]
+pub struct RwLockMap {
+    values: RwLock<HashMap<String, AtomicU64>>,
+}
+
+impl SharedMap for RwLockMap {
+    fn add(&self, key: &str, n: u64) {
+        if let Some(value) = self.values.read().unwrap().get(key) {
+            value.fetch_add(n, Ordering::Relaxed);
+            return;
+        }
+
+        // Another thread may have inserted the key between dropping the read lock and taking
+        // the write lock, so go through the entry API rather than inserting blindly.
+        self.values
+            .write()
+            .unwrap()
+            .entry(String::from(key))
+            .or_default()
+            .fetch_add(n, Ordering::Relaxed);
+    }
+
+    fn get(&self, key: &str) -> u64 {
+        self.values
+            .read()
+            .unwrap()
+            .get(key)
+            .map(|value| value.load(Ordering::Relaxed))
+            .unwrap_or(0)
+    }
+
+    fn snapshot(&self) -> HashMap<String, u64> {
+        self.values
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(key, value)| (key.clone(), value.load(Ordering::Relaxed)))
+            .collect()
+    }
+}
+
+pub struct ShardedMap {
+    shards: Vec<Padded<Mutex<HashMap<String, u64>>>>,
+}
+
+impl ShardedMap {
+    pub fn new(shards: usize) -> ShardedMap {
+        ShardedMap {
+            shards: (0..shards.max(1)).map(|_| Padded::default()).collect(),
+        }
+    }
+}
+
+impl Default for ShardedMap {
+    fn default() -> ShardedMap {
+        ShardedMap::new(default_shards())
+    }
+}
+
+impl SharedMap for ShardedMap {
+    fn add(&self, key: &str, n: u64) {
+        let mut shard = self.shards[shard_index(self.shards.len())].0.lock().unwrap();
+        match shard.get_mut(key) {
+            Some(value) => *value += n,
+            None => {
+                shard.insert(String::from(key), n);
+            }
+        }
+    }
+
+    fn get(&self, key: &str) -> u64 {
+        self.shards
+            .iter()
+            .map(|shard| shard.0.lock().unwrap().get(key).copied().unwrap_or(0))
+            .sum()
+    }
+
+    fn snapshot(&self) -> HashMap<String, u64> {
+        let mut merged = HashMap::new();
+        for shard in &self.shards {
+            for (key, value) in shard.0.lock().unwrap().iter() {
+                *merged.entry(key.clone()).or_insert(0) += value;
+            }
+        }
+        merged
+    }
+}
+
+fn default_shards() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(4) * 4
+}
+
+// Hands every thread a number the first time it touches a sharded value, so consecutive
+// threads land on different shards.
+fn shard_index(shards: usize) -> usize {
+    static NEXT_THREAD: AtomicUsize = AtomicUsize::new(0);
+
+    thread_local! {
+        static THREAD: usize = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+    }
+
+    THREAD.with(|thread| thread % shards)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THREADS: u64 = 8;
+    const ADDS: u64 = 1_000;
+
+    fn hammer_counter(counter: &dyn SharedCounter) -> u64 {
+        thread::scope(|scope| {
+            for _ in 0..THREADS {
+                scope.spawn(|| {
+                    for _ in 0..ADDS {
+                        counter.add(1);
+                    }
+                });
+            }
+        });
+        counter.get()
+    }
+
+    fn hammer_map(map: &dyn SharedMap) -> HashMap<String, u64> {
+        thread::scope(|scope| {
+            for t in 0..THREADS {
+                scope.spawn(move || {
+                    for i in 0..ADDS {
+                        map.add(if (t + i) % 2 == 0 { "even" } else { "odd" }, 1);
+                    }
+                });
+            }
+        });
+        map.snapshot()
+    }
+
+    #[test]
+    fn counters_agree_under_contention() {
+        assert_eq!(hammer_counter(&MutexCounter::default()), THREADS * ADDS);
+        assert_eq!(hammer_counter(&AtomicCounter::default()), THREADS * ADDS);
+        assert_eq!(hammer_counter(&ShardedCounter::new(3)), THREADS * ADDS);
+    }
+
+    #[test]
+    fn maps_agree_under_contention() {
+        let expected = hammer_map(&MutexMap::default());
+        assert_eq!(expected["even"] + expected["odd"], THREADS * ADDS);
+
+        assert_eq!(hammer_map(&RwLockMap::default()), expected);
+        assert_eq!(hammer_map(&ShardedMap::new(3)), expected);
+    }
+
+    #[test]
+    fn missing_keys_read_as_zero() {
+        let maps: Vec<Box<dyn SharedMap>> = vec![
+            Box::new(MutexMap::default()),
+            Box::new(RwLockMap::default()),
+            Box::new(ShardedMap::default()),
+        ];
+
+        for map in maps {
+            map.add("seen", 2);
+            assert_eq!(map.get("seen"), 2);
+            assert_eq!(map.get("unseen"), 0);
+        }
+    }
+}
@@ -0,0 +1,238 @@
+// Parallel versions of the common iterator adaptors over slices (and so over vectors too), so a
+// loop can be spread across threads without hand-writing the Arc<Mutex<_>> bookkeeping from
+// concurrency::run.
+//
+// The work is split by a small work-stealing scheduler. Every worker starts with an equal share
+// of the index range in its own deque. It repeatedly takes the newest range from the back of its
+// deque, splits it in half while it is bigger than the grain size (pushing the other half back),
+// and processes what is left. A worker whose deque runs dry steals the oldest (and so biggest)
+// range from the front of another worker's deque. Busy workers keep splitting, so idle ones
+// always find something to take until the whole range is done.
+//
+// The threads are scoped (thread::scope), which is what lets the closures borrow the slice.
+
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::Mutex;
+use std::thread;
+
+pub struct Scheduler {
+    threads: usize,
+    grain: usize,
+}
+
+impl Default for Scheduler {
+    fn default() -> Scheduler {
+        let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+        Scheduler::new(threads, 1024)
+    }
+}
+
+impl Scheduler {
+    /// `grain` is the largest range a worker processes without splitting it further.
+    pub fn new(threads: usize, grain: usize) -> Scheduler {
+        Scheduler {
+            threads: threads.max(1),
+            grain: grain.max(1),
+        }
+    }
+
+    /// Calls `task` on disjoint ranges that together cover `0..len`, from several threads.
+    pub fn run<F>(&self, len: usize, task: F)
+    where
+        F: Fn(Range<usize>) + Sync,
+    {
+        if len == 0 {
+            return;
+        }
+
+        let threads = self.threads.min(len.div_ceil(self.grain));
+        if threads == 1 {
+            task(0..len);
+            return;
+        }
+
+        let share = len.div_ceil(threads);
+        let deques: Vec<Mutex<VecDeque<Range<usize>>>> = (0..threads)
+            .map(|i| {
+                let start = (i * share).min(len);
+                let end = ((i + 1) * share).min(len);
+                let mut deque = VecDeque::new();
+                deque.push_back(start..end);
+                Mutex::new(deque)
+            })
+            .collect();
+
+        thread::scope(|scope| {
+            for id in 0..threads {
+                let deques = &deques;
+                let task = &task;
+                scope.spawn(move || self.work(id, deques, task));
+            }
+        });
+    }
+
+    fn work<F>(&self, id: usize, deques: &[Mutex<VecDeque<Range<usize>>>], task: &F)
+    where
+        F: Fn(Range<usize>) + Sync,
+    {
+        while let Some(mut range) = pop_own(&deques[id]).or_else(|| steal(id, deques)) {
+            while range.len() > self.grain {
+                let mid = range.start + range.len() / 2;
+                deques[id].lock().unwrap().push_back(mid..range.end);
+                range = range.start..mid;
+            }
+
+            if !range.is_empty() {
+                task(range);
+            }
+        }
+    }
+}
+
+fn pop_own(deque: &Mutex<VecDeque<Range<usize>>>) -> Option<Range<usize>> {
+    deque.lock().unwrap().pop_back()
+}
+
+fn steal(thief: usize, deques: &[Mutex<VecDeque<Range<usize>>>]) -> Option<Range<usize>> {
+    (1..deques.len())
+        .map(|offset| (thief + offset) % deques.len())
+        .find_map(|victim| deques[victim].lock().unwrap().pop_front())
+}
+
+pub trait ParallelSlice<T: Sync> {
+    fn par_map<U, F>(&self, f: F) -> Vec<U>
+    where
+        U: Send,
+        F: Fn(&T) -> U + Sync;
+
+    fn par_filter<F>(&self, predicate: F) -> Vec<&T>
+    where
+        F: Fn(&T) -> bool + Sync;
+
+    /// `op` must be associative and `identity` must leave values unchanged under `op`, because
+    /// the slice is reduced in pieces and the partial results are combined afterwards.
+    fn par_reduce<F>(&self, identity: T, op: F) -> T
+    where
+        T: Clone + Send,
+        F: Fn(T, T) -> T + Sync;
+
+    fn par_for_each<F>(&self, f: F)
+    where
+        F: Fn(&T) + Sync;
+}
+
+impl<T: Sync> ParallelSlice<T> for [T] {
+    fn par_map<U, F>(&self, f: F) -> Vec<U>
+    where
+        U: Send,
+        F: Fn(&T) -> U + Sync,
+    {
+        collect_in_order(self, |chunk| chunk.iter().map(&f).collect())
+    }
+
+    fn par_filter<F>(&self, predicate: F) -> Vec<&T>
+    where
+        F: Fn(&T) -> bool + Sync,
+    {
+        collect_in_order(self, |chunk| chunk.iter().filter(|x| predicate(x)).collect())
+    }
+
+    fn par_reduce<F>(&self, identity: T, op: F) -> T
+    where
+        T: Clone + Send,
+        F: Fn(T, T) -> T + Sync,
+    {
+        let identity = Mutex::new(identity);
+        let partials = collect_in_order(self, |chunk| {
+            let start = identity.lock().unwrap().clone();
+            vec![chunk.iter().cloned().fold(start, &op)]
+        });
+
+        partials
+            .into_iter()
+            .fold(identity.into_inner().unwrap(), &op)
+    }
+
+    fn par_for_each<F>(&self, f: F)
+    where
+        F: Fn(&T) + Sync,
+    {
+        Scheduler::default().run(self.len(), |range| self[range].iter().for_each(&f));
+    }
+}
+
+// Runs `chunk_fn` over pieces of the slice in parallel and concatenates the outputs in slice
+// order, whatever order the pieces finished in.
+fn collect_in_order<'a, T, U, F>(items: &'a [T], chunk_fn: F) -> Vec<U>
+where
+    T: Sync,
+    U: Send,
+    F: Fn(&'a [T]) -> Vec<U> + Sync,
+{
+    let pieces = Mutex::new(vec![]);
+
+    Scheduler::default().run(items.len(), |range| {
+        let start = range.start;
+        let output = chunk_fn(&items[range]);
+        pieces.lock().unwrap().push((start, output));
+    });
+
+    let mut pieces = pieces.into_inner().unwrap();
+    pieces.sort_by_key(|(start, _)| *start);
+    pieces.into_iter().flat_map(|(_, output)| output).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn scheduler_covers_every_index_once() {
+        let visits: Vec<AtomicUsize> = (0..10_000).map(|_| AtomicUsize::new(0)).collect();
+
+        Scheduler::new(8, 7).run(visits.len(), |range| {
+            for i in range {
+                visits[i].fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        assert!(visits.iter().all(|v| v.load(Ordering::SeqCst) == 1));
+    }
+
+    #[test]
+    fn par_map_keeps_the_original_order() {
+        let v: Vec<u64> = (0..50_000).collect();
+
+        assert_eq!(v.par_map(|x| x * 2), v.iter().map(|x| x * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn par_filter_matches_sequential_filter() {
+        let v: Vec<u32> = (0..20_000).collect();
+
+        let expected: Vec<&u32> = v.iter().filter(|x| *x % 3 == 0).collect();
+        assert_eq!(v.par_filter(|x| x % 3 == 0), expected);
+    }
+
+    #[test]
+    fn par_reduce_matches_sequential_sum() {
+        let v: Vec<u64> = (1..=100_000).collect();
+
+        assert_eq!(v.par_reduce(0, |a, b| a + b), 5_000_050_000);
+        assert_eq!(Vec::<u64>::new().par_reduce(0, |a, b| a + b), 0);
+    }
+
+    #[test]
+    fn par_for_each_visits_every_item() {
+        let v: Vec<usize> = (0..10_000).collect();
+        let total = AtomicUsize::new(0);
+
+        v.par_for_each(|x| {
+            total.fetch_add(*x, Ordering::SeqCst);
+        });
+
+        assert_eq!(total.load(Ordering::SeqCst), v.iter().sum::<usize>());
+    }
+}
@@ -0,0 +1,212 @@
+// A fixed set of worker threads that take jobs from a shared mpsc queue, instead of spawning a
+// new OS thread for every small piece of work.
+//
+// The receiving end of the queue is shared between the workers as Arc<Mutex<Receiver<Job>>>:
+// whichever worker grabs the lock first takes the next job. Every job runs inside
+// panic::catch_unwind, so a panicking job is reported through its JobHandle and the worker that
+// ran it carries on with the next one.
+
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, mpsc, Mutex};
+use std::thread;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+pub struct ThreadPool {
+    workers: Vec<Worker>,
+    sender: Option<mpsc::Sender<Job>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PoolCreationError;
+
+impl fmt::Display for PoolCreationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a thread pool needs at least one worker")
+    }
+}
+
+impl std::error::Error for PoolCreationError {}
+
+impl ThreadPool {
+    /// Create a new ThreadPool.
+    ///
+    /// The size is the number of threads in the pool.
+    ///
+    /// # Panics
+    ///
+    /// The `new` function will panic if the size is zero.
+    pub fn new(size: usize) -> ThreadPool {
+        match ThreadPool::build(size) {
+            Ok(pool) => pool,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn build(size: usize) -> Result<ThreadPool, PoolCreationError> {
+        if size == 0 {
+            return Err(PoolCreationError);
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let mut workers = Vec::with_capacity(size);
+        for id in 0..size {
+            workers.push(Worker::new(id, Arc::clone(&receiver)));
+        }
+
+        Ok(ThreadPool {
+            workers,
+            sender: Some(sender),
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.workers.len()
+    }
+
+    pub fn execute<F, T>(&self, f: F) -> JobHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (result_sender, result_receiver) = mpsc::channel();
+
+        let job = Box::new(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(f));
+            // The caller may have dropped the handle because it doesn't care about the result.
+            let _ = result_sender.send(result);
+        });
+
+        self.sender
+            .as_ref()
+            .expect("the sender is only taken when the pool is dropped")
+            .send(job)
+            .expect("workers only stop once the sender is dropped");
+
+        JobHandle {
+            receiver: result_receiver,
+        }
+    }
+}
+
+// Dropping the sender closes the queue. Each worker finishes whatever is still queued, sees the
+// channel is closed and exits, so joining them waits for every job that was submitted.
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        drop(self.sender.take());
+
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                thread.join().unwrap();
+            }
+        }
+    }
+}
+
+/// The result of a job submitted with `ThreadPool::execute`, mirroring `thread::JoinHandle`.
+pub struct JobHandle<T> {
+    receiver: mpsc::Receiver<thread::Result<T>>,
+}
+
+impl<T> JobHandle<T> {
+    /// Waits for the job to finish. Returns `Err` with the panic payload if the job panicked.
+    pub fn join(self) -> thread::Result<T> {
+        match self.receiver.recv() {
+            Ok(result) => result,
+            Err(_) => Err(Box::new("the job was dropped before it ran")),
+        }
+    }
+}
+
+struct Worker {
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Worker {
+    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Worker {
+        let thread = thread::Builder::new()
+            .name(format!("pool-worker-{}", id))
+            .spawn(move || loop {
+                // The lock is released at the end of this statement, before the job runs, so
+                // other workers can pick up jobs in the meantime.
+                let message = receiver.lock().unwrap().recv();
+
+                match message {
+                    Ok(job) => job(),
+                    Err(_) => break,
+                }
+            })
+            .expect("failed to spawn a pool worker");
+
+        Worker {
+            thread: Some(thread),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn returns_job_results() {
+        let pool = ThreadPool::new(4);
+
+        let handles: Vec<_> = (0..10).map(|i| pool.execute(move || i * i)).collect();
+        let results: Vec<i32> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        assert_eq!(results, vec![0, 1, 4, 9, 16, 25, 36, 49, 64, 81]);
+    }
+
+    #[test]
+    fn single_worker_runs_jobs_in_submission_order() {
+        let pool = ThreadPool::new(1);
+        let order = Arc::new(Mutex::new(vec![]));
+
+        for i in 0..5 {
+            let order = Arc::clone(&order);
+            pool.execute(move || order.lock().unwrap().push(i));
+        }
+        drop(pool);
+
+        assert_eq!(*order.lock().unwrap(), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn panicking_job_does_not_kill_the_worker() {
+        let pool = ThreadPool::new(1);
+
+        let failed = pool.execute(|| -> i32 { panic!("job failed") });
+        let next = pool.execute(|| 42);
+
+        let payload = failed.join().unwrap_err();
+        assert_eq!(payload.downcast_ref::<&str>(), Some(&"job failed"));
+        assert_eq!(next.join().unwrap(), 42);
+    }
+
+    #[test]
+    fn drop_waits_for_queued_jobs() {
+        let pool = ThreadPool::new(2);
+        let counter = Arc::new(Mutex::new(0));
+
+        for _ in 0..8 {
+            let counter = Arc::clone(&counter);
+            pool.execute(move || {
+                thread::sleep(Duration::from_millis(5));
+                *counter.lock().unwrap() += 1;
+            });
+        }
+        drop(pool);
+
+        assert_eq!(*counter.lock().unwrap(), 8);
+    }
+
+    #[test]
+    fn zero_workers_is_an_error() {
+        assert_eq!(ThreadPool::build(0).err(), Some(PoolCreationError));
+    }
+}
@@ -0,0 +1,419 @@
+// A Mutex wrapper for debugging lock ordering.
+//
+// Two threads deadlock when one holds `a` and waits for `b` while the other holds `b` and waits
+// for `a`. That only happens with unlucky timing, but the ingredient is visible on every run:
+// the program takes `a` then `b` in one place and `b` then `a` in another. TrackedMutex looks
+// for exactly that.
+//
+// Each thread keeps a list of the tracked locks it currently holds. Taking lock `b` while
+// holding `a` adds the edge a -> b to a lock-order graph, remembering the call site. If `a` can
+// already be reached from `b` in that graph, the new edge closes a cycle and a DeadlockReport is
+// recorded with the call sites of both orders. The check happens before blocking, so the report
+// is made even on runs where the timing works out.
+//
+// Taking a lock the thread already holds is the shortest cycle of all, a -> a, and it isn't a
+// maybe: a Mutex taken twice by one thread never comes back. So instead of blocking, `lock`
+// returns LockError::Deadlock with the report. This check is cheap and happens in every build.
+//
+// Reports are kept on the LockGraph for the caller to look at through `reports()`.
+//
+// Tracking costs a global lock per acquisition, so it only happens in debug builds; in release
+// builds TrackedMutex behaves like a plain Mutex. Either way a poisoned lock comes back as a
+// LockError naming the lock, instead of a PoisonError to unwrap.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::panic::Location;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static HELD: RefCell<Vec<Held>> = const { RefCell::new(Vec::new()) };
+}
+
+struct Held {
+    graph: usize,
+    lock: usize,
+    name: &'static str,
+    site: &'static Location<'static>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeadlockReport {
+    /// The locks involved, starting and ending with the lock held during the new acquisition.
+    pub cycle: Vec<&'static str>,
+    /// Where the lock that closed the cycle was taken.
+    pub acquired_at: &'static Location<'static>,
+    /// Where the locks were previously taken in the opposite order, or for a lock taken again by
+    /// the thread holding it, where it was first taken.
+    pub conflicting_at: Vec<&'static Location<'static>>,
+}
+
+impl fmt::Display for DeadlockReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let [name, _] = self.cycle[..] {
+            return write!(
+                f,
+                "deadlock: lock `{}` taken at {} while this thread already holds it from {}",
+                name, self.acquired_at, self.conflicting_at[0]
+            );
+        }
+
+        write!(
+            f,
+            "potential deadlock: lock order {} at {}, but the opposite order was taken at ",
+            self.cycle.join(" -> "),
+            self.acquired_at
+        )?;
+
+        let sites: Vec<String> = self.conflicting_at.iter().map(|site| site.to_string()).collect();
+        write!(f, "{}", sites.join(", "))
+    }
+}
+
+struct Edge {
+    to: usize,
+    site: &'static Location<'static>,
+}
+
+#[derive(Default)]
+struct Graph {
+    names: HashMap<usize, &'static str>,
+    edges: HashMap<usize, Vec<Edge>>,
+    reports: Vec<DeadlockReport>,
+}
+
+impl Graph {
+    // Depth-first search for a path between two locks, returning the edges it follows.
+    fn path(&self, from: usize, to: usize) -> Option<Vec<(usize, &'static Location<'static>)>> {
+        let mut visited = HashSet::new();
+        let mut stack = vec![(from, vec![])];
+
+        while let Some((node, path)) = stack.pop() {
+            if node == to {
+                return Some(path);
+            }
+            if !visited.insert(node) {
+                continue;
+            }
+            for edge in self.edges.get(&node).into_iter().flatten() {
+                let mut next = path.clone();
+                next.push((edge.to, edge.site));
+                stack.push((edge.to, next));
+            }
+        }
+
+        None
+    }
+}
+
+/// Where the lock-order edges and deadlock reports of a group of TrackedMutexes are kept.
+pub struct LockGraph {
+    id: usize,
+    inner: Mutex<Graph>,
+}
+
+impl LockGraph {
+    pub fn new() -> Arc<LockGraph> {
+        Arc::new(LockGraph {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            inner: Mutex::new(Graph::default()),
+        })
+    }
+
+    /// The graph used by `TrackedMutex::new`.
+    pub fn global() -> Arc<LockGraph> {
+        static GLOBAL: OnceLock<Arc<LockGraph>> = OnceLock::new();
+        Arc::clone(GLOBAL.get_or_init(LockGraph::new))
+    }
+
+    pub fn reports(&self) -> Vec<DeadlockReport> {
+        self.graph().reports.clone()
+    }
+
+    fn graph(&self) -> MutexGuard<'_, Graph> {
+        // Nothing panics while the graph is locked, so poisoning can't leave it half updated.
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn record(&self, lock: usize, name: &'static str, site: &'static Location<'static>) {
+        let mut graph = self.graph();
+        graph.names.insert(lock, name);
+
+        let held: Vec<(usize, &'static str)> = HELD.with(|held| {
+            held.borrow()
+                .iter()
+                .filter(|h| h.graph == self.id)
+                .map(|h| (h.lock, h.name))
+                .collect()
+        });
+
+        for (from, from_name) in held {
+            let known = graph
+                .edges
+                .get(&from)
+                .is_some_and(|edges| edges.iter().any(|edge| edge.to == lock));
+            if known {
+                continue;
+            }
+
+            if let Some(path) = graph.path(lock, from) {
+                let mut cycle = vec![from_name, name];
+                cycle.extend(path.iter().map(|(node, _)| graph.names[node]));
+
+                let report = DeadlockReport {
+                    cycle,
+                    acquired_at: site,
+                    conflicting_at: path.iter().map(|(_, site)| *site).collect(),
+                };
+                graph.reports.push(report);
+            }
+
+            graph.edges.entry(from).or_default().push(Edge { to: lock, site });
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LockError {
+    /// Another thread panicked while holding the lock, so the data may be inconsistent.
+    Poisoned {
+        name: &'static str,
+        site: &'static Location<'static>,
+    },
+    /// The thread already holds the lock, so waiting for it would never end.
+    Deadlock(DeadlockReport),
+}
+
+impl fmt::Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LockError::Poisoned { name, site } => write!(
+                f,
+                "lock `{}` taken at {} is poisoned: a thread panicked while holding it",
+                name, site
+            ),
+            LockError::Deadlock(report) => write!(f, "{}", report),
+        }
+    }
+}
+
+impl std::error::Error for LockError {}
+
+pub struct TrackedMutex<T> {
+    id: usize,
+    name: &'static str,
+    graph: Arc<LockGraph>,
+    inner: Mutex<T>,
+}
+
+impl<T> TrackedMutex<T> {
+    pub fn new(name: &'static str, value: T) -> TrackedMutex<T> {
+        TrackedMutex::new_in(&LockGraph::global(), name, value)
+    }
+
+    pub fn new_in(graph: &Arc<LockGraph>, name: &'static str, value: T) -> TrackedMutex<T> {
+        TrackedMutex {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            name,
+            graph: Arc::clone(graph),
+            inner: Mutex::new(value),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    #[track_caller]
+    pub fn lock(&self) -> Result<TrackedGuard<'_, T>, LockError> {
+        let site = Location::caller();
+
+        let held_at = HELD.with(|held| held.borrow().iter().find(|h| h.lock == self.id).map(|h| h.site));
+        if let Some(held_at) = held_at {
+            let report = DeadlockReport {
+                cycle: vec![self.name, self.name],
+                acquired_at: site,
+                conflicting_at: vec![held_at],
+            };
+            self.graph.graph().reports.push(report.clone());
+            return Err(LockError::Deadlock(report));
+        }
+
+        if cfg!(debug_assertions) {
+            self.graph.record(self.id, self.name, site);
+        }
+
+        let guard = self.inner.lock().map_err(|_| LockError::Poisoned {
+            name: self.name,
+            site,
+        })?;
+
+        HELD.with(|held| {
+            held.borrow_mut().push(Held {
+                graph: self.graph.id,
+                lock: self.id,
+                name: self.name,
+                site,
+            })
+        });
+
+        Ok(TrackedGuard {
+            id: self.id,
+            guard,
+        })
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for TrackedMutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TrackedMutex")
+            .field("name", &self.name)
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+pub struct TrackedGuard<'a, T> {
+    id: usize,
+    guard: MutexGuard<'a, T>,
+}
+
+impl<T> Deref for TrackedGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for TrackedGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+// Guards don't have to be dropped in the order they were taken, so remove this lock wherever it
+// is in the list.
+impl<T> Drop for TrackedGuard<'_, T> {
+    fn drop(&mut self) {
+        HELD.with(|held| {
+            let mut held = held.borrow_mut();
+            if let Some(position) = held.iter().rposition(|h| h.lock == self.id) {
+                held.remove(position);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn consistent_order_reports_nothing() {
+        let graph = LockGraph::new();
+        let a = TrackedMutex::new_in(&graph, "a", 0);
+        let b = TrackedMutex::new_in(&graph, "b", 0);
+
+        for _ in 0..3 {
+            let _a = a.lock().unwrap();
+            let _b = b.lock().unwrap();
+        }
+
+        assert!(graph.reports().is_empty());
+    }
+
+    #[test]
+    fn opposite_order_on_another_thread_is_reported() {
+        let graph = LockGraph::new();
+        let a = Arc::new(TrackedMutex::new_in(&graph, "a", 0));
+        let b = Arc::new(TrackedMutex::new_in(&graph, "b", 0));
+
+        {
+            let _a = a.lock().unwrap();
+            let _b = b.lock().unwrap();
+        }
+
+        let (a2, b2) = (Arc::clone(&a), Arc::clone(&b));
+        thread::spawn(move || {
+            let _b = b2.lock().unwrap();
+            let _a = a2.lock().unwrap();
+        })
+        .join()
+        .unwrap();
+
+        let reports = graph.reports();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].cycle, vec!["b", "a", "b"]);
+        assert_eq!(reports[0].conflicting_at.len(), 1);
+        assert_ne!(reports[0].acquired_at, reports[0].conflicting_at[0]);
+        assert!(reports[0].to_string().contains(file!()));
+    }
+
+    #[test]
+    fn longer_cycles_are_found() {
+        let graph = LockGraph::new();
+        let a = TrackedMutex::new_in(&graph, "a", ());
+        let b = TrackedMutex::new_in(&graph, "b", ());
+        let c = TrackedMutex::new_in(&graph, "c", ());
+
+        {
+            let _a = a.lock().unwrap();
+            let _b = b.lock().unwrap();
+        }
+        {
+            let _b = b.lock().unwrap();
+            let _c = c.lock().unwrap();
+        }
+        {
+            let _c = c.lock().unwrap();
+            let _a = a.lock().unwrap();
+        }
+
+        assert_eq!(graph.reports()[0].cycle, vec!["c", "a", "b", "c"]);
+    }
+
+    #[test]
+    fn taking_a_held_lock_again_is_reported() {
+        let graph = LockGraph::new();
+        let a = TrackedMutex::new_in(&graph, "a", ());
+
+        let _a = a.lock().unwrap();
+        let again = match a.lock() {
+            Err(LockError::Deadlock(report)) => report,
+            Err(e) => panic!("expected a deadlock, got {}", e),
+            Ok(_) => panic!("expected a deadlock"),
+        };
+
+        assert_eq!(again.cycle, vec!["a", "a"]);
+        assert_eq!(again.acquired_at.line(), line!() - 7);
+        assert_eq!(again.conflicting_at[0].line(), line!() - 9);
+        assert!(again.to_string().starts_with("deadlock: lock `a` taken at "));
+        assert_eq!(graph.reports().len(), 1);
+    }
+
+    #[test]
+    fn poisoned_lock_is_an_error() {
+        let graph = LockGraph::new();
+        let counter = Arc::new(TrackedMutex::new_in(&graph, "counter", 0));
+
+        let c = Arc::clone(&counter);
+        let result = thread::spawn(move || {
+            let _num = c.lock().unwrap();
+            panic!("oops");
+        })
+        .join();
+        assert!(result.is_err());
+
+        match counter.lock() {
+            Err(LockError::Poisoned { name, .. }) => assert_eq!(name, "counter"),
+            _ => panic!("expected the lock to be poisoned"),
+        };
+    }
+}
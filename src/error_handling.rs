@@ -1,6 +1,19 @@
+pub mod error;
+pub mod fs;
+pub mod profile;
+pub mod recovery;
+
 use std::fs::File;
-use std::io;
-use std::io::{ErrorKind, Read};
+use std::io::Read;
+use std::path::Path;
+
+use error::{report, Context, Error, Result};
+use fs::{FileSystem, RealFileSystem};
+use recovery::{Outcome, Recovery, Retry};
+
+use crate::structs::{build_user, User};
+
+const USERNAME_FILE: &str = "hello.txt";
 
 pub fn run(){
     // panic!("crash and burn");
@@ -18,20 +31,40 @@ pub fn run(){
     // };
 
     // Matching on Different Errors
-    let f = File::open("hello.txt");
+    // let f = File::open("hello.txt");
+    //
+    // let f = match f {
+    //     Ok(file) => file,
+    //     Err(error) => match error.kind() {
+    //         ErrorKind::NotFound => match File::create("hello.txt") {
+    //             Ok(fc) => fc,
+    //             Err(e) => panic!("Problem creating the file: {:?}", e),
+    //         },
+    //         other_error => {
+    //             panic!("Problem opening the file: {:?}", other_error)
+    //         }
+    //     },
+    // };
 
-    let f = match f {
-        Ok(file) => file,
-        Err(error) => match error.kind() {
-            ErrorKind::NotFound => match File::create("hello.txt") {
-                Ok(fc) => fc,
-                Err(e) => panic!("Problem creating the file: {:?}", e),
-            },
-            other_error => {
-                panic!("Problem opening the file: {:?}", other_error)
-            }
-        },
-    };
+    // The same, with a recovery policy instead of a panic: retry errors that may go away, create
+    // the file with a guest profile if it's missing, and carry on without saving if it can't be
+    // created.
+    let guest = build_user(String::from("guest@example.com"), String::from("guest"));
+    let recovery = Recovery::new()
+        .retry(Retry::default())
+        .create_with(&profile::format(&guest))
+        .read_only_on_failure();
+    match recovery.read(&RealFileSystem, USERNAME_FILE) {
+        Ok(recovered) if recovered.outcome == Outcome::Created => println!("created {}", USERNAME_FILE),
+        Ok(recovered) if recovered.is_read_only() => println!("{} can't be created; carrying on", USERNAME_FILE),
+        Ok(_) => {}
+        Err(e) => println!("{}", report(&e)),
+    }
+
+    match read_username_from_file_question_mark(&RealFileSystem) {
+        Ok(user) => println!("username: {}", user.username),
+        Err(e) => println!("{}", report(&e)),
+    }
 
     // Using match works well enough, but it can be a bit verbose and doesn’t always communicate
     // intent well. The Result<T, E> type has many helper methods defined on it to do various, more
@@ -44,7 +77,7 @@ pub fn run(){
     // Similarly, the expect method lets us also choose the panic! error message. Using expect
     // instead of unwrap and providing good error messages can convey your intent and make tracking
     // down the source of a panic easier. The syntax of expect looks like this:
-    let f = File::open("bye.txt").expect("Failed to open bye.txt");
+    let _f = File::open("bye.txt").expect("Failed to open bye.txt");
 }
 
 // Propagating Errors
@@ -54,27 +87,115 @@ pub fn run(){
 // where there might be more information or logic that dictates how the error should be handled than
 // what you have available in the context of your code.
 
-fn read_username_from_file() -> Result<String, io::Error> {
-    let f = File::open("hello.txt");
+// Both take the filesystem to read from, so they can be tested against a MemoryFileSystem, and
+// parse the file as a profile (see profile.rs) to give back the whole User.
+pub fn read_username_from_file<F: FileSystem + ?Sized>(fs: &F) -> Result<User> {
+    let f = fs.open(Path::new(USERNAME_FILE));
 
     let mut f = match f {
         Ok(file) => file,
-        Err(e) => return Err(e),
+        Err(e) => return Err(Error::io(USERNAME_FILE, "open", e)),
     };
 
     let mut s = String::new();
 
     match f.read_to_string(&mut s) {
-        Ok(_) => Ok(s),
-        Err(e) => Err(e),
+        Ok(_) => profile::parse(&s).map_err(|e| e.in_file(USERNAME_FILE)),
+        Err(e) => Err(Error::io(USERNAME_FILE, "read", e)),
     }
 }
 
 // This pattern of propagating errors is so common in Rust that Rust provides the question mark
-// operator ? to make this easier.
-fn read_username_from_file_question_mark() -> Result<String, io::Error> {
-    let mut f = File::open("hello.txt")?;
+// operator ? to make this easier. `?` also converts the error into the function's error type
+// through From, and `context` says what was being attempted when it happened.
+pub fn read_username_from_file_question_mark<F: FileSystem + ?Sized>(fs: &F) -> Result<User> {
+    let mut f = fs.open(Path::new(USERNAME_FILE))
+        .map_err(|e| Error::io(USERNAME_FILE, "open", e))
+        .context("couldn't load the username")?;
     let mut s = String::new();
-    f.read_to_string(&mut s)?;
-    Ok(s)
-}
\ No newline at end of file
+    f.read_to_string(&mut s)
+        .map_err(|e| Error::io(USERNAME_FILE, "read", e))
+        .context("couldn't load the username")?;
+    profile::parse(&s)
+        .map_err(|e| e.in_file(USERNAME_FILE))
+        .context("couldn't load the username")
+}
+
+// Creating Custom Types for Validation
+// A function that takes a Guess can rely on the value being between 1 and 100, because the only
+// way to make one checks it. Being out of range is a bug in the calling code, not something it
+// could recover from, so `new` panics instead of returning a Result.
+pub struct Guess {
+    value: i32,
+}
+
+impl Guess {
+    pub fn new(value: i32) -> Guess {
+        if !(1..=100).contains(&value) {
+            panic!("Guess value must be between 1 and 100, got {}.", value);
+        }
+
+        Guess { value }
+    }
+
+    pub fn value(&self) -> i32 {
+        self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fs::{Fault, MemoryFileSystem};
+    use std::io::ErrorKind;
+
+    // Runs both versions, which have to agree apart from the context the second one adds.
+    fn read_username(fs: &MemoryFileSystem) -> Result<User> {
+        let plain = read_username_from_file(fs);
+        let with_context = read_username_from_file_question_mark(fs);
+        match (&plain, &with_context) {
+            (Ok(a), Ok(b)) => assert_eq!(a, b),
+            (Err(a), Err(b)) => assert_eq!(a.to_string(), b.root().to_string()),
+            _ => panic!("{:?} and {:?} disagree", plain, with_context),
+        }
+        plain
+    }
+
+    #[test]
+    fn the_profile_is_read_through_short_reads() {
+        let ferris = build_user(String::from("ferris@example.com"), String::from("ferris"));
+        let fs = MemoryFileSystem::new().with_file(USERNAME_FILE, &profile::format(&ferris));
+        assert_eq!(read_username(&fs).unwrap(), ferris);
+
+        fs.inject(USERNAME_FILE, Fault::PartialReads { chunk: 1 });
+        assert_eq!(read_username(&fs).unwrap(), ferris);
+    }
+
+    #[test]
+    fn failures_say_what_went_wrong_with_which_file() {
+        let fs = MemoryFileSystem::new();
+        assert!(read_username(&fs).unwrap_err().is_not_found());
+
+        fs.insert(USERNAME_FILE, "ferris");
+        fs.inject(USERNAME_FILE, Fault::PermissionDenied);
+        let error = read_username(&fs).unwrap_err();
+        assert_eq!(report(&error), "error: couldn't open hello.txt\ncaused by: permission denied");
+
+        fs.clear_faults(USERNAME_FILE);
+        fs.inject(USERNAME_FILE, Fault::BrokenRead { after: 2, kind: ErrorKind::ConnectionReset });
+        let error = read_username_from_file_question_mark(&fs).unwrap_err();
+        assert_eq!(
+            report(&error),
+            "error: couldn't load the username\n\
+             caused by: couldn't read hello.txt\n\
+             caused by: connection reset"
+        );
+
+        fs.clear_faults(USERNAME_FILE);
+        fs.insert(USERNAME_FILE, "username = ferris\nemail: ferris@example.com\n");
+        assert_eq!(
+            read_username(&fs).unwrap_err().to_string(),
+            "hello.txt:2:26: expected `key = value`"
+        );
+    }
+}
@@ -0,0 +1,272 @@
+// One error type for everything the error_handling lesson can get wrong, instead of handing a
+// bare io::Error back to the caller. Each variant says what kind of failure it was and carries
+// the file it happened with, so the message can name it:
+//
+//     error: couldn't load the username
+//     caused by: couldn't read hello.txt
+//     caused by: Permission denied (os error 13)
+//
+// The underlying error is kept as the `source()`, not pasted into the message, so `report` can
+// walk the chain and print each cause on its own line.
+
+use std::error;
+use std::fmt;
+use std::io::{self, ErrorKind};
+use std::num::ParseIntError;
+use std::path::{Path, PathBuf};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+type Source = Box<dyn error::Error + Send + Sync + 'static>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// An I/O operation on `path` failed. `action` is what was being done: "open", "read"...
+    Io {
+        path: Option<PathBuf>,
+        action: &'static str,
+        source: io::Error,
+    },
+    NotFound {
+        path: PathBuf,
+    },
+    /// The contents couldn't be understood. `position` is the line and column, counting from 1.
+    Parse {
+        path: Option<PathBuf>,
+        position: Option<(usize, usize)>,
+        message: String,
+        source: Option<Source>,
+    },
+    /// The contents were understood but aren't acceptable.
+    Validation {
+        path: Option<PathBuf>,
+        field: &'static str,
+        message: String,
+    },
+    /// What was being attempted when `source` happened.
+    Context {
+        context: String,
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// Wraps an I/O error from doing `action` on `path`. A missing file becomes NotFound.
+    pub fn io<P: AsRef<Path>>(path: P, action: &'static str, source: io::Error) -> Error {
+        let path = path.as_ref().to_path_buf();
+        match source.kind() {
+            ErrorKind::NotFound => Error::NotFound { path },
+            _ => Error::Io {
+                path: Some(path),
+                action,
+                source,
+            },
+        }
+    }
+
+    pub fn parse(line: usize, column: usize, message: impl Into<String>) -> Error {
+        Error::Parse {
+            path: None,
+            position: Some((line, column)),
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    pub fn validation(field: &'static str, message: impl Into<String>) -> Error {
+        Error::Validation {
+            path: None,
+            field,
+            message: message.into(),
+        }
+    }
+
+    /// Fills in the file the error happened with, if it doesn't name one already.
+    pub fn in_file<P: AsRef<Path>>(self, file: P) -> Error {
+        match self {
+            Error::Context { context, source } => Error::Context {
+                context,
+                source: Box::new(source.in_file(file)),
+            },
+            mut error => {
+                if let Error::Io { path, .. } | Error::Parse { path, .. } | Error::Validation { path, .. } =
+                    &mut error
+                {
+                    path.get_or_insert_with(|| file.as_ref().to_path_buf());
+                }
+                error
+            }
+        }
+    }
+
+    pub fn context(self, context: impl Into<String>) -> Error {
+        Error::Context {
+            context: context.into(),
+            source: Box::new(self),
+        }
+    }
+
+    /// The file the error happened with, looking through any context.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::Io { path, .. } | Error::Parse { path, .. } | Error::Validation { path, .. } => {
+                path.as_deref()
+            }
+            Error::NotFound { path } => Some(path),
+            Error::Context { source, .. } => source.path(),
+        }
+    }
+
+    /// The error under any context.
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::Context { source, .. } => source.root(),
+            _ => self,
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        matches!(self.root(), Error::NotFound { .. })
+    }
+}
+
+// "hello.txt: " or nothing.
+struct InFile<'a>(&'a Option<PathBuf>);
+
+impl fmt::Display for InFile<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(path) => write!(f, "{}: ", path.display()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path: Some(path), action, .. } => {
+                write!(f, "couldn't {} {}", action, path.display())
+            }
+            Error::Io { path: None, action, .. } => write!(f, "couldn't {}", action),
+            Error::NotFound { path } => write!(f, "{} doesn't exist", path.display()),
+            Error::Parse { path, position, message, .. } => {
+                match (path, position) {
+                    (Some(path), Some((line, column))) => {
+                        write!(f, "{}:{}:{}: ", path.display(), line, column)?
+                    }
+                    (None, Some((line, column))) => write!(f, "line {}, column {}: ", line, column)?,
+                    (path, None) => write!(f, "{}", InFile(path))?,
+                }
+                write!(f, "{}", message)
+            }
+            Error::Validation { path, field, message } => {
+                write!(f, "{}invalid {}: {}", InFile(path), field, message)
+            }
+            Error::Context { context, .. } => write!(f, "{}", context),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Parse { source: Some(source), .. } => Some(source.as_ref()),
+            Error::Context { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// An io::Error from `?` without a path. Use `Error::io` where the path is known.
+impl From<io::Error> for Error {
+    fn from(source: io::Error) -> Error {
+        Error::Io {
+            path: None,
+            action: "do I/O",
+            source,
+        }
+    }
+}
+
+impl From<ParseIntError> for Error {
+    fn from(source: ParseIntError) -> Error {
+        Error::Parse {
+            path: None,
+            position: None,
+            message: String::from("invalid number"),
+            source: Some(Box::new(source)),
+        }
+    }
+}
+
+/// Adds context to the error of a Result, as in `load().context("couldn't load the profile")?`.
+pub trait Context<T> {
+    fn context(self, context: impl Into<String>) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> Context<T> for std::result::Result<T, E> {
+    fn context(self, context: impl Into<String>) -> Result<T> {
+        self.map_err(|e| e.into().context(context))
+    }
+}
+
+/// The error followed by each of its causes, one per line.
+pub fn report(error: &dyn error::Error) -> String {
+    let mut report = format!("error: {}", error);
+    let mut cause = error.source();
+    while let Some(e) = cause {
+        report.push_str(&format!("\ncaused by: {}", e));
+        cause = e.source();
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_files_are_not_found_and_others_keep_their_cause() {
+        let missing = Error::io("hello.txt", "open", io::Error::from(ErrorKind::NotFound));
+        assert!(missing.is_not_found());
+        assert_eq!(missing.to_string(), "hello.txt doesn't exist");
+
+        let denied = Error::io("hello.txt", "read", io::Error::new(ErrorKind::PermissionDenied, "denied"));
+        assert!(!denied.is_not_found());
+        assert_eq!(denied.path(), Some(Path::new("hello.txt")));
+        assert_eq!(report(&denied), "error: couldn't read hello.txt\ncaused by: denied");
+    }
+
+    #[test]
+    fn reports_walk_the_whole_chain() {
+        fn count(text: &str) -> Result<u64> {
+            Ok(text.trim().parse::<u64>()?)
+        }
+
+        let error = count("many")
+            .map_err(|e| e.in_file("profile.txt"))
+            .context("couldn't load the profile")
+            .unwrap_err();
+
+        assert!(matches!(error.root(), Error::Parse { .. }));
+        assert_eq!(error.path(), Some(Path::new("profile.txt")));
+        assert_eq!(
+            report(&error),
+            "error: couldn't load the profile\n\
+             caused by: profile.txt: invalid number\n\
+             caused by: invalid digit found in string"
+        );
+    }
+
+    #[test]
+    fn parse_and_validation_errors_name_where_they_are() {
+        let parse = Error::parse(3, 7, "expected `=`").in_file("profile.txt");
+        assert_eq!(parse.to_string(), "profile.txt:3:7: expected `=`");
+        assert_eq!(Error::parse(3, 7, "expected `=`").to_string(), "line 3, column 7: expected `=`");
+
+        let invalid = Error::validation("username", "can't be empty").context("loading");
+        assert_eq!(invalid.in_file("hello.txt").root().to_string(), "hello.txt: invalid username: can't be empty");
+    }
+}
@@ -0,0 +1,257 @@
+// The file operations error_handling needs, behind a trait so code that opens files can be
+// handed something other than the real filesystem.
+//
+// RealFileSystem goes to the disk. MemoryFileSystem keeps files in a map, and faults can be
+// injected into it per path to make opening, reading or writing a file fail in a chosen way:
+// permission denied, a full disk, short reads or a read that breaks off partway. Code written
+// against the trait can be tested against every one of those without touching the disk.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+pub trait FileSystem {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>>;
+
+    /// Creates the file, or truncates it if it exists.
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + '_>>;
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        let mut s = String::new();
+        self.open(path)?.read_to_string(&mut s)?;
+        Ok(s)
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        let mut file = self.create(path)?;
+        file.write_all(contents.as_bytes())?;
+        file.flush()
+    }
+}
+
+/// The filesystem of the machine, through std::fs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealFileSystem;
+
+impl FileSystem for RealFileSystem {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(File::open(path)?))
+    }
+
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + '_>> {
+        Ok(Box::new(File::create(path)?))
+    }
+}
+
+/// Something for a MemoryFileSystem to get wrong with one file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The next open or create fails with this error. Queued faults are used up in order.
+    Fail(ErrorKind),
+    /// Opening the file (if it exists) and creating it fail with PermissionDenied.
+    PermissionDenied,
+    /// Writes fail with StorageFull once the file holds `capacity` bytes.
+    DiskFull { capacity: usize },
+    /// Each read returns at most `chunk` bytes, as reads from pipes and sockets can.
+    PartialReads { chunk: usize },
+    /// Reading fails with `kind` after `after` bytes.
+    BrokenRead { after: usize, kind: ErrorKind },
+}
+
+/// Files kept in memory, with faults injected per path, for tests that must not depend on what
+/// is in the working directory.
+#[derive(Debug, Default)]
+pub struct MemoryFileSystem {
+    files: RefCell<HashMap<PathBuf, Vec<u8>>>,
+    faults: RefCell<HashMap<PathBuf, Vec<Fault>>>,
+}
+
+impl MemoryFileSystem {
+    pub fn new() -> MemoryFileSystem {
+        MemoryFileSystem::default()
+    }
+
+    pub fn with_file<P: AsRef<Path>>(self, path: P, contents: &str) -> MemoryFileSystem {
+        self.insert(path, contents);
+        self
+    }
+
+    pub fn insert<P: AsRef<Path>>(&self, path: P, contents: &str) {
+        let path = path.as_ref().to_path_buf();
+        self.files.borrow_mut().insert(path, contents.as_bytes().to_vec());
+    }
+
+    pub fn remove<P: AsRef<Path>>(&self, path: P) {
+        self.files.borrow_mut().remove(path.as_ref());
+    }
+
+    pub fn contents<P: AsRef<Path>>(&self, path: P) -> Option<String> {
+        let files = self.files.borrow();
+        files.get(path.as_ref()).map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    pub fn inject<P: AsRef<Path>>(&self, path: P, fault: Fault) {
+        let path = path.as_ref().to_path_buf();
+        self.faults.borrow_mut().entry(path).or_default().push(fault);
+    }
+
+    pub fn clear_faults<P: AsRef<Path>>(&self, path: P) {
+        self.faults.borrow_mut().remove(path.as_ref());
+    }
+
+    fn fault<T>(&self, path: &Path, find: impl Fn(&Fault) -> Option<T>) -> Option<T> {
+        self.faults.borrow().get(path)?.iter().find_map(find)
+    }
+
+    // Fails with the first queued Fail fault, or PermissionDenied if `denied` and it applies.
+    fn check_access(&self, path: &Path, denied: bool) -> io::Result<()> {
+        let mut faults = self.faults.borrow_mut();
+        let Some(faults) = faults.get_mut(path) else {
+            return Ok(());
+        };
+        if let Some(i) = faults.iter().position(|f| matches!(f, Fault::Fail(_))) {
+            if let Fault::Fail(kind) = faults.remove(i) {
+                return Err(io::Error::from(kind));
+            }
+        }
+        if denied && faults.contains(&Fault::PermissionDenied) {
+            return Err(io::Error::from(ErrorKind::PermissionDenied));
+        }
+        Ok(())
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        let exists = self.files.borrow().contains_key(path);
+        self.check_access(path, exists)?;
+        let data = match self.files.borrow().get(path) {
+            Some(data) => data.clone(),
+            None => return Err(io::Error::from(ErrorKind::NotFound)),
+        };
+
+        Ok(Box::new(MemoryReader {
+            data,
+            position: 0,
+            chunk: self.fault(path, |f| match f {
+                Fault::PartialReads { chunk } => Some((*chunk).max(1)),
+                _ => None,
+            }),
+            broken: self.fault(path, |f| match f {
+                Fault::BrokenRead { after, kind } => Some((*after, *kind)),
+                _ => None,
+            }),
+        }))
+    }
+
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + '_>> {
+        self.check_access(path, true)?;
+        self.files.borrow_mut().insert(path.to_path_buf(), vec![]);
+
+        Ok(Box::new(MemoryWriter {
+            fs: self,
+            path: path.to_path_buf(),
+            capacity: self.fault(path, |f| match f {
+                Fault::DiskFull { capacity } => Some(*capacity),
+                _ => None,
+            }),
+        }))
+    }
+}
+
+struct MemoryReader {
+    data: Vec<u8>,
+    position: usize,
+    chunk: Option<usize>,
+    broken: Option<(usize, ErrorKind)>,
+}
+
+impl Read for MemoryReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut end = self.data.len();
+        if let Some((after, kind)) = self.broken {
+            if self.position >= after.min(end) && self.position < end {
+                return Err(io::Error::from(kind));
+            }
+            end = end.min(after);
+        }
+        let len = buf.len().min(end - self.position).min(self.chunk.unwrap_or(usize::MAX));
+
+        buf[..len].copy_from_slice(&self.data[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+struct MemoryWriter<'a> {
+    fs: &'a MemoryFileSystem,
+    path: PathBuf,
+    capacity: Option<usize>,
+}
+
+impl Write for MemoryWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut files = self.fs.files.borrow_mut();
+        let file = files.entry(self.path.clone()).or_default();
+        let room = self.capacity.map_or(usize::MAX, |c| c.saturating_sub(file.len()));
+        if room == 0 && !buf.is_empty() {
+            return Err(io::Error::from(ErrorKind::StorageFull));
+        }
+
+        let len = buf.len().min(room);
+        file.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_reads_still_read_everything() {
+        let fs = MemoryFileSystem::new().with_file("hello.txt", "ferris the crab");
+        fs.inject("hello.txt", Fault::PartialReads { chunk: 2 });
+
+        let mut reader = fs.open(Path::new("hello.txt")).unwrap();
+        let mut buf = [0; 8];
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(fs.read_to_string(Path::new("hello.txt")).unwrap(), "ferris the crab");
+    }
+
+    #[test]
+    fn faults_fail_the_operations_they_apply_to() {
+        let fs = MemoryFileSystem::new().with_file("hello.txt", "ferris");
+        let path = Path::new("hello.txt");
+
+        fs.inject(path, Fault::Fail(ErrorKind::Interrupted));
+        assert_eq!(fs.open(path).err().unwrap().kind(), ErrorKind::Interrupted);
+        assert!(fs.open(path).is_ok());
+
+        fs.inject(path, Fault::BrokenRead { after: 3, kind: ErrorKind::UnexpectedEof });
+        let mut s = String::new();
+        let error = fs.open(path).unwrap().read_to_string(&mut s).unwrap_err();
+        assert_eq!((error.kind(), s.as_str()), (ErrorKind::UnexpectedEof, "fer"));
+
+        fs.inject(path, Fault::PermissionDenied);
+        assert_eq!(fs.open(path).err().unwrap().kind(), ErrorKind::PermissionDenied);
+        assert_eq!(fs.write(path, "crab").unwrap_err().kind(), ErrorKind::PermissionDenied);
+        assert_eq!(fs.contents(path).unwrap(), "ferris");
+
+        fs.clear_faults(path);
+        fs.inject(path, Fault::DiskFull { capacity: 4 });
+        assert_eq!(fs.write(path, "ferris").unwrap_err().kind(), ErrorKind::StorageFull);
+        assert_eq!(fs.contents(path).unwrap(), "ferr");
+
+        // A missing file is missing, whatever else is wrong with it.
+        fs.remove(path);
+        fs.inject(path, Fault::PermissionDenied);
+        assert_eq!(fs.open(path).err().unwrap().kind(), ErrorKind::NotFound);
+    }
+}
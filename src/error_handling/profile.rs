@@ -0,0 +1,228 @@
+// The profile file read_username_from_file loads: one `key = value` entry per line, for each
+// field of structs::User.
+//
+//     # the profile of the user signed in on this machine
+//     username = ferris
+//     email = ferris@example.com
+//     active = true
+//     sign_in_count = 3
+//
+// Blank lines and lines starting with `#` are skipped, and spaces around keys and values don't
+// matter. Every key has to be there exactly once. A file that can't be parsed is reported with
+// the line and column of the problem; one that parses but holds values a User can't have (an
+// empty username, an email address without a domain) is a validation error for that field.
+//
+// `format` writes a User back in the same layout, so saving and loading gives the same User.
+
+use std::path::Path;
+
+use super::error::{Error, Result};
+use super::fs::FileSystem;
+use crate::structs::User;
+
+const KEYS: [&str; 4] = ["username", "email", "active", "sign_in_count"];
+
+pub fn parse(text: &str) -> Result<User> {
+    // The line each key was set on.
+    let mut first_set = [None; 4];
+    let mut username = None;
+    let mut email = None;
+    let mut active = None;
+    let mut sign_in_count = None;
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let entry = line.trim_end_matches('\r');
+        let trimmed = entry.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        // The column a suffix of the line starts at.
+        let column = |rest: &str| entry[..entry.len() - rest.len()].chars().count() + 1;
+
+        let Some((key, value)) = trimmed.split_once('=') else {
+            return Err(Error::parse(
+                line_number,
+                entry.trim_end().chars().count() + 1,
+                "expected `key = value`",
+            ));
+        };
+        let key = key.trim_end();
+        let value_column = column(value.trim_start());
+        let value = value.trim();
+
+        let Some(index) = KEYS.iter().position(|k| *k == key) else {
+            return Err(Error::parse(
+                line_number,
+                column(trimmed),
+                format!("unknown key `{}`; expected one of {}", key, KEYS.join(", ")),
+            ));
+        };
+        if let Some(first) = first_set[index] {
+            return Err(Error::parse(
+                line_number,
+                column(trimmed),
+                format!("`{}` is already set on line {}", key, first),
+            ));
+        }
+        first_set[index] = Some(line_number);
+
+        match key {
+            "username" => username = Some(String::from(value)),
+            "email" => email = Some(String::from(value)),
+            "active" => {
+                active = Some(match value {
+                    "true" => true,
+                    "false" => false,
+                    _ => {
+                        return Err(Error::parse(
+                            line_number,
+                            value_column,
+                            format!("expected `true` or `false`, found `{}`", value),
+                        ))
+                    }
+                })
+            }
+            _ => {
+                sign_in_count = Some(value.parse::<u64>().map_err(|e| Error::Parse {
+                    path: None,
+                    position: Some((line_number, value_column)),
+                    message: format!("`{}` isn't a sign-in count", value),
+                    source: Some(Box::new(e)),
+                })?)
+            }
+        }
+    }
+
+    let missing = |key: &str| Error::parse(text.lines().count() + 1, 1, format!("missing `{}`", key));
+    let user = User {
+        username: username.ok_or_else(|| missing("username"))?,
+        email: email.ok_or_else(|| missing("email"))?,
+        active: active.ok_or_else(|| missing("active"))?,
+        sign_in_count: sign_in_count.ok_or_else(|| missing("sign_in_count"))?,
+    };
+    validate(&user)?;
+    Ok(user)
+}
+
+pub fn validate(user: &User) -> Result<()> {
+    let username = &user.username;
+    if username.is_empty() {
+        return Err(Error::validation("username", "it can't be empty"));
+    }
+    if username.chars().count() > 32 {
+        return Err(Error::validation("username", "it can be at most 32 characters long"));
+    }
+    if let Some(c) = username.chars().find(|c| !(c.is_alphanumeric() || "_-.".contains(*c))) {
+        return Err(Error::validation(
+            "username",
+            format!("`{}` can't be used in a username", c),
+        ));
+    }
+
+    let email = &user.email;
+    let valid_email = match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !email.chars().any(char::is_whitespace)
+        }
+        None => false,
+    };
+    if !valid_email {
+        return Err(Error::validation(
+            "email",
+            format!("`{}` isn't an email address", email),
+        ));
+    }
+    Ok(())
+}
+
+pub fn format(user: &User) -> String {
+    format!(
+        "username = {}\nemail = {}\nactive = {}\nsign_in_count = {}\n",
+        user.username, user.email, user.active, user.sign_in_count
+    )
+}
+
+pub fn load<F: FileSystem + ?Sized, P: AsRef<Path>>(fs: &F, path: P) -> Result<User> {
+    let path = path.as_ref();
+    let text = fs.read_to_string(path).map_err(|e| Error::io(path, "read", e))?;
+    parse(&text).map_err(|e| e.in_file(path))
+}
+
+/// Writes the profile, unless it wouldn't pass validation when it is loaded again.
+pub fn save<F: FileSystem + ?Sized, P: AsRef<Path>>(fs: &F, path: P, user: &User) -> Result<()> {
+    let path = path.as_ref();
+    validate(user).map_err(|e| e.in_file(path))?;
+    fs.write(path, &format(user)).map_err(|e| Error::io(path, "write", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_handling::fs::{Fault, MemoryFileSystem};
+    use crate::structs::build_user;
+
+    fn ferris() -> User {
+        build_user(String::from("ferris@example.com"), String::from("ferris"))
+    }
+
+    #[test]
+    fn profiles_round_trip() {
+        let fs = MemoryFileSystem::new();
+        let mut user = ferris();
+        user.sign_in_count = 42;
+
+        save(&fs, "profile.txt", &user).unwrap();
+        assert_eq!(load(&fs, "profile.txt").unwrap(), user);
+
+        let text = "# me\r\n\n  email=ferris@example.com  \r\nusername   =  ferris\nsign_in_count = 1\nactive = true\n";
+        assert_eq!(parse(text).unwrap(), ferris());
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_line_and_column() {
+        let error = |text: &str| parse(text).unwrap_err().to_string();
+
+        assert_eq!(error("username = ferris\nemail"), "line 2, column 6: expected `key = value`");
+        assert_eq!(
+            error("  name = ferris"),
+            "line 1, column 3: unknown key `name`; expected one of username, email, active, sign_in_count"
+        );
+        assert_eq!(error("username = a\n\nusername = b"), "line 3, column 1: `username` is already set on line 1");
+        assert_eq!(error("active =   yes"), "line 1, column 12: expected `true` or `false`, found `yes`");
+        assert_eq!(error("username = ferris\n"), "line 2, column 1: missing `email`");
+
+        let fs = MemoryFileSystem::new().with_file("profile.txt", "sign_in_count = -1\n");
+        let error = load(&fs, "profile.txt").unwrap_err();
+        assert_eq!(
+            super::super::error::report(&error),
+            "error: profile.txt:1:17: `-1` isn't a sign-in count\ncaused by: invalid digit found in string"
+        );
+    }
+
+    #[test]
+    fn invalid_users_are_neither_loaded_nor_saved() {
+        let mut user = ferris();
+        user.email = String::from("ferris@localhost");
+        assert_eq!(
+            parse(&format(&user)).unwrap_err().to_string(),
+            "invalid email: `ferris@localhost` isn't an email address"
+        );
+
+        let fs = MemoryFileSystem::new();
+        user.email = String::from("ferris@example.com");
+        user.username = String::from("ferris the crab");
+        let error = save(&fs, "profile.txt", &user).unwrap_err();
+        assert_eq!(error.to_string(), "profile.txt: invalid username: ` ` can't be used in a username");
+        assert_eq!(fs.contents("profile.txt"), None);
+
+        fs.inject("profile.txt", Fault::DiskFull { capacity: 10 });
+        let error = save(&fs, "profile.txt", &ferris()).unwrap_err();
+        assert_eq!(error.to_string(), "couldn't write profile.txt");
+    }
+}
@@ -0,0 +1,300 @@
+// What to do when a file can't be read, instead of panicking: a Recovery policy is built up from
+// the strategies it should use, and `read` applies them in order until one of them works.
+//
+// 1. Retry: errors that may go away on their own (Interrupted, WouldBlock, TimedOut) are retried
+//    with a backoff that doubles each time, up to a limit. Other errors aren't retried.
+// 2. Fallback: if the file can't be read, the alternative paths are tried in turn.
+// 3. Create: if the file doesn't exist, it is created with default contents.
+// 4. Read-only: if it can't be created either, the default contents are used anyway and the
+//    caller is told that nothing will be saved.
+//
+// A policy with none of them is a plain read. The files are reached through a FileSystem, so the
+// policies can be tested against a fake one, and the sleeping between retries is replaceable too.
+
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use super::error::{Error, Result};
+use super::fs::FileSystem;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retry {
+    /// How many times to try in all, including the first; 1 means no retries.
+    pub attempts: u32,
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Retry {
+    pub fn none() -> Retry {
+        Retry {
+            attempts: 1,
+            backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+        }
+    }
+
+    /// How long to wait before retry number `retry` (counting from 0).
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for Retry {
+    fn default() -> Retry {
+        Retry {
+            attempts: 4,
+            backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Whether trying again may give a different result.
+pub fn is_transient(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::Interrupted | ErrorKind::WouldBlock | ErrorKind::TimedOut
+    )
+}
+
+/// How the contents were obtained.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Read from the file that was asked for.
+    Read,
+    /// Read from one of the fallback paths.
+    Fallback(PathBuf),
+    /// The file didn't exist and was created with the default contents.
+    Created,
+    /// The file didn't exist and couldn't be created; the default contents only live in memory.
+    ReadOnly,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recovered {
+    pub contents: String,
+    pub outcome: Outcome,
+    /// How many times a file operation was attempted, over every path.
+    pub attempts: u32,
+}
+
+impl Recovered {
+    pub fn is_read_only(&self) -> bool {
+        self.outcome == Outcome::ReadOnly
+    }
+}
+
+pub struct Recovery {
+    retry: Retry,
+    fallbacks: Vec<PathBuf>,
+    default_contents: Option<String>,
+    read_only: bool,
+    sleep: Box<dyn Fn(Duration)>,
+}
+
+impl Recovery {
+    /// A policy that doesn't recover from anything.
+    pub fn new() -> Recovery {
+        Recovery {
+            retry: Retry::none(),
+            fallbacks: vec![],
+            default_contents: None,
+            read_only: false,
+            sleep: Box::new(thread::sleep),
+        }
+    }
+
+    pub fn retry(mut self, retry: Retry) -> Recovery {
+        self.retry = retry;
+        self
+    }
+
+    /// Adds a path to try when the ones before it can't be read.
+    pub fn fallback<P: AsRef<Path>>(mut self, path: P) -> Recovery {
+        self.fallbacks.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn create_with(mut self, contents: &str) -> Recovery {
+        self.default_contents = Some(String::from(contents));
+        self
+    }
+
+    /// Use the default contents without saving them when the file can't be created.
+    pub fn read_only_on_failure(mut self) -> Recovery {
+        self.read_only = true;
+        self
+    }
+
+    /// Replaces thread::sleep between retries.
+    pub fn sleep_with<F: Fn(Duration) + 'static>(mut self, sleep: F) -> Recovery {
+        self.sleep = Box::new(sleep);
+        self
+    }
+
+    pub fn read<F, P>(&self, fs: &F, path: P) -> Result<Recovered>
+    where
+        F: FileSystem + ?Sized,
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let mut attempts = 0;
+        let mut first_error = None;
+
+        for candidate in std::iter::once(path).chain(self.fallbacks.iter().map(PathBuf::as_path)) {
+            match self.with_retries(&mut attempts, || fs.read_to_string(candidate)) {
+                Ok(contents) => {
+                    let outcome = if candidate == path {
+                        Outcome::Read
+                    } else {
+                        Outcome::Fallback(candidate.to_path_buf())
+                    };
+                    return Ok(Recovered { contents, outcome, attempts });
+                }
+                Err(e) => {
+                    first_error.get_or_insert(Error::io(candidate, "read", e));
+                }
+            }
+        }
+
+        // Only the file that was asked for can be reported: there is at least that one.
+        let error = first_error.unwrap();
+        let contents = match &self.default_contents {
+            Some(contents) if error.is_not_found() => contents.clone(),
+            _ if self.fallbacks.is_empty() => return Err(error),
+            _ => {
+                let tried = self.fallbacks.len() + 1;
+                return Err(error.context(format!("none of the {} paths could be read", tried)));
+            }
+        };
+
+        match self.with_retries(&mut attempts, || fs.write(path, &contents)) {
+            Ok(()) => Ok(Recovered {
+                contents,
+                outcome: Outcome::Created,
+                attempts,
+            }),
+            Err(_) if self.read_only => Ok(Recovered {
+                contents,
+                outcome: Outcome::ReadOnly,
+                attempts,
+            }),
+            Err(e) => Err(Error::io(path, "create", e).context(format!("{} doesn't exist", path.display()))),
+        }
+    }
+
+    fn with_retries<T>(&self, attempts: &mut u32, mut f: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+        let mut retry = 0;
+        loop {
+            *attempts += 1;
+            match f() {
+                Err(e) if is_transient(&e) && retry + 1 < self.retry.attempts => {
+                    (self.sleep)(self.retry.delay(retry));
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl Default for Recovery {
+    fn default() -> Recovery {
+        Recovery::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_handling::fs::{Fault, MemoryFileSystem};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn fail(fs: &MemoryFileSystem, path: &str, kinds: &[ErrorKind]) {
+        for kind in kinds {
+            fs.inject(path, Fault::Fail(*kind));
+        }
+    }
+
+    fn recording_sleeps(recovery: Recovery) -> (Recovery, Rc<RefCell<Vec<Duration>>>) {
+        let sleeps = Rc::new(RefCell::new(vec![]));
+        let recorded = Rc::clone(&sleeps);
+        (recovery.sleep_with(move |d| recorded.borrow_mut().push(d)), sleeps)
+    }
+
+    #[test]
+    fn transient_errors_are_retried_with_growing_backoff() {
+        let fs = MemoryFileSystem::new().with_file("hello.txt", "ferris");
+        fail(&fs, "hello.txt", &[ErrorKind::Interrupted, ErrorKind::TimedOut, ErrorKind::WouldBlock]);
+
+        let retry = Retry {
+            attempts: 4,
+            backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(25),
+        };
+        let (recovery, sleeps) = recording_sleeps(Recovery::new().retry(retry));
+
+        let recovered = recovery.read(&fs, "hello.txt").unwrap();
+        assert_eq!((recovered.contents.as_str(), recovered.attempts), ("ferris", 4));
+        assert_eq!(
+            *sleeps.borrow(),
+            [10, 20, 25].map(Duration::from_millis).to_vec()
+        );
+
+        // Out of attempts, and errors that won't go away aren't retried at all.
+        fail(&fs, "hello.txt", &[ErrorKind::Interrupted; 4]);
+        assert!(matches!(recovery.read(&fs, "hello.txt"), Err(Error::Io { .. })));
+        fail(&fs, "hello.txt", &[ErrorKind::PermissionDenied, ErrorKind::Interrupted]);
+        assert!(recovery.read(&fs, "hello.txt").is_err());
+        assert_eq!(sleeps.borrow().len(), 6);
+    }
+
+    #[test]
+    fn fallbacks_are_tried_in_order() {
+        let fs = MemoryFileSystem::new()
+            .with_file("local.txt", "unreadable")
+            .with_file("backup.txt", "from backup");
+        fs.inject("local.txt", Fault::PermissionDenied);
+
+        let recovery = Recovery::new().fallback("local.txt").fallback("backup.txt");
+        let recovered = recovery.read(&fs, "hello.txt").unwrap();
+        assert_eq!(recovered.contents, "from backup");
+        assert_eq!(recovered.outcome, Outcome::Fallback(PathBuf::from("backup.txt")));
+
+        // When none can be read, the error is about the file that was asked for.
+        let error = Recovery::new().fallback("local.txt").read(&fs, "hello.txt").unwrap_err();
+        assert!(error.is_not_found());
+        assert_eq!(error.to_string(), "none of the 2 paths could be read");
+    }
+
+    #[test]
+    fn missing_files_are_created_or_read_only() {
+        let fs = MemoryFileSystem::new();
+        let recovery = Recovery::new().create_with("anonymous\n");
+
+        let recovered = recovery.read(&fs, "hello.txt").unwrap();
+        assert_eq!(recovered.outcome, Outcome::Created);
+        assert_eq!(fs.contents("hello.txt").unwrap(), "anonymous\n");
+        assert_eq!(recovery.read(&fs, "hello.txt").unwrap().outcome, Outcome::Read);
+
+        // Only a missing file is created: one that can't be read is left alone.
+        fs.inject("hello.txt", Fault::PermissionDenied);
+        assert!(recovery.read(&fs, "hello.txt").is_err());
+
+        let read_only_fs = MemoryFileSystem::new();
+        read_only_fs.inject("hello.txt", Fault::PermissionDenied);
+        let error = recovery.read(&read_only_fs, "hello.txt").unwrap_err();
+        assert_eq!(error.to_string(), "hello.txt doesn't exist");
+
+        let recovered = recovery.read_only_on_failure().read(&read_only_fs, "hello.txt").unwrap();
+        assert!(recovered.is_read_only());
+        assert_eq!(recovered.contents, "anonymous\n");
+        assert_eq!(read_only_fs.contents("hello.txt"), None);
+    }
+}
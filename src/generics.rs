@@ -54,6 +54,10 @@ pub fn run(){
 
     println!("p.x = {}", p.x());
 
+    let f = Point { x: 3.0, y: 4.0 };
+
+    println!("f is {} from the origin", f.distance_from_origin());
+
 
     //--------------------
     let p1 = MultiPoint { x: 5, y: 10.4 };
@@ -94,7 +98,9 @@ fn largest_char(list: &[char]) -> char {
 // the same code, so let’s eliminate the duplication by introducing a generic type parameter in a
 // single function.
 
-fn largest<T>(list: &[T]) -> T {
+// Comparing and copying out of the slice only works for types that allow it, so T is bound by
+// PartialOrd and Copy. `list[0]` still panics when the slice is empty.
+pub fn largest<T: PartialOrd + Copy>(list: &[T]) -> T {
     let mut largest = list[0];
 
     for &item in list {
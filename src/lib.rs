@@ -0,0 +1,11 @@
+pub mod concurrency;
+pub mod error_handling;
+pub mod generics;
+pub mod object_oriented;
+pub mod object_oriented2_state_pattern;
+pub mod object_oriented3_state_pattern_rust_way;
+pub mod panic_boundary;
+pub mod smart_pointers3;
+pub mod state_machine;
+pub mod structs;
+pub mod vectors;
@@ -1,28 +1,30 @@
 // mod rust_concepts;
 // mod references_borrowing;
 // mod slice;
-// mod structs;
+// use rust_concepts::structs;
 // mod methods;
 // mod enums;
 // mod match_control_flow;
 // use restaurant;
-// mod vectors;
+// use rust_concepts::vectors;
 // mod strings;
 // mod hash_maps;
-// mod error_handling;
-// mod generics;
+// use rust_concepts::error_handling;
+// use rust_concepts::generics;
 // mod traits;
 // mod lifetimes;
 // mod closures;
 // mod iterators;
 // mod smart_pointers;
 // mod smart_pointers2;
-// mod smart_pointers3;
+// use rust_concepts::smart_pointers3;
 // mod smart_pointers_tree;
-// mod concurrency;
-// mod object_oriented;
-// mod object_oriented2_state_pattern;
-// mod object_oriented3_state_pattern_rust_way;
+// use rust_concepts::concurrency;
+// use rust_concepts::object_oriented;
+// use rust_concepts::object_oriented2_state_pattern;
+// use rust_concepts::object_oriented3_state_pattern_rust_way;
+// use rust_concepts::state_machine;
+// use rust_concepts::panic_boundary;
 mod patterns_and_matching;
 
 fn main() {
@@ -52,5 +54,7 @@ fn main() {
     // object_oriented::run();
     // object_oriented2_state_pattern::run();
     // object_oriented3_state_pattern_rust_way::run();
+    // state_machine::run();
+    // panic_boundary::run();
     patterns_and_matching::run();
 }
\ No newline at end of file
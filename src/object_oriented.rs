@@ -7,6 +7,18 @@
 // flexibility in the code that we wrote in Listing 17-5 and were able to support in Listing 17-9,
 // so it’s a trade-off to consider.
 
+pub mod events;
+pub mod frame;
+pub mod layout;
+pub mod redraw;
+pub mod screen_file;
+pub mod widgets;
+
+use std::io::{self, Write};
+
+use crate::object_oriented::events::{Event, Interact, Key, MouseKind};
+use crate::object_oriented::frame::{FrameBuffer, Rect, Size};
+
 pub fn run(){
     let screen = Screen {
         components: vec![
@@ -18,11 +30,13 @@ pub fn run(){
                     String::from("Maybe"),
                     String::from("No"),
                 ],
+                selected: Some(0),
             }),
             Box::new(Button {
                 width: 50,
                 height: 10,
                 label: String::from("OK"),
+                on_click: Some(Box::new(|| println!("OK clicked"))),
             }),
         ],
     };
@@ -30,8 +44,22 @@ pub fn run(){
     screen.run();
 }
 
+// A component gets a rectangle of the frame to draw itself in. The rectangle is normally as big
+// as the size the component asks for, but it can be smaller when there isn't enough room, and
+// anything drawn outside it is clipped.
 pub trait Draw{
-    fn draw(&self);
+    fn size(&self) -> Size;
+    fn draw(&self, frame: &mut FrameBuffer, area: Rect);
+
+    // Components that react to keyboard and mouse input hand out their Interact implementation
+    // here. Most components only draw, hence the default.
+    fn interact(&mut self) -> Option<&mut dyn Interact> {
+        None
+    }
+
+    // Containers call `f` with each child and the area the child is drawn in, so events can be
+    // delivered to components nested inside layouts.
+    fn for_each_child(&mut self, _area: Rect, _f: &mut dyn FnMut(&mut dyn Draw, Rect)) {}
 }
 
 pub struct Screen{
@@ -40,34 +68,263 @@ pub struct Screen{
 
 impl Screen {
     pub fn run(&self){
-        for component in self.components.iter(){
-            component.draw();
+        self.render(&mut io::stdout()).expect("Failed to write the screen to stdout");
+    }
+
+    pub fn render<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        self.frame().write_to(out)
+    }
+
+    pub fn frame(&self) -> FrameBuffer {
+        let areas = self.areas();
+        let width = areas.iter().map(|a| a.right()).max().unwrap_or(0);
+        let height = areas.iter().map(|a| a.bottom()).max().unwrap_or(0);
+
+        let mut frame = FrameBuffer::new(width, height);
+
+        for (component, area) in self.components.iter().zip(areas) {
+            frame.clipped(area, |frame| component.draw(frame, area));
         }
+
+        frame
+    }
+
+    // Components are stacked from the top, each one as wide and as tall as it asks to be.
+    pub fn areas(&self) -> Vec<Rect> {
+        let mut y = 0;
+
+        self.components
+            .iter()
+            .map(|component| {
+                let size = component.size();
+                let area = Rect::new(0, y, size.width, size.height);
+                y = area.bottom();
+                area
+            })
+            .collect()
     }
 }
 
 pub struct Button{
     pub width: u32,
     pub height: u32,
-    pub label: String
+    pub label: String,
+    pub on_click: Option<Box<dyn FnMut()>>
 }
 
 impl Draw for Button{
-    fn draw(&self) {
-        // todo!()
+    fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+
+    fn draw(&self, frame: &mut FrameBuffer, area: Rect) {
+        frame.border(area);
+
+        let inner = area.inner();
+        if inner.height > 0 {
+            frame.text_centered(inner, inner.y.saturating_add((inner.height - 1) / 2), &self.label);
+        }
+    }
+
+    fn interact(&mut self) -> Option<&mut dyn Interact> {
+        Some(self)
+    }
+}
+
+// A button is clicked with the mouse, or with Enter or Space while it has the focus.
+impl Interact for Button {
+    fn handle_event(&mut self, event: &Event, _area: Rect) -> bool {
+        let clicked = matches!(
+            event,
+            Event::Key(Key::Enter) | Event::Key(Key::Char(' ')) | Event::Mouse { kind: MouseKind::Click, .. }
+        );
+
+        if clicked {
+            if let Some(on_click) = self.on_click.as_mut() {
+                on_click();
+            }
+        }
+        clicked
     }
 }
 
 // If someone using our library decides to implement a SelectBox struct that has width, height,
 // and options fields, they implement the Draw trait on the SelectBox type as well
-struct SelectBox {
-    width: u32,
-    height: u32,
-    options: Vec<String>,
+pub struct SelectBox {
+    pub width: u32,
+    pub height: u32,
+    pub options: Vec<String>,
+    pub selected: Option<usize>,
 }
 
 impl Draw for SelectBox {
-    fn draw(&self) {
-        // code to actually draw a select box
+    fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+
+    fn draw(&self, frame: &mut FrameBuffer, area: Rect) {
+        frame.border(area);
+
+        let inner = area.inner();
+        frame.clipped(inner, |frame| {
+            for (i, option) in self.options.iter().enumerate() {
+                let marker = if self.selected == Some(i) { "(*)" } else { "( )" };
+                frame.text(inner.x, inner.y.saturating_add(i as u32), &format!("{} {}", marker, option));
+            }
+        });
+    }
+
+    fn interact(&mut self) -> Option<&mut dyn Interact> {
+        Some(self)
     }
-}
\ No newline at end of file
+}
+
+// The arrow keys move the selection up and down the options; clicking an option selects it.
+impl Interact for SelectBox {
+    fn handle_event(&mut self, event: &Event, area: Rect) -> bool {
+        if self.options.is_empty() {
+            return false;
+        }
+        let last = self.options.len() - 1;
+
+        match event {
+            Event::Key(Key::Up) => {
+                self.selected = Some(self.selected.map_or(last, |i| i.saturating_sub(1)));
+                true
+            }
+            Event::Key(Key::Down) => {
+                self.selected = Some(self.selected.map_or(0, |i| (i + 1).min(last)));
+                true
+            }
+            Event::Mouse { x, y, kind: MouseKind::Click } => {
+                let inner = area.inner();
+                let row = (*y as usize).checked_sub(inner.y as usize);
+
+                match row {
+                    Some(row) if inner.contains(*x, *y) && row <= last => {
+                        self.selected = Some(row);
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(screen: &Screen) -> String {
+        let mut out = vec![];
+        screen.render(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn button_draws_a_box_with_its_label_centered() {
+        let screen = Screen {
+            components: vec![Box::new(Button {
+                width: 10,
+                height: 3,
+                label: String::from("OK"),
+                on_click: None,
+            })],
+        };
+
+        assert_eq!(
+            render(&screen),
+            "\
++--------+
+|   OK   |
++--------+
+"
+        );
+    }
+
+    #[test]
+    fn select_box_marks_the_selected_option() {
+        let screen = Screen {
+            components: vec![Box::new(SelectBox {
+                width: 11,
+                height: 5,
+                options: vec![
+                    String::from("Yes"),
+                    String::from("Maybe"),
+                    String::from("No"),
+                ],
+                selected: Some(1),
+            })],
+        };
+
+        assert_eq!(
+            render(&screen),
+            "\
++---------+
+|( ) Yes  |
+|(*) Maybe|
+|( ) No   |
++---------+
+"
+        );
+    }
+
+    #[test]
+    fn screen_stacks_components_and_clips_overflow() {
+        let screen = Screen {
+            components: vec![
+                Box::new(SelectBox {
+                    width: 9,
+                    height: 3,
+                    options: vec![String::from("Yes"), String::from("No")],
+                    selected: None,
+                }),
+                Box::new(Button {
+                    width: 6,
+                    height: 3,
+                    label: String::from("Cancel"),
+                    on_click: None,
+                }),
+            ],
+        };
+
+        assert_eq!(
+            render(&screen),
+            "\
++-------+
+|( ) Yes|
++-------+
++----+
+|Canc|
++----+
+"
+        );
+    }
+
+    #[test]
+    fn huge_components_are_placed_without_overflowing() {
+        let button = |height| -> Box<dyn Draw> {
+            Box::new(Button {
+                width: u32::MAX,
+                height,
+                label: String::from("OK"),
+                on_click: None,
+            })
+        };
+        let screen = Screen {
+            components: vec![button(u32::MAX), button(3)],
+        };
+        assert_eq!(
+            screen.areas(),
+            vec![Rect::new(0, 0, u32::MAX, u32::MAX), Rect::new(0, u32::MAX, u32::MAX, 3)]
+        );
+        assert_eq!(screen.areas()[1].bottom(), u32::MAX);
+
+        let mut frame = FrameBuffer::new(4, 3);
+        screen.components[0].draw(&mut frame, Rect::new(u32::MAX - 1, u32::MAX - 1, 10, 10));
+        frame.text(u32::MAX - 1, 0, "abc");
+        assert_eq!(frame.to_string(), "\n\n\n");
+    }
+}
@@ -0,0 +1,368 @@
+// Keyboard and mouse input for Screen components.
+//
+// Components that react to input implement Interact next to Draw and return themselves from
+// `Draw::interact`. Ui wraps a Screen and keeps track of which of those components has the
+// focus:
+//
+// - Tab and BackTab move the focus to the next or previous focusable component, in the order
+//   they appear on the screen (containers are searched depth first).
+// - Every other key goes to the focused component.
+// - A mouse event goes to the innermost focusable component under the pointer, which also gets
+//   the focus.
+//
+// Because events are plain values, a UI can be driven from a script instead of a terminal, see
+// `parse_script` and `Ui::play`.
+
+use std::fmt;
+
+use crate::object_oriented::frame::{FrameBuffer, Rect};
+use crate::object_oriented::{Draw, Screen};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Enter,
+    Tab,
+    BackTab,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Esc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseKind {
+    Click,
+    ScrollUp,
+    ScrollDown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Key(Key),
+    /// Screen coordinates of the pointer.
+    Mouse { x: u32, y: u32, kind: MouseKind },
+}
+
+pub trait Interact {
+    /// Whether Tab can stop on this component. A disabled component can return false.
+    fn focusable(&self) -> bool {
+        true
+    }
+
+    /// Handles one event. `area` is where the component was last drawn, so mouse coordinates
+    /// can be related to its contents. Returns whether the event was used.
+    fn handle_event(&mut self, event: &Event, area: Rect) -> bool;
+}
+
+pub struct Ui {
+    pub screen: Screen,
+    focused: Option<usize>,
+}
+
+impl Ui {
+    /// The first focusable component starts with the focus.
+    pub fn new(screen: Screen) -> Ui {
+        let mut ui = Ui {
+            screen,
+            focused: None,
+        };
+        if !ui.focusable_areas().is_empty() {
+            ui.focused = Some(0);
+        }
+        ui
+    }
+
+    /// Position of the focused component among the focusable ones, in screen order.
+    pub fn focused(&self) -> Option<usize> {
+        self.focused
+    }
+
+    /// Returns whether the event was used, either to move the focus or by a component.
+    pub fn dispatch(&mut self, event: &Event) -> bool {
+        let count = self.focusable_areas().len();
+        if count == 0 {
+            return false;
+        }
+
+        match event {
+            Event::Key(Key::Tab) => {
+                self.focused = Some(self.focused.map_or(0, |i| (i + 1) % count));
+                true
+            }
+            Event::Key(Key::BackTab) => {
+                self.focused = Some(self.focused.map_or(count - 1, |i| (i + count - 1) % count));
+                true
+            }
+            Event::Key(_) => match self.focused {
+                Some(target) => self.deliver(target, event),
+                None => false,
+            },
+            Event::Mouse { x, y, .. } => {
+                let hit = self
+                    .focusable_areas()
+                    .iter()
+                    .rposition(|area| area.contains(*x, *y));
+
+                match hit {
+                    Some(target) => {
+                        self.focused = Some(target);
+                        self.deliver(target, event)
+                    }
+                    None => false,
+                }
+            }
+        }
+    }
+
+    /// Dispatches every event in a script, see `parse_script`.
+    pub fn play(&mut self, script: &str) -> Result<(), ScriptError> {
+        for event in parse_script(script)? {
+            self.dispatch(&event);
+        }
+        Ok(())
+    }
+
+    /// The screen, with the border corners of the focused component (if it has a border)
+    /// drawn as `#`.
+    pub fn frame(&mut self) -> FrameBuffer {
+        let mut frame = self.screen.frame();
+
+        let focused_area = self
+            .focused
+            .and_then(|i| self.focusable_areas().get(i).copied());
+
+        if let Some(area) = focused_area.filter(|a| a.width > 0 && a.height > 0) {
+            let (right, bottom) = (area.right() - 1, area.bottom() - 1);
+            for (x, y) in [(area.x, area.y), (right, area.y), (area.x, bottom), (right, bottom)] {
+                if frame.get(x, y) == Some('+') {
+                    frame.put(x, y, '#');
+                }
+            }
+        }
+
+        frame
+    }
+
+    fn focusable_areas(&mut self) -> Vec<Rect> {
+        let mut areas = vec![];
+        self.walk(&mut |component, area| {
+            if component.interact().is_some_and(|c| c.focusable()) {
+                areas.push(area);
+            }
+        });
+        areas
+    }
+
+    fn deliver(&mut self, target: usize, event: &Event) -> bool {
+        let mut index = 0;
+        let mut handled = false;
+
+        self.walk(&mut |component, area| {
+            if let Some(interactive) = component.interact().filter(|c| c.focusable()) {
+                if index == target {
+                    handled = interactive.handle_event(event, area);
+                }
+                index += 1;
+            }
+        });
+
+        handled
+    }
+
+    fn walk(&mut self, f: &mut dyn FnMut(&mut dyn Draw, Rect)) {
+        let areas = self.screen.areas();
+        for (component, area) in self.screen.components.iter_mut().zip(areas) {
+            walk(component.as_mut(), area, f);
+        }
+    }
+}
+
+fn walk(component: &mut dyn Draw, area: Rect, f: &mut dyn FnMut(&mut dyn Draw, Rect)) {
+    f(component, area);
+    component.for_each_child(area, &mut |child, child_area| walk(child, child_area, f));
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ScriptError {
+    /// Which token (counting from 1) could not be understood.
+    pub position: usize,
+    pub token: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown event `{}` at position {}", self.token, self.position)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// Turns a whitespace separated list of events into Events, for example
+/// `tab down down enter type:hello click:3,4`.
+///
+/// Keys are written by name (`enter`, `tab`, `backtab`, `backspace`, `delete`, `left`, `right`,
+/// `up`, `down`, `home`, `end`, `esc`, `space`), `type:text` types each character of `text`,
+/// and `click:x,y`, `scrollup:x,y` and `scrolldown:x,y` are mouse events.
+pub fn parse_script(script: &str) -> Result<Vec<Event>, ScriptError> {
+    let mut events = vec![];
+
+    for (i, token) in script.split_whitespace().enumerate() {
+        let error = || ScriptError {
+            position: i + 1,
+            token: String::from(token),
+        };
+
+        let key = match token {
+            "enter" => Some(Key::Enter),
+            "tab" => Some(Key::Tab),
+            "backtab" => Some(Key::BackTab),
+            "backspace" => Some(Key::Backspace),
+            "delete" => Some(Key::Delete),
+            "left" => Some(Key::Left),
+            "right" => Some(Key::Right),
+            "up" => Some(Key::Up),
+            "down" => Some(Key::Down),
+            "home" => Some(Key::Home),
+            "end" => Some(Key::End),
+            "esc" => Some(Key::Esc),
+            "space" => Some(Key::Char(' ')),
+            _ => None,
+        };
+        if let Some(key) = key {
+            events.push(Event::Key(key));
+            continue;
+        }
+
+        let (name, argument) = token.split_once(':').ok_or_else(error)?;
+        if name == "type" {
+            events.extend(argument.chars().map(|c| Event::Key(Key::Char(c))));
+            continue;
+        }
+
+        let kind = match name {
+            "click" => MouseKind::Click,
+            "scrollup" => MouseKind::ScrollUp,
+            "scrolldown" => MouseKind::ScrollDown,
+            _ => return Err(error()),
+        };
+        let (x, y) = argument.split_once(',').ok_or_else(error)?;
+        let x = x.parse().map_err(|_| error())?;
+        let y = y.parse().map_err(|_| error())?;
+
+        events.push(Event::Mouse { x, y, kind });
+    }
+
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object_oriented::layout::{Constraint, HStack};
+    use crate::object_oriented::{Button, SelectBox};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // A select box above two buttons side by side, with every click logged.
+    fn form(clicks: &Rc<RefCell<Vec<&'static str>>>) -> Ui {
+        let button = |label: &'static str| {
+            let clicks = Rc::clone(clicks);
+            Box::new(Button {
+                width: 6,
+                height: 3,
+                label: String::from(label),
+                on_click: Some(Box::new(move || clicks.borrow_mut().push(label))),
+            })
+        };
+
+        Ui::new(Screen {
+            components: vec![
+                Box::new(SelectBox {
+                    width: 12,
+                    height: 5,
+                    options: vec![
+                        String::from("Yes"),
+                        String::from("Maybe"),
+                        String::from("No"),
+                    ],
+                    selected: None,
+                }),
+                Box::new(HStack {
+                    spacing: 0,
+                    children: vec![
+                        (Constraint::Auto, button("OK")),
+                        (Constraint::Auto, button("Quit")),
+                    ],
+                }),
+            ],
+        })
+    }
+
+    #[test]
+    fn tab_moves_focus_through_nested_components() {
+        let clicks = Rc::new(RefCell::new(vec![]));
+        let mut ui = form(&clicks);
+
+        assert_eq!(ui.focused(), Some(0));
+        ui.play("tab tab").unwrap();
+        assert_eq!(ui.focused(), Some(2));
+        ui.play("tab").unwrap();
+        assert_eq!(ui.focused(), Some(0));
+        ui.play("backtab").unwrap();
+        assert_eq!(ui.focused(), Some(2));
+    }
+
+    #[test]
+    fn keys_select_options_and_click_buttons() {
+        let clicks = Rc::new(RefCell::new(vec![]));
+        let mut ui = form(&clicks);
+
+        ui.play("down down down up tab enter tab space").unwrap();
+
+        assert_eq!(*clicks.borrow(), vec!["OK", "Quit"]);
+        assert_eq!(
+            ui.frame().to_string(),
+            "\
++----------+
+|( ) Yes   |
+|(*) Maybe |
+|( ) No    |
++----------+
++----+#----#
+| OK ||Quit|
++----+#----#
+"
+        );
+    }
+
+    #[test]
+    fn mouse_clicks_focus_and_activate_the_component_under_the_pointer() {
+        let clicks = Rc::new(RefCell::new(vec![]));
+        let mut ui = form(&clicks);
+
+        ui.play("click:3,3 click:8,6 click:40,40").unwrap();
+
+        assert_eq!(*clicks.borrow(), vec!["Quit"]);
+        assert_eq!(ui.focused(), Some(2));
+        assert!(ui.frame().to_string().contains("(*) No"));
+    }
+
+    #[test]
+    fn bad_script_tokens_are_reported() {
+        assert_eq!(
+            parse_script("tab jump"),
+            Err(ScriptError {
+                position: 2,
+                token: String::from("jump"),
+            })
+        );
+        assert!(parse_script("click:1").is_err());
+        assert_eq!(parse_script("type:ab").unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,195 @@
+// A grid of character cells that components draw into, and the coordinates they draw with.
+// Once everything is drawn the whole frame is written out at once, line by line.
+//
+// Drawing outside the frame, or outside the current clip area, is silently ignored, so a
+// component never has to check whether it fits. Coordinates saturate at u32::MAX rather than
+// overflow, so even an absurdly large component only ends up off the edge of the frame.
+
+use std::fmt;
+use std::io::{self, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Size {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Size {
+    pub fn new(width: u32, height: u32) -> Size {
+        Size { width, height }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    pub fn right(&self) -> u32 {
+        self.x.saturating_add(self.width)
+    }
+
+    pub fn bottom(&self) -> u32 {
+        self.y.saturating_add(self.height)
+    }
+
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    /// The area left inside a one-cell border.
+    pub fn inner(&self) -> Rect {
+        self.shrink(1)
+    }
+
+    pub fn shrink(&self, by: u32) -> Rect {
+        Rect {
+            x: self.x.saturating_add(by),
+            y: self.y.saturating_add(by),
+            width: self.width.saturating_sub(by.saturating_mul(2)),
+            height: self.height.saturating_sub(by.saturating_mul(2)),
+        }
+    }
+
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.right().min(other.right()).max(x);
+        let bottom = self.bottom().min(other.bottom()).max(y);
+
+        Rect::new(x, y, right - x, bottom - y)
+    }
+}
+
+pub struct FrameBuffer {
+    width: u32,
+    height: u32,
+    cells: Vec<char>,
+    clip: Rect,
+}
+
+impl FrameBuffer {
+    pub fn new(width: u32, height: u32) -> FrameBuffer {
+        FrameBuffer {
+            width,
+            height,
+            cells: vec![' '; width as usize * height as usize],
+            clip: Rect::new(0, 0, width, height),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Option<char> {
+        if x < self.width && y < self.height {
+            Some(self.cells[self.index(x, y)])
+        } else {
+            None
+        }
+    }
+
+    pub fn put(&mut self, x: u32, y: u32, ch: char) {
+        if self.clip.contains(x, y) {
+            let i = self.index(x, y);
+            self.cells[i] = ch;
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+
+    /// Writes `text` from (x, y) to the right. Whatever doesn't fit is cut off.
+    pub fn text(&mut self, x: u32, y: u32, text: &str) {
+        for (i, ch) in text.chars().enumerate() {
+            self.put(x.saturating_add(i as u32), y, ch);
+        }
+    }
+
+    /// Writes `text` centred horizontally in `area`, on row `y`.
+    pub fn text_centered(&mut self, area: Rect, y: u32, text: &str) {
+        let len = text.chars().count() as u32;
+        let x = area.x.saturating_add(area.width.saturating_sub(len) / 2);
+        self.clipped(area, |frame| frame.text(x, y, text));
+    }
+
+    pub fn fill(&mut self, area: Rect, ch: char) {
+        for y in area.y..area.bottom() {
+            for x in area.x..area.right() {
+                self.put(x, y, ch);
+            }
+        }
+    }
+
+    /// Draws a one-cell border along the edges of `area`.
+    pub fn border(&mut self, area: Rect) {
+        if area.width == 0 || area.height == 0 {
+            return;
+        }
+
+        let (right, bottom) = (area.right() - 1, area.bottom() - 1);
+
+        for x in area.x..=right {
+            self.put(x, area.y, '-');
+            self.put(x, bottom, '-');
+        }
+        for y in area.y..=bottom {
+            self.put(area.x, y, '|');
+            self.put(right, y, '|');
+        }
+        for (x, y) in [(area.x, area.y), (right, area.y), (area.x, bottom), (right, bottom)] {
+            self.put(x, y, '+');
+        }
+    }
+
+    /// Runs `draw` with drawing restricted to `area` (and to whatever clip area was already in
+    /// place), then restores the previous clip area.
+    pub fn clipped<F: FnOnce(&mut FrameBuffer)>(&mut self, area: Rect, draw: F) {
+        let previous = self.clip;
+        self.clip = previous.intersect(&area);
+        draw(self);
+        self.clip = previous;
+    }
+
+    /// The rows of the frame, without trailing spaces.
+    pub fn lines(&self) -> Vec<String> {
+        if self.width == 0 {
+            return vec![String::new(); self.height as usize];
+        }
+
+        self.cells
+            .chunks(self.width as usize)
+            .map(|row| row.iter().collect::<String>().trim_end().to_string())
+            .collect()
+    }
+
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for line in self.lines() {
+            writeln!(out, "{}", line)?;
+        }
+        out.flush()
+    }
+}
+
+impl fmt::Display for FrameBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in self.lines() {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,451 @@
+// Layout containers. Each one is a Draw component itself, so they nest: a VStack can hold an
+// HStack of Buttons next to a Padding around a SelectBox, and the Screen only sees one component.
+//
+// A stack shares its length (height for VStack, width for HStack) between its children according
+// to each child's Constraint:
+//
+// - Auto: as much as the child asks for through `Draw::size`.
+// - Length(n): exactly n cells.
+// - Percent(p): p percent of the stack.
+// - Fill(weight): nothing at first; whatever is left after the other children is split between
+//   the Fill children in proportion to their weights.
+//
+// Across the stack every child is stretched to the stack's full width (or height). When the
+// children ask for more than there is, they are laid out in order until the space runs out: the
+// child that crosses the edge is cut short and the ones after it get no space at all. Nothing a
+// child draws can leak outside the area it was given. Sizes and offsets that would pass u32::MAX
+// are held there instead.
+
+use crate::object_oriented::frame::{FrameBuffer, Rect, Size};
+use crate::object_oriented::Draw;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Constraint {
+    Auto,
+    Length(u32),
+    Percent(u32),
+    Fill(u32),
+}
+
+pub struct VStack {
+    pub spacing: u32,
+    pub children: Vec<(Constraint, Box<dyn Draw>)>,
+}
+
+pub struct HStack {
+    pub spacing: u32,
+    pub children: Vec<(Constraint, Box<dyn Draw>)>,
+}
+
+// Cells are filled left to right, top to bottom, and every cell gets the same share of the grid.
+pub struct Grid {
+    pub columns: usize,
+    pub cells: Vec<Box<dyn Draw>>,
+}
+
+pub struct Padding {
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub left: u32,
+    pub child: Box<dyn Draw>,
+}
+
+impl Padding {
+    pub fn uniform(padding: u32, child: Box<dyn Draw>) -> Padding {
+        Padding {
+            top: padding,
+            right: padding,
+            bottom: padding,
+            left: padding,
+            child,
+        }
+    }
+}
+
+// `cells * count`, held at u32::MAX.
+fn times(cells: u32, count: usize) -> u32 {
+    clamp(cells as u64 * count as u64)
+}
+
+fn clamp(cells: u64) -> u32 {
+    cells.min(u32::MAX as u64) as u32
+}
+
+/// Splits `total` cells between children with the given constraints and preferred lengths.
+/// Returns each child's (offset, length) along the stack.
+pub fn split(total: u32, spacing: u32, constraints: &[(Constraint, u32)]) -> Vec<(u32, u32)> {
+    let gaps = times(spacing, constraints.len().saturating_sub(1));
+    let available = total.saturating_sub(gaps);
+
+    let mut lengths: Vec<u32> = constraints
+        .iter()
+        .map(|(constraint, preferred)| match constraint {
+            Constraint::Auto => *preferred,
+            Constraint::Length(n) => *n,
+            Constraint::Percent(p) => clamp(available as u64 * *p as u64 / 100),
+            Constraint::Fill(_) => 0,
+        })
+        .collect();
+
+    let fill_weights: u64 = constraints
+        .iter()
+        .map(|(constraint, _)| match constraint {
+            Constraint::Fill(weight) => *weight as u64,
+            _ => 0,
+        })
+        .sum();
+
+    let remaining = available.saturating_sub(clamp(lengths.iter().map(|n| *n as u64).sum()));
+    let mut handed_out = 0;
+
+    for (length, (constraint, _)) in lengths.iter_mut().zip(constraints) {
+        if let Constraint::Fill(weight) = constraint {
+            *length = (remaining as u64 * *weight as u64).checked_div(fill_weights).unwrap_or(0) as u32;
+            handed_out += *length;
+        }
+    }
+
+    // Rounding down leaves a few cells over; give them to the first Fill children.
+    let mut leftover = remaining - handed_out;
+    for (length, (constraint, _)) in lengths.iter_mut().zip(constraints) {
+        if leftover == 0 {
+            break;
+        }
+        if matches!(constraint, Constraint::Fill(weight) if *weight > 0) {
+            *length += 1;
+            leftover -= 1;
+        }
+    }
+
+    let mut offset = 0;
+    lengths
+        .into_iter()
+        .map(|length| {
+            let start = offset.min(total);
+            let length = length.min(total - start);
+            offset = (start + length).saturating_add(spacing);
+            (start, length)
+        })
+        .collect()
+}
+
+fn stack_size(spacing: u32, children: &[(Constraint, Box<dyn Draw>)], vertical: bool) -> Size {
+    let gaps = times(spacing, children.len().saturating_sub(1));
+    let mut main = gaps;
+    let mut cross = 0;
+
+    for (constraint, child) in children {
+        let size = child.size();
+        let (child_main, child_cross) = if vertical {
+            (size.height, size.width)
+        } else {
+            (size.width, size.height)
+        };
+
+        main = main.saturating_add(match constraint {
+            Constraint::Length(n) => *n,
+            _ => child_main,
+        });
+        cross = cross.max(child_cross);
+    }
+
+    if vertical {
+        Size::new(cross, main)
+    } else {
+        Size::new(main, cross)
+    }
+}
+
+fn draw_child(frame: &mut FrameBuffer, child: &dyn Draw, area: Rect) {
+    if area.width > 0 && area.height > 0 {
+        frame.clipped(area, |frame| child.draw(frame, area));
+    }
+}
+
+fn visit_children<'a>(
+    children: impl IntoIterator<Item = &'a mut Box<dyn Draw>>,
+    areas: Vec<Rect>,
+    f: &mut dyn FnMut(&mut dyn Draw, Rect),
+) {
+    for (child, area) in children.into_iter().zip(areas) {
+        if area.width > 0 && area.height > 0 {
+            f(child.as_mut(), area);
+        }
+    }
+}
+
+impl VStack {
+    fn child_areas(&self, area: Rect) -> Vec<Rect> {
+        let constraints: Vec<(Constraint, u32)> = self
+            .children
+            .iter()
+            .map(|(constraint, child)| (*constraint, child.size().height))
+            .collect();
+
+        split(area.height, self.spacing, &constraints)
+            .into_iter()
+            .map(|(offset, height)| Rect::new(area.x, area.y.saturating_add(offset), area.width, height))
+            .collect()
+    }
+}
+
+impl Draw for VStack {
+    fn size(&self) -> Size {
+        stack_size(self.spacing, &self.children, true)
+    }
+
+    fn draw(&self, frame: &mut FrameBuffer, area: Rect) {
+        for ((_, child), child_area) in self.children.iter().zip(self.child_areas(area)) {
+            draw_child(frame, child.as_ref(), child_area);
+        }
+    }
+
+    fn for_each_child(&mut self, area: Rect, f: &mut dyn FnMut(&mut dyn Draw, Rect)) {
+        let areas = self.child_areas(area);
+        visit_children(self.children.iter_mut().map(|(_, child)| child), areas, f);
+    }
+}
+
+impl HStack {
+    fn child_areas(&self, area: Rect) -> Vec<Rect> {
+        let constraints: Vec<(Constraint, u32)> = self
+            .children
+            .iter()
+            .map(|(constraint, child)| (*constraint, child.size().width))
+            .collect();
+
+        split(area.width, self.spacing, &constraints)
+            .into_iter()
+            .map(|(offset, width)| Rect::new(area.x.saturating_add(offset), area.y, width, area.height))
+            .collect()
+    }
+}
+
+impl Draw for HStack {
+    fn size(&self) -> Size {
+        stack_size(self.spacing, &self.children, false)
+    }
+
+    fn draw(&self, frame: &mut FrameBuffer, area: Rect) {
+        for ((_, child), child_area) in self.children.iter().zip(self.child_areas(area)) {
+            draw_child(frame, child.as_ref(), child_area);
+        }
+    }
+
+    fn for_each_child(&mut self, area: Rect, f: &mut dyn FnMut(&mut dyn Draw, Rect)) {
+        let areas = self.child_areas(area);
+        visit_children(self.children.iter_mut().map(|(_, child)| child), areas, f);
+    }
+}
+
+impl Grid {
+    fn rows(&self) -> usize {
+        self.cells.len().div_ceil(self.columns.max(1))
+    }
+
+    fn cell_areas(&self, area: Rect) -> Vec<Rect> {
+        let columns = self.columns.max(1);
+        let even = |n: usize| vec![(Constraint::Fill(1), 0); n];
+        let column_spans = split(area.width, 0, &even(columns));
+        let row_spans = split(area.height, 0, &even(self.rows()));
+
+        (0..self.cells.len())
+            .map(|i| {
+                let (x, width) = column_spans[i % columns];
+                let (y, height) = row_spans[i / columns];
+                Rect::new(area.x.saturating_add(x), area.y.saturating_add(y), width, height)
+            })
+            .collect()
+    }
+}
+
+impl Draw for Grid {
+    fn size(&self) -> Size {
+        let widest = self.cells.iter().map(|c| c.size().width).max().unwrap_or(0);
+        let tallest = self.cells.iter().map(|c| c.size().height).max().unwrap_or(0);
+
+        Size::new(times(widest, self.columns.max(1)), times(tallest, self.rows()))
+    }
+
+    fn draw(&self, frame: &mut FrameBuffer, area: Rect) {
+        for (cell, cell_area) in self.cells.iter().zip(self.cell_areas(area)) {
+            draw_child(frame, cell.as_ref(), cell_area);
+        }
+    }
+
+    fn for_each_child(&mut self, area: Rect, f: &mut dyn FnMut(&mut dyn Draw, Rect)) {
+        let areas = self.cell_areas(area);
+        visit_children(&mut self.cells, areas, f);
+    }
+}
+
+impl Padding {
+    fn child_area(&self, area: Rect) -> Rect {
+        let x = area.x.saturating_add(self.left).min(area.right());
+        let y = area.y.saturating_add(self.top).min(area.bottom());
+
+        Rect::new(
+            x,
+            y,
+            area.right().saturating_sub(self.right).saturating_sub(x),
+            area.bottom().saturating_sub(self.bottom).saturating_sub(y),
+        )
+    }
+}
+
+impl Draw for Padding {
+    fn size(&self) -> Size {
+        let child = self.child.size();
+        Size::new(
+            child.width.saturating_add(self.left).saturating_add(self.right),
+            child.height.saturating_add(self.top).saturating_add(self.bottom),
+        )
+    }
+
+    fn draw(&self, frame: &mut FrameBuffer, area: Rect) {
+        draw_child(frame, self.child.as_ref(), self.child_area(area));
+    }
+
+    fn for_each_child(&mut self, area: Rect, f: &mut dyn FnMut(&mut dyn Draw, Rect)) {
+        let child_area = self.child_area(area);
+        visit_children([&mut self.child], vec![child_area], f);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object_oriented::{Button, Screen};
+
+    fn button(label: &str, width: u32, height: u32) -> Box<dyn Draw> {
+        Box::new(Button {
+            width,
+            height,
+            label: String::from(label),
+            on_click: None,
+        })
+    }
+
+    #[test]
+    fn split_honours_each_constraint() {
+        let constraints = [
+            (Constraint::Length(3), 0),
+            (Constraint::Percent(20), 0),
+            (Constraint::Auto, 5),
+            (Constraint::Fill(1), 0),
+            (Constraint::Fill(2), 0),
+        ];
+
+        assert_eq!(
+            split(30, 0, &constraints),
+            vec![(0, 3), (3, 6), (9, 5), (14, 6), (20, 10)]
+        );
+    }
+
+    #[test]
+    fn split_cuts_off_children_that_overflow() {
+        let constraints = [(Constraint::Length(6), 0), (Constraint::Length(6), 0), (Constraint::Auto, 4)];
+
+        assert_eq!(split(10, 1, &constraints), vec![(0, 6), (7, 3), (10, 0)]);
+    }
+
+    #[test]
+    fn nested_stacks_render_side_by_side() {
+        let screen = Screen {
+            components: vec![Box::new(VStack {
+                spacing: 0,
+                children: vec![
+                    (Constraint::Auto, button("Title", 16, 3)),
+                    (
+                        Constraint::Auto,
+                        Box::new(HStack {
+                            spacing: 2,
+                            children: vec![
+                                (Constraint::Fill(1), button("OK", 4, 3)),
+                                (Constraint::Fill(1), button("No", 4, 3)),
+                            ],
+                        }),
+                    ),
+                ],
+            })],
+        };
+
+        assert_eq!(
+            screen.frame().to_string(),
+            "\
++--------------+
+|    Title     |
++--------------+
++-----+  +-----+
+| OK  |  | No  |
++-----+  +-----+
+"
+        );
+    }
+
+    #[test]
+    fn grid_and_padding_place_cells() {
+        let grid = Grid {
+            columns: 2,
+            cells: vec![button("1", 5, 3), button("2", 5, 3), button("3", 5, 3)],
+        };
+        let screen = Screen {
+            components: vec![Box::new(Padding::uniform(1, Box::new(grid)))],
+        };
+
+        assert_eq!(
+            screen.frame().to_string(),
+            "
+ +---++---+
+ | 1 || 2 |
+ +---++---+
+ +---+
+ | 3 |
+ +---+
+
+"
+        );
+    }
+
+    #[test]
+    fn a_grid_without_columns_is_sized_as_it_is_drawn() {
+        let grid = Grid {
+            columns: 0,
+            cells: vec![button("1", 5, 3), button("2", 4, 3)],
+        };
+        let drawn = grid.cell_areas(Rect::new(0, 0, 5, 6));
+
+        assert_eq!(grid.size(), Size::new(5, 6));
+        assert_eq!(drawn, vec![Rect::new(0, 0, 5, 3), Rect::new(0, 3, 5, 3)]);
+    }
+
+    #[test]
+    fn huge_sizes_and_spacing_are_held_at_the_largest_u32() {
+        let max = u32::MAX;
+        assert_eq!(split(10, max, &[(Constraint::Auto, 3), (Constraint::Auto, 3)]), vec![(0, 3), (10, 0)]);
+        assert_eq!(split(10, 0, &[(Constraint::Percent(max), 0)]), vec![(0, 10)]);
+        assert_eq!(
+            split(max, 0, &[(Constraint::Auto, max), (Constraint::Fill(max), 0), (Constraint::Fill(max), 0)]),
+            vec![(0, max), (max, 0), (max, 0)]
+        );
+        assert_eq!(split(9, 0, &[(Constraint::Fill(max), 0), (Constraint::Fill(max), 0)]), vec![(0, 5), (5, 4)]);
+
+        let stack = VStack {
+            spacing: max,
+            children: vec![(Constraint::Auto, button("1", 5, 3)), (Constraint::Length(max), button("2", 5, 3))],
+        };
+        assert_eq!(stack.size(), Size::new(5, max));
+        assert_eq!(stack.child_areas(Rect::new(0, max - 1, 5, 10))[1], Rect::new(0, max, 5, 0));
+
+        let grid = Grid {
+            columns: 2,
+            cells: vec![button("1", max, max), button("2", 1, 1), button("3", 1, 1)],
+        };
+        assert_eq!(grid.size(), Size::new(max, max));
+
+        let padding = Padding::uniform(max, button("1", 5, 3));
+        assert_eq!(padding.size(), Size::new(max, max));
+        assert_eq!(padding.child_area(Rect::new(1, 1, 10, 10)), Rect::new(11, 11, 0, 0));
+    }
+}
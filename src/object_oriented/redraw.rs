@@ -0,0 +1,306 @@
+// Incremental drawing for terminals. Screen::run draws every component and prints the whole
+// frame each time; RetainedScreen keeps the frame that is already on the terminal and only
+// touches what changed:
+//
+// - Components are marked dirty when they are reached through `component_mut` (or with
+//   `mark_dirty`), and when they move because a component above them changed size.
+// - On `render`, only the dirty regions are cleared and redrawn. Any other component that
+//   overlaps a dirty region is redrawn as well, clipped to the region.
+// - The rows those regions cover are compared with the terminal cell by cell, and only the
+//   changed runs of cells are written, each preceded by an ANSI cursor move. Runs separated by
+//   a short stretch of unchanged cells are joined, because rewriting a few cells is cheaper
+//   than another cursor move.
+//
+// benches/redraw.rs compares this with drawing the whole screen.
+
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+use crate::object_oriented::frame::{FrameBuffer, Rect};
+use crate::object_oriented::{Draw, Screen};
+
+// A cursor move is `ESC [ row ; col H`, six to ten bytes on a normal sized terminal.
+const JOIN_GAP: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RenderStats {
+    /// How many times a component was drawn, counting partial redraws.
+    pub components_drawn: usize,
+    pub cells_changed: usize,
+    pub bytes_written: usize,
+}
+
+pub struct RetainedScreen {
+    screen: Screen,
+    dirty: Vec<bool>,
+    // Where each component was drawn in `shown`.
+    areas: Vec<Rect>,
+    // Regions left behind by removed components.
+    cleared: Vec<Rect>,
+    // What the terminal shows, or None before the first render.
+    shown: Option<FrameBuffer>,
+}
+
+impl RetainedScreen {
+    pub fn new(screen: Screen) -> RetainedScreen {
+        RetainedScreen {
+            dirty: vec![true; screen.components.len()],
+            screen,
+            areas: vec![],
+            cleared: vec![],
+            shown: None,
+        }
+    }
+
+    pub fn screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    /// Gives access to a component, or lets it be replaced, and marks it for redrawing.
+    pub fn component_mut(&mut self, index: usize) -> &mut Box<dyn Draw> {
+        self.dirty[index] = true;
+        &mut self.screen.components[index]
+    }
+
+    pub fn mark_dirty(&mut self, index: usize) {
+        self.dirty[index] = true;
+    }
+
+    pub fn push(&mut self, component: Box<dyn Draw>) {
+        self.screen.components.push(component);
+        self.dirty.push(true);
+    }
+
+    pub fn remove(&mut self, index: usize) -> Box<dyn Draw> {
+        self.dirty.remove(index);
+        if index < self.areas.len() {
+            self.cleared.push(self.areas.remove(index));
+        }
+        self.screen.components.remove(index)
+    }
+
+    /// The frame as of the last render.
+    pub fn frame(&self) -> Option<&FrameBuffer> {
+        self.shown.as_ref()
+    }
+
+    /// Redraws what changed and writes the difference to `out`. The first render clears the
+    /// terminal and writes everything.
+    pub fn render<W: Write>(&mut self, out: &mut W) -> io::Result<RenderStats> {
+        let areas = self.screen.areas();
+        let width = areas.iter().map(|a| a.right()).max().unwrap_or(0);
+        let height = areas.iter().map(|a| a.bottom()).max().unwrap_or(0);
+
+        let mut regions = std::mem::take(&mut self.cleared);
+        for (i, area) in areas.iter().enumerate() {
+            let previous = self.areas.get(i).copied();
+            if self.dirty[i] || previous != Some(*area) {
+                regions.push(*area);
+                regions.extend(previous.filter(|p| p != area));
+            }
+        }
+        // A region inside another one would only be drawn twice.
+        let regions: Vec<Rect> = regions
+            .iter()
+            .enumerate()
+            .filter(|(i, r)| {
+                r.width > 0
+                    && r.height > 0
+                    && !regions.iter().enumerate().any(|(j, other)| {
+                        j != *i && contains(other, r) && (j < *i || !contains(r, other))
+                    })
+            })
+            .map(|(_, r)| *r)
+            .collect();
+
+        let mut bytes = vec![];
+        let previous = match self.shown.take() {
+            Some(frame) => frame,
+            None => {
+                bytes.extend_from_slice(b"\x1b[2J");
+                FrameBuffer::new(0, 0)
+            }
+        };
+
+        // Only rows with a dirty region can change, unless the frame changes size. Keep a copy
+        // of those rows as they are now, to compare with afterwards.
+        let span = width.max(previous.width());
+        let rows: BTreeSet<u32> = if previous.width() == width && previous.height() == height {
+            regions.iter().flat_map(|r| r.y..r.bottom().min(height)).collect()
+        } else {
+            (0..height.max(previous.height())).collect()
+        };
+        let old_rows: Vec<(u32, Vec<char>)> =
+            rows.into_iter().map(|y| (y, row(&previous, y, span))).collect();
+
+        let mut next = resized(previous, width, height);
+        let mut stats = RenderStats::default();
+
+        for region in &regions {
+            next.fill(*region, ' ');
+            for (component, area) in self.screen.components.iter().zip(&areas) {
+                let overlap = area.intersect(region);
+                if overlap.width > 0 && overlap.height > 0 {
+                    next.clipped(overlap, |frame| component.draw(frame, *area));
+                    stats.components_drawn += 1;
+                }
+            }
+        }
+
+        stats.cells_changed = diff(&old_rows, &next, span, &mut bytes);
+        if !bytes.is_empty() {
+            // Leave the cursor below the screen, where the next output belongs.
+            bytes.extend_from_slice(format!("\x1b[{};1H", height + 1).as_bytes());
+        }
+        out.write_all(&bytes)?;
+        out.flush()?;
+        stats.bytes_written = bytes.len();
+
+        self.shown = Some(next);
+        self.areas = areas;
+        self.dirty.iter_mut().for_each(|d| *d = false);
+        Ok(stats)
+    }
+}
+
+fn contains(outer: &Rect, inner: &Rect) -> bool {
+    outer.intersect(inner) == *inner
+}
+
+// A copy of `frame` with a new size. Cells that don't fit are dropped; new cells are blank.
+fn resized(frame: FrameBuffer, width: u32, height: u32) -> FrameBuffer {
+    if frame.width() == width && frame.height() == height {
+        return frame;
+    }
+
+    let mut copy = FrameBuffer::new(width, height);
+    for y in 0..height.min(frame.height()) {
+        for x in 0..width.min(frame.width()) {
+            copy.put(x, y, frame.get(x, y).unwrap_or(' '));
+        }
+    }
+    copy
+}
+
+// `span` cells of row `y`, where cells outside the frame count as blank.
+fn row(frame: &FrameBuffer, y: u32, span: u32) -> Vec<char> {
+    (0..span).map(|x| frame.get(x, y).unwrap_or(' ')).collect()
+}
+
+// Writes the escape sequences that turn the `old` rows into the same rows of `new` on the
+// terminal and returns how many cells changed. Cells outside `new` count as blank, so a
+// shrinking screen gets erased.
+fn diff(old: &[(u32, Vec<char>)], new: &FrameBuffer, span: u32, out: &mut Vec<u8>) -> usize {
+    let mut changed = 0;
+
+    for (y, old_row) in old {
+        let new_row = row(new, *y, span);
+        let differs = |x: usize| old_row[x] != new_row[x];
+
+        let mut x = 0;
+        while x < new_row.len() {
+            if !differs(x) {
+                x += 1;
+                continue;
+            }
+
+            // A run starts here; extend it over changed cells and short unchanged gaps.
+            let start = x;
+            let mut end = x + 1;
+            changed += 1;
+            let mut probe = end;
+            while probe < new_row.len() && probe - end <= JOIN_GAP {
+                if differs(probe) {
+                    changed += 1;
+                    end = probe + 1;
+                }
+                probe += 1;
+            }
+
+            let text: String = new_row[start..end].iter().collect();
+            out.extend_from_slice(format!("\x1b[{};{}H{}", y + 1, start + 1, text).as_bytes());
+            x = end;
+        }
+    }
+
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object_oriented::widgets::{Label, ProgressBar, Style};
+
+    fn label(text: &str) -> Box<dyn Draw> {
+        Box::new(Label {
+            text: String::from(text),
+            style: Style::default(),
+        })
+    }
+
+    fn bar(progress: f64) -> Box<dyn Draw> {
+        Box::new(ProgressBar {
+            width: 20,
+            progress,
+            style: Style::default(),
+        })
+    }
+
+    fn render(screen: &mut RetainedScreen) -> (RenderStats, String) {
+        let mut out = vec![];
+        let stats = screen.render(&mut out).unwrap();
+        (stats, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn first_render_draws_everything() {
+        let mut screen = RetainedScreen::new(Screen {
+            components: vec![label("one"), label("two")],
+        });
+
+        let (stats, out) = render(&mut screen);
+
+        assert_eq!(stats.components_drawn, 2);
+        assert_eq!(out, "\x1b[2J\x1b[1;1Hone\x1b[2;1Htwo\x1b[3;1H");
+        assert_eq!(screen.frame().unwrap().to_string(), "one\ntwo\n");
+    }
+
+    #[test]
+    fn only_changed_cells_are_written() {
+        let mut screen = RetainedScreen::new(Screen {
+            components: vec![label("title"), bar(0.1), label("footer")],
+        });
+        render(&mut screen);
+
+        let (stats, out) = render(&mut screen);
+        assert_eq!((stats.components_drawn, stats.bytes_written), (0, 0));
+        assert!(out.is_empty());
+
+        // Dirty but unchanged: redrawn, yet nothing reaches the terminal.
+        screen.mark_dirty(0);
+        let (stats, out) = render(&mut screen);
+        assert_eq!((stats.components_drawn, stats.cells_changed), (1, 0));
+        assert!(out.is_empty());
+
+        *screen.component_mut(1) = bar(0.5);
+        let (stats, out) = render(&mut screen);
+        assert_eq!(stats.components_drawn, 1);
+        assert_eq!(out, "\x1b[2;3H######\x1b[2;18H5\x1b[4;1H");
+        assert_eq!(screen.frame().unwrap().lines()[1], "[#######......]  50%");
+    }
+
+    #[test]
+    fn components_that_move_or_disappear_are_erased() {
+        let mut screen = RetainedScreen::new(Screen {
+            components: vec![label("a\nb"), label("c"), label("d")],
+        });
+        render(&mut screen);
+
+        screen.remove(0);
+        let (stats, out) = render(&mut screen);
+
+        assert_eq!(stats.components_drawn, 2);
+        assert_eq!(out, "\x1b[1;1Hc\x1b[2;1Hd\x1b[3;1H \x1b[4;1H \x1b[3;1H");
+        assert_eq!(screen.frame().unwrap().to_string(), "c\nd\n");
+    }
+}
@@ -0,0 +1,926 @@
+// Screens described in a JSON file instead of Rust literals:
+//
+//     {
+//       "components": [
+//         { "type": "select_box", "width": 12, "height": 5, "options": ["Yes", "No"] },
+//         { "type": "hstack", "spacing": 1, "children": [
+//           { "type": "button", "label": "OK", "width": 6, "height": 3 },
+//           { "type": "button", "label": "Quit", "width": 8, "height": 3, "constraint": "fill:2" }
+//         ]}
+//       ]
+//     }
+//
+// Each object's "type" picks a builder from a Registry, which turns the object into a
+// Box<dyn Draw>. The built-in components and layouts are registered by `Registry::default`, and
+// any other component joins by registering a builder under a new name. Builders read their
+// fields through Spec, which checks types as it goes; once a builder is done, any field it
+// didn't read is reported as unknown, so typos don't slip through silently.
+//
+// Children of a stack can carry a "constraint": "auto", "fill", "fill:N", "length:N" or
+// "percent:N" (see layout::Constraint). Callbacks such as Button::on_click can't be written in a
+// file, so they are left empty.
+//
+// Every error carries the line and column of the value (or key) it is about. Objects and arrays
+// can be nested at most MAX_DEPTH deep, so a hostile file can't exhaust the stack, and sizes,
+// spacing, padding and constraints are at most MAX_CELLS (percentages at most 100), so a file
+// that loads can also be drawn.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::object_oriented::layout::{Constraint, Grid, HStack, Padding, VStack};
+use crate::object_oriented::widgets::{
+    Align, Checkbox, Column, Label, List, ProgressBar, Style, Table, TextInput,
+};
+use crate::object_oriented::{Button, Draw, Screen, SelectBox};
+
+const MAX_DEPTH: usize = 128;
+const MAX_CELLS: u32 = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreenFileError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScreenFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ScreenFileError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Node>),
+    Object(Vec<Field>),
+}
+
+/// A parsed value and where it starts in the file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub line: usize,
+    pub column: usize,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub line: usize,
+    pub column: usize,
+    pub name: String,
+    pub value: Node,
+}
+
+impl Node {
+    pub fn error(&self, message: impl Into<String>) -> ScreenFileError {
+        ScreenFileError {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self.value {
+            Value::Null => "null",
+            Value::Bool(_) => "a boolean",
+            Value::Number(_) => "a number",
+            Value::String(_) => "a string",
+            Value::Array(_) => "an array",
+            Value::Object(_) => "an object",
+        }
+    }
+}
+
+type Children = Vec<(Constraint, Box<dyn Draw>)>;
+
+pub type Builder = Box<dyn Fn(&Spec, &Registry) -> Result<Box<dyn Draw>, ScreenFileError>>;
+
+pub struct Registry {
+    builders: HashMap<String, Builder>,
+}
+
+impl Registry {
+    /// A registry that knows no components at all.
+    pub fn empty() -> Registry {
+        Registry {
+            builders: HashMap::new(),
+        }
+    }
+
+    /// Makes `type_name` available in screen files. Registering a name again replaces the
+    /// earlier builder, so the built-in components can be overridden too.
+    pub fn register<F>(&mut self, type_name: &str, builder: F)
+    where
+        F: Fn(&Spec, &Registry) -> Result<Box<dyn Draw>, ScreenFileError> + 'static,
+    {
+        self.builders.insert(String::from(type_name), Box::new(builder));
+    }
+
+    pub fn knows(&self, type_name: &str) -> bool {
+        self.builders.contains_key(type_name)
+    }
+
+    /// Builds one component from an object with a "type" field.
+    pub fn build(&self, node: &Node) -> Result<Box<dyn Draw>, ScreenFileError> {
+        let spec = Spec::new(node)?;
+        self.build_spec(&spec)
+    }
+
+    fn build_spec(&self, spec: &Spec) -> Result<Box<dyn Draw>, ScreenFileError> {
+        let type_node = spec
+            .field("type")
+            .ok_or_else(|| spec.error("component has no \"type\""))?;
+        let type_name = match &type_node.value {
+            Value::String(name) => name,
+            _ => {
+                let message = format!("\"type\" must be a string, not {}", type_node.kind());
+                return Err(type_node.error(message));
+            }
+        };
+        let builder = self
+            .builders
+            .get(type_name)
+            .ok_or_else(|| type_node.error(format!("unknown component type `{}`", type_name)))?;
+
+        let component = builder(spec, self)?;
+        spec.finish()?;
+        Ok(component)
+    }
+
+    /// Parses a whole screen: an object with a "components" array.
+    pub fn parse_screen(&self, source: &str) -> Result<Screen, ScreenFileError> {
+        let root = parse(source)?;
+        let spec = Spec::new(&root)?;
+        let components = spec.components("components", self)?;
+        spec.finish()?;
+        Ok(Screen { components })
+    }
+
+    /// Reads and parses a screen file. Errors in the file come back as InvalidData, with the
+    /// path, line and column in the message.
+    pub fn load_screen<P: AsRef<Path>>(&self, path: P) -> io::Result<Screen> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)?;
+        self.parse_screen(&source).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))
+        })
+    }
+}
+
+impl Default for Registry {
+    /// A registry with every component and layout in object_oriented.
+    fn default() -> Registry {
+        let mut registry = Registry::empty();
+
+        registry.register("button", |spec, _| {
+            Ok(Box::new(Button {
+                width: spec.u32("width")?,
+                height: spec.u32_or("height", 3)?,
+                label: spec.string_or("label", "")?,
+                on_click: None,
+            }))
+        });
+        registry.register("select_box", |spec, _| {
+            let options = spec.strings("options")?;
+            let selected = spec.optional_u32("selected")?.map(|i| i as usize);
+            if let Some(i) = selected.filter(|i| *i >= options.len()) {
+                return Err(spec.field("selected").unwrap().error(format!(
+                    "option {} is selected but there are only {} options",
+                    i,
+                    options.len()
+                )));
+            }
+            Ok(Box::new(SelectBox {
+                width: spec.u32("width")?,
+                height: spec.u32_or("height", options.len() as u32 + 2)?,
+                options,
+                selected,
+            }))
+        });
+        registry.register("label", |spec, _| {
+            Ok(Box::new(Label {
+                text: spec.string("text")?,
+                style: spec.style()?,
+            }))
+        });
+        registry.register("text_input", |spec, _| {
+            let mut input = TextInput::new(spec.u32("width")?);
+            input.value = spec.string_or("value", "")?;
+            input.cursor = input.value.chars().count();
+            input.placeholder = spec.string_or("placeholder", "")?;
+            input.style = spec.style()?;
+            Ok(Box::new(input))
+        });
+        registry.register("checkbox", |spec, _| {
+            Ok(Box::new(Checkbox {
+                label: spec.string("label")?,
+                checked: spec.bool_or("checked", false)?,
+                style: spec.style()?,
+            }))
+        });
+        registry.register("progress_bar", |spec, _| {
+            Ok(Box::new(ProgressBar {
+                width: spec.u32("width")?,
+                progress: spec.f64_or("progress", 0.0)?,
+                style: spec.style()?,
+            }))
+        });
+        registry.register("table", |spec, _| {
+            let columns = spec
+                .objects("columns")?
+                .iter()
+                .map(|column| {
+                    let built = Column {
+                        title: column.string("title")?,
+                        width: column.u32("width")?,
+                    };
+                    column.finish()?;
+                    Ok(built)
+                })
+                .collect::<Result<Vec<_>, ScreenFileError>>()?;
+            let rows = match spec.field("rows") {
+                Some(node) => array(node)?.iter().map(strings).collect::<Result<_, _>>()?,
+                None => vec![],
+            };
+            Ok(Box::new(Table {
+                columns,
+                rows,
+                style: spec.style()?,
+            }))
+        });
+        registry.register("list", |spec, _| {
+            Ok(Box::new(List {
+                width: spec.u32("width")?,
+                height: spec.u32("height")?,
+                items: spec.strings("items")?,
+                selected: None,
+                offset: 0,
+                style: spec.style()?,
+            }))
+        });
+        registry.register("vstack", |spec, registry| {
+            Ok(Box::new(VStack {
+                spacing: spec.u32_or("spacing", 0)?,
+                children: spec.children("children", registry)?,
+            }))
+        });
+        registry.register("hstack", |spec, registry| {
+            Ok(Box::new(HStack {
+                spacing: spec.u32_or("spacing", 0)?,
+                children: spec.children("children", registry)?,
+            }))
+        });
+        registry.register("grid", |spec, registry| {
+            Ok(Box::new(Grid {
+                columns: spec.u32("columns")?.max(1) as usize,
+                cells: spec.components("cells", registry)?,
+            }))
+        });
+        registry.register("padding", |spec, registry| {
+            let all = spec.u32_or("padding", 0)?;
+            Ok(Box::new(Padding {
+                top: spec.u32_or("top", all)?,
+                right: spec.u32_or("right", all)?,
+                bottom: spec.u32_or("bottom", all)?,
+                left: spec.u32_or("left", all)?,
+                child: spec.component("child", registry)?,
+            }))
+        });
+
+        registry
+    }
+}
+
+/// The fields of one object, as seen by a builder. Reading a field marks it as used.
+pub struct Spec<'a> {
+    node: &'a Node,
+    fields: &'a [Field],
+    used: RefCell<Vec<&'a str>>,
+}
+
+impl<'a> Spec<'a> {
+    pub fn new(node: &'a Node) -> Result<Spec<'a>, ScreenFileError> {
+        match &node.value {
+            Value::Object(fields) => Ok(Spec {
+                node,
+                fields,
+                used: RefCell::new(vec!["type"]),
+            }),
+            _ => Err(node.error(format!("expected an object, found {}", node.kind()))),
+        }
+    }
+
+    /// An error pointing at the start of the object.
+    pub fn error(&self, message: impl Into<String>) -> ScreenFileError {
+        self.node.error(message)
+    }
+
+    pub fn field(&self, name: &str) -> Option<&'a Node> {
+        let field = self.fields.iter().find(|f| f.name == name)?;
+        self.used.borrow_mut().push(&field.name);
+        Some(&field.value)
+    }
+
+    /// Fails on the first field that hasn't been read.
+    pub fn finish(&self) -> Result<(), ScreenFileError> {
+        let used = self.used.borrow();
+        match self.fields.iter().find(|f| !used.contains(&f.name.as_str())) {
+            Some(field) => Err(ScreenFileError {
+                line: field.line,
+                column: field.column,
+                message: format!("unknown field `{}`", field.name),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn required(&self, name: &str) -> Result<&'a Node, ScreenFileError> {
+        self.field(name)
+            .ok_or_else(|| self.error(format!("missing field \"{}\"", name)))
+    }
+
+    pub fn optional_u32(&self, name: &str) -> Result<Option<u32>, ScreenFileError> {
+        self.field(name).map(number_u32).transpose()
+    }
+
+    pub fn u32(&self, name: &str) -> Result<u32, ScreenFileError> {
+        number_u32(self.required(name)?)
+    }
+
+    pub fn u32_or(&self, name: &str, default: u32) -> Result<u32, ScreenFileError> {
+        Ok(self.optional_u32(name)?.unwrap_or(default))
+    }
+
+    pub fn f64_or(&self, name: &str, default: f64) -> Result<f64, ScreenFileError> {
+        match self.field(name) {
+            Some(Node { value: Value::Number(n), .. }) => Ok(*n),
+            Some(node) => Err(node.error(format!("expected a number, found {}", node.kind()))),
+            None => Ok(default),
+        }
+    }
+
+    pub fn bool_or(&self, name: &str, default: bool) -> Result<bool, ScreenFileError> {
+        match self.field(name) {
+            Some(Node { value: Value::Bool(b), .. }) => Ok(*b),
+            Some(node) => Err(node.error(format!("expected true or false, found {}", node.kind()))),
+            None => Ok(default),
+        }
+    }
+
+    pub fn string(&self, name: &str) -> Result<String, ScreenFileError> {
+        string(self.required(name)?)
+    }
+
+    pub fn string_or(&self, name: &str, default: &str) -> Result<String, ScreenFileError> {
+        match self.field(name) {
+            Some(node) => string(node),
+            None => Ok(String::from(default)),
+        }
+    }
+
+    /// An array of strings; a missing field is an empty array.
+    pub fn strings(&self, name: &str) -> Result<Vec<String>, ScreenFileError> {
+        match self.field(name) {
+            Some(node) => strings(node),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// An array of objects, each with its own Spec. Call `finish` on each one when done.
+    pub fn objects(&self, name: &str) -> Result<Vec<Spec<'a>>, ScreenFileError> {
+        array(self.required(name)?)?.iter().map(Spec::new).collect()
+    }
+
+    /// "border" (true or false) and "align" ("left", "center" or "right").
+    pub fn style(&self) -> Result<Style, ScreenFileError> {
+        let align = match self.field("align") {
+            None => Align::Left,
+            Some(node) => match string(node)?.as_str() {
+                "left" => Align::Left,
+                "center" => Align::Center,
+                "right" => Align::Right,
+                other => return Err(node.error(format!("unknown alignment `{}`", other))),
+            },
+        };
+        Ok(Style {
+            border: self.bool_or("border", false)?,
+            align,
+        })
+    }
+
+    pub fn component(&self, name: &str, registry: &Registry) -> Result<Box<dyn Draw>, ScreenFileError> {
+        registry.build(self.required(name)?)
+    }
+
+    pub fn components(&self, name: &str, registry: &Registry) -> Result<Vec<Box<dyn Draw>>, ScreenFileError> {
+        array(self.required(name)?)?
+            .iter()
+            .map(|node| registry.build(node))
+            .collect()
+    }
+
+    /// Components together with their "constraint", for stacks.
+    pub fn children(
+        &self,
+        name: &str,
+        registry: &Registry,
+    ) -> Result<Children, ScreenFileError> {
+        array(self.required(name)?)?
+            .iter()
+            .map(|node| {
+                let child = Spec::new(node)?;
+                let constraint = match child.field("constraint") {
+                    Some(node) => constraint(node)?,
+                    None => Constraint::Auto,
+                };
+                Ok((constraint, registry.build_spec(&child)?))
+            })
+            .collect()
+    }
+}
+
+fn number_u32(node: &Node) -> Result<u32, ScreenFileError> {
+    match node.value {
+        Value::Number(n) if n >= 0.0 && n <= MAX_CELLS as f64 && n.fract() == 0.0 => Ok(n as u32),
+        Value::Number(n) if n > MAX_CELLS as f64 => {
+            Err(node.error(format!("expected at most {} cells, found {}", MAX_CELLS, n)))
+        }
+        Value::Number(n) => Err(node.error(format!("expected a whole number of cells, found {}", n))),
+        _ => Err(node.error(format!("expected a number, found {}", node.kind()))),
+    }
+}
+
+fn string(node: &Node) -> Result<String, ScreenFileError> {
+    match &node.value {
+        Value::String(s) => Ok(s.clone()),
+        _ => Err(node.error(format!("expected a string, found {}", node.kind()))),
+    }
+}
+
+fn array(node: &Node) -> Result<&[Node], ScreenFileError> {
+    match &node.value {
+        Value::Array(items) => Ok(items),
+        _ => Err(node.error(format!("expected an array, found {}", node.kind()))),
+    }
+}
+
+fn strings(node: &Node) -> Result<Vec<String>, ScreenFileError> {
+    array(node)?.iter().map(string).collect()
+}
+
+fn constraint(node: &Node) -> Result<Constraint, ScreenFileError> {
+    let text = string(node)?;
+    let (name, argument) = match text.split_once(':') {
+        Some((name, argument)) => (name, Some(argument)),
+        None => (text.as_str(), None),
+    };
+    let number = |default: Option<u32>, most: u32| match argument {
+        Some(argument) => match argument.parse::<u32>() {
+            Ok(n) if n > most => Err(node.error(format!("`{}` can be at most {}", name, most))),
+            n => Ok(n.ok()),
+        },
+        None => Ok(default),
+    };
+
+    let constraint = match name {
+        "auto" if argument.is_none() => Some(Constraint::Auto),
+        "fill" => number(Some(1), MAX_CELLS)?.map(Constraint::Fill),
+        "length" => number(None, MAX_CELLS)?.map(Constraint::Length),
+        "percent" => number(None, 100)?.map(Constraint::Percent),
+        _ => None,
+    };
+    constraint.ok_or_else(|| node.error(format!("unknown constraint `{}`", text)))
+}
+
+/// Parses a JSON document, keeping the position of every value and key.
+pub fn parse(source: &str) -> Result<Node, ScreenFileError> {
+    let mut parser = Parser {
+        chars: source.chars().collect(),
+        pos: 0,
+        line: 1,
+        column: 1,
+        depth: 0,
+    };
+
+    let node = parser.value()?;
+    parser.skip_whitespace();
+    if parser.peek().is_some() {
+        return Err(parser.error("unexpected text after the end of the document"));
+    }
+    Ok(node)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    column: usize,
+    /// How many objects and arrays the parser is inside of.
+    depth: usize,
+}
+
+impl Parser {
+    fn error(&self, message: impl Into<String>) -> ScreenFileError {
+        ScreenFileError {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.bump();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ScreenFileError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("expected `{}`, found `{}`", expected, c))),
+            None => Err(self.error(format!("expected `{}`, found the end of the file", expected))),
+        }
+    }
+
+    fn value(&mut self) -> Result<Node, ScreenFileError> {
+        self.skip_whitespace();
+        let (line, column) = (self.line, self.column);
+
+        let value = match self.peek() {
+            Some(c @ ('{' | '[')) => {
+                if self.depth == MAX_DEPTH {
+                    return Err(self.error(format!("nested more than {} levels deep", MAX_DEPTH)));
+                }
+                self.depth += 1;
+                let value = if c == '{' { self.object() } else { self.array() };
+                self.depth -= 1;
+                value?
+            }
+            Some('"') => Value::String(self.string()?),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number()?,
+            Some(c) if c.is_alphabetic() => match self.word().as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                "null" => Value::Null,
+                word => {
+                    return Err(ScreenFileError {
+                        line,
+                        column,
+                        message: format!("unexpected `{}`", word),
+                    })
+                }
+            },
+            Some(c) => return Err(self.error(format!("unexpected `{}`", c))),
+            None => return Err(self.error("unexpected end of the file")),
+        };
+
+        Ok(Node { line, column, value })
+    }
+
+    fn object(&mut self) -> Result<Value, ScreenFileError> {
+        self.expect('{')?;
+        let mut fields: Vec<Field> = vec![];
+
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.bump();
+            return Ok(Value::Object(fields));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let (line, column) = (self.line, self.column);
+            if self.peek() != Some('"') {
+                return Err(self.error("expected a field name in double quotes"));
+            }
+            let name = self.string()?;
+            if fields.iter().any(|f| f.name == name) {
+                return Err(ScreenFileError {
+                    line,
+                    column,
+                    message: format!("field `{}` appears twice", name),
+                });
+            }
+
+            self.skip_whitespace();
+            self.expect(':')?;
+            let value = self.value()?;
+            fields.push(Field { line, column, name, value });
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => {
+                    self.bump();
+                }
+                Some('}') => {
+                    self.bump();
+                    return Ok(Value::Object(fields));
+                }
+                _ => return Err(self.error("expected `,` or `}`")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, ScreenFileError> {
+        self.expect('[')?;
+        let mut items = vec![];
+
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.bump();
+            return Ok(Value::Array(items));
+        }
+
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => {
+                    self.bump();
+                }
+                Some(']') => {
+                    self.bump();
+                    return Ok(Value::Array(items));
+                }
+                _ => return Err(self.error("expected `,` or `]`")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ScreenFileError> {
+        self.expect('"')?;
+        let mut text = String::new();
+
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(text),
+                Some('\\') => {
+                    let escaped = match self.bump() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.unicode_escape()?,
+                        Some(c) => return Err(self.error(format!("invalid escape `\\{}`", c))),
+                        None => return Err(self.error("unterminated string")),
+                    };
+                    text.push(escaped);
+                }
+                Some('\n') | None => return Err(self.error("unterminated string")),
+                Some(c) => text.push(c),
+            }
+        }
+    }
+
+    // The rest of a `\uXXXX` escape. Characters outside the Basic Multilingual Plane are written
+    // as two escapes, a high surrogate followed by a low one.
+    fn unicode_escape(&mut self) -> Result<char, ScreenFileError> {
+        let high = self.hex4()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high)
+                .ok_or_else(|| self.error(format!("unpaired surrogate `\\u{:04x}`", high)));
+        }
+
+        if self.peek() != Some('\\') || self.chars.get(self.pos + 1) != Some(&'u') {
+            return Err(self.error(format!("unpaired surrogate `\\u{:04x}`", high)));
+        }
+        self.bump();
+        self.bump();
+        let low = self.hex4()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err(self.error(format!("`\\u{:04x}` can't follow the surrogate `\\u{:04x}`", low, high)));
+        }
+        let code = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+        Ok(char::from_u32(code).expect("surrogate pairs decode to valid characters"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, ScreenFileError> {
+        let hex: String = (0..4).filter_map(|_| self.bump()).collect();
+        match u32::from_str_radix(&hex, 16) {
+            Ok(n) if hex.len() == 4 && hex.chars().all(|c| c.is_ascii_hexdigit()) => Ok(n),
+            _ => Err(self.error(format!("invalid escape `\\u{}`", hex))),
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, ScreenFileError> {
+        let (line, column) = (self.line, self.column);
+        let mut text = String::new();
+        while let Some(c) = self.peek().filter(|c| c.is_ascii_digit() || "+-.eE".contains(*c)) {
+            text.push(c);
+            self.bump();
+        }
+
+        text.parse().map(Value::Number).map_err(|_| ScreenFileError {
+            line,
+            column,
+            message: format!("invalid number `{}`", text),
+        })
+    }
+
+    fn word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(c) = self.peek().filter(|c| c.is_alphanumeric()) {
+            word.push(c);
+            self.bump();
+        }
+        word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object_oriented::frame::{FrameBuffer, Rect, Size};
+
+    const FORM: &str = r#"{
+  "components": [
+    { "type": "select_box", "width": 12, "options": ["Yes", "Maybe", "No"], "selected": 1 },
+    { "type": "hstack", "children": [
+      { "type": "button", "label": "OK", "width": 6 },
+      { "type": "button", "label": "Quit", "width": 6, "constraint": "length:8" }
+    ]},
+    { "type": "label", "text": "Saved ✓" }
+  ]
+}"#;
+
+    fn error(source: &str) -> ScreenFileError {
+        match Registry::default().parse_screen(source) {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn builds_nested_components_from_json() {
+        let screen = Registry::default().parse_screen(FORM).unwrap();
+
+        assert_eq!(
+            screen.frame().to_string(),
+            "\
++----------+
+|( ) Yes   |
+|(*) Maybe |
+|( ) No    |
++----------+
++----++------+
+| OK || Quit |
++----++------+
+Saved ✓
+"
+        );
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_line() {
+        let source = FORM.replace("\"width\": 12", "\"width\": \"wide\"");
+        assert_eq!(
+            error(&source),
+            ScreenFileError {
+                line: 3,
+                column: 38,
+                message: String::from("expected a number, found a string"),
+            }
+        );
+
+        let source = FORM.replace("\"label\": \"OK\"", "\"lable\": \"OK\"");
+        let e = error(&source);
+        assert_eq!((e.line, e.message.as_str()), (5, "unknown field `lable`"));
+
+        let source = FORM.replace("hstack", "hbox");
+        let e = error(&source);
+        assert_eq!((e.line, e.message.as_str()), (4, "unknown component type `hbox`"));
+
+        assert_eq!(error("{\n  \"components\": [\n    {\"type\": \"button\"}\n  ]\n}").line, 3);
+        assert_eq!(error("{\n  \"components\": [,]\n}").to_string(), "line 2, column 18: unexpected `,`");
+        assert_eq!(error(&FORM.replace("length:8", "length")).line, 6);
+    }
+
+    struct Star;
+
+    impl Draw for Star {
+        fn size(&self) -> Size {
+            Size::new(1, 1)
+        }
+
+        fn draw(&self, frame: &mut FrameBuffer, area: Rect) {
+            frame.put(area.x, area.y, '*');
+        }
+    }
+
+    #[test]
+    fn third_party_components_can_join_the_registry() {
+        let mut registry = Registry::default();
+        registry.register("star", |_, _| Ok(Box::new(Star)));
+        assert!(registry.knows("star"));
+
+        let screen = registry
+            .parse_screen(r#"{"components": [{"type": "grid", "columns": 3, "cells": [{"type": "star"}, {"type": "star"}, {"type": "star"}]}]}"#)
+            .unwrap();
+        assert_eq!(screen.frame().to_string(), "***\n");
+
+        assert!(Registry::empty().parse_screen(FORM).is_err());
+    }
+
+    #[test]
+    fn strings_decode_surrogate_pairs() {
+        let text = |source: &str| match parse(source).map(|node| node.value) {
+            Ok(Value::String(s)) => s,
+            other => panic!("expected a string, got {:?}", other),
+        };
+        assert_eq!(text(r#""\ud83e\udd80 \u00e9""#), "🦀 é");
+
+        assert_eq!(parse(r#""\ud83e""#).unwrap_err().message, "unpaired surrogate `\\ud83e`");
+        assert_eq!(parse(r#""\udd80""#).unwrap_err().message, "unpaired surrogate `\\udd80`");
+        assert_eq!(
+            parse(r#""\ud83e\u0041""#).unwrap_err().message,
+            "`\\u0041` can't follow the surrogate `\\ud83e`"
+        );
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        assert!(parse(&format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH))).is_ok());
+
+        // Each `[{"a": ` opens two levels in seven characters.
+        let e = parse(&"[{\"a\": ".repeat(100_000)).unwrap_err();
+        assert_eq!((e.line, e.column), (1, 7 * MAX_DEPTH / 2 + 1));
+        assert_eq!(e.message, "nested more than 128 levels deep");
+    }
+
+    #[test]
+    fn sizes_too_big_to_draw_are_errors() {
+        let too_big = |component: &str| {
+            error(&format!("{{\n  \"components\": [\n    {}\n  ]\n}}", component))
+        };
+        let e = too_big(r#"{"type": "button", "width": 4294967295}"#);
+        assert_eq!((e.line, e.column), (3, 33));
+        assert_eq!(e.message, "expected at most 10000 cells, found 4294967295");
+
+        let e = too_big(r#"{"type": "button", "width": 100000, "height": 100000}"#);
+        assert_eq!((e.line, e.column), (3, 33));
+        let e = too_big(r#"{"type": "vstack", "spacing": 4294967295, "children": []}"#);
+        assert_eq!((e.line, e.column), (3, 35));
+        let e = too_big(r#"{"type": "padding", "padding": 4294967295, "child": {"type": "checkbox", "label": "a"}}"#);
+        assert_eq!((e.line, e.column), (3, 36));
+
+        let e = too_big(r#"{"type": "hstack", "children": [{"type": "label", "text": "a", "constraint": "percent:4294967295"}]}"#);
+        assert_eq!((e.line, e.column, e.message.as_str()), (3, 82, "`percent` can be at most 100"));
+        let e = too_big(r#"{"type": "hstack", "children": [{"type": "label", "text": "a", "constraint": "fill:10001"}]}"#);
+        assert_eq!(e.message, "`fill` can be at most 10000");
+
+        let screen = Registry::default()
+            .parse_screen(r#"{"components": [{"type": "button", "width": 10000, "height": 1}]}"#)
+            .unwrap();
+        assert_eq!(screen.frame().to_string().trim_end().chars().count(), 10_000);
+    }
+
+    #[test]
+    fn load_screen_reports_the_path() {
+        let path = std::env::temp_dir().join(format!("screen_file_{}.json", std::process::id()));
+        fs::write(&path, "{\"components\": [}").unwrap();
+
+        let result = Registry::default().load_screen(&path);
+        fs::remove_file(&path).unwrap();
+
+        let Err(e) = result else { panic!("expected an error") };
+
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(e.to_string().starts_with(&path.display().to_string()));
+        assert!(e.to_string().ends_with("line 1, column 17: unexpected `}`"));
+    }
+}
@@ -0,0 +1,567 @@
+// More components for Screen, next to Button and SelectBox.
+//
+// Every widget takes a Style with the same two options: whether to draw a border around the
+// widget (which makes it two cells wider and taller) and how to align its text. TextInput,
+// Checkbox and List react to input through Interact; Label, ProgressBar and Table only draw.
+
+use crate::object_oriented::events::{Event, Interact, Key, MouseKind};
+use crate::object_oriented::frame::{FrameBuffer, Rect, Size};
+use crate::object_oriented::Draw;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Style {
+    pub border: bool,
+    pub align: Align,
+}
+
+impl Style {
+    pub fn bordered() -> Style {
+        Style {
+            border: true,
+            ..Style::default()
+        }
+    }
+
+    fn outer_size(&self, content: Size) -> Size {
+        if self.border {
+            Size::new(content.width + 2, content.height + 2)
+        } else {
+            content
+        }
+    }
+
+    // Draws the border if there is one and returns the area left for the content.
+    fn content_area(&self, frame: &mut FrameBuffer, area: Rect) -> Rect {
+        if self.border {
+            frame.border(area);
+            area.inner()
+        } else {
+            area
+        }
+    }
+
+    // Writes one line of text in row `y` of `area`, aligned and cut off at the edge.
+    fn text(&self, frame: &mut FrameBuffer, area: Rect, y: u32, text: &str) {
+        let len = text.chars().count() as u32;
+        let x = match self.align {
+            Align::Left => area.x,
+            Align::Center => area.x + area.width.saturating_sub(len) / 2,
+            Align::Right => area.x + area.width.saturating_sub(len),
+        };
+        frame.clipped(area, |frame| frame.text(x, y, text));
+    }
+}
+
+fn char_len(text: &str) -> u32 {
+    text.chars().count() as u32
+}
+
+pub struct Label {
+    pub text: String,
+    pub style: Style,
+}
+
+impl Draw for Label {
+    fn size(&self) -> Size {
+        let width = self.text.lines().map(char_len).max().unwrap_or(0);
+        let height = self.text.lines().count() as u32;
+        self.style.outer_size(Size::new(width, height))
+    }
+
+    fn draw(&self, frame: &mut FrameBuffer, area: Rect) {
+        let content = self.style.content_area(frame, area);
+        for (i, line) in self.text.lines().enumerate() {
+            self.style.text(frame, content, content.y + i as u32, line);
+        }
+    }
+}
+
+// A single line of editable text. The cursor is a position between characters: typing inserts
+// at the cursor, Backspace deletes before it and Delete after it. When the text is longer than
+// the input the view scrolls to keep the cursor in sight. The cursor is drawn as a `_` cell
+// between the characters it sits between, so none of the text is hidden under it.
+pub struct TextInput {
+    pub width: u32,
+    pub value: String,
+    /// Position of the cursor, counted in characters.
+    pub cursor: usize,
+    pub placeholder: String,
+    pub style: Style,
+}
+
+impl TextInput {
+    pub fn new(width: u32) -> TextInput {
+        TextInput {
+            width,
+            value: String::new(),
+            cursor: 0,
+            placeholder: String::new(),
+            style: Style::default(),
+        }
+    }
+
+    fn byte_index(&self, cursor: usize) -> usize {
+        self.value
+            .char_indices()
+            .nth(cursor)
+            .map_or(self.value.len(), |(i, _)| i)
+    }
+
+    fn len(&self) -> usize {
+        self.value.chars().count()
+    }
+}
+
+impl Draw for TextInput {
+    fn size(&self) -> Size {
+        self.style.outer_size(Size::new(self.width, 1))
+    }
+
+    fn draw(&self, frame: &mut FrameBuffer, area: Rect) {
+        let content = self.style.content_area(frame, area);
+        if content.width == 0 || content.height == 0 {
+            return;
+        }
+
+        if self.value.is_empty() {
+            frame.put(content.x, content.y, '_');
+            frame.clipped(content, |frame| frame.text(content.x + 1, content.y, &self.placeholder));
+            return;
+        }
+
+        let cursor = self.cursor.min(self.len());
+        let first = (cursor + 1).saturating_sub(content.width as usize);
+        let before: String = self.value.chars().skip(first).take(cursor - first).collect();
+        let after: String = self.value.chars().skip(cursor).collect();
+        let cursor_x = content.x + (cursor - first) as u32;
+
+        frame.clipped(content, |frame| {
+            frame.text(content.x, content.y, &before);
+            frame.put(cursor_x, content.y, '_');
+            frame.text(cursor_x + 1, content.y, &after);
+        });
+    }
+
+    fn interact(&mut self) -> Option<&mut dyn Interact> {
+        Some(self)
+    }
+}
+
+impl Interact for TextInput {
+    fn handle_event(&mut self, event: &Event, _area: Rect) -> bool {
+        self.cursor = self.cursor.min(self.len());
+
+        match event {
+            Event::Key(Key::Char(c)) => {
+                let at = self.byte_index(self.cursor);
+                self.value.insert(at, *c);
+                self.cursor += 1;
+            }
+            Event::Key(Key::Backspace) if self.cursor > 0 => {
+                self.cursor -= 1;
+                let at = self.byte_index(self.cursor);
+                self.value.remove(at);
+            }
+            Event::Key(Key::Delete) if self.cursor < self.len() => {
+                let at = self.byte_index(self.cursor);
+                self.value.remove(at);
+            }
+            Event::Key(Key::Left) => self.cursor = self.cursor.saturating_sub(1),
+            Event::Key(Key::Right) => self.cursor = (self.cursor + 1).min(self.len()),
+            Event::Key(Key::Home) => self.cursor = 0,
+            Event::Key(Key::End) => self.cursor = self.len(),
+            Event::Mouse { kind: MouseKind::Click, .. } => {}
+            _ => return false,
+        }
+        true
+    }
+}
+
+pub struct Checkbox {
+    pub label: String,
+    pub checked: bool,
+    pub style: Style,
+}
+
+impl Draw for Checkbox {
+    fn size(&self) -> Size {
+        self.style.outer_size(Size::new(4 + char_len(&self.label), 1))
+    }
+
+    fn draw(&self, frame: &mut FrameBuffer, area: Rect) {
+        let content = self.style.content_area(frame, area);
+        let mark = if self.checked { 'x' } else { ' ' };
+        self.style
+            .text(frame, content, content.y, &format!("[{}] {}", mark, self.label));
+    }
+
+    fn interact(&mut self) -> Option<&mut dyn Interact> {
+        Some(self)
+    }
+}
+
+// Space, Enter or a click toggles the box.
+impl Interact for Checkbox {
+    fn handle_event(&mut self, event: &Event, _area: Rect) -> bool {
+        let toggle = matches!(
+            event,
+            Event::Key(Key::Char(' ')) | Event::Key(Key::Enter) | Event::Mouse { kind: MouseKind::Click, .. }
+        );
+        if toggle {
+            self.checked = !self.checked;
+        }
+        toggle
+    }
+}
+
+// Drawn as `[####......]  40%`; the percentage takes the last five cells.
+pub struct ProgressBar {
+    pub width: u32,
+    /// How much is done, from 0.0 to 1.0. Values outside that range are clamped.
+    pub progress: f64,
+    pub style: Style,
+}
+
+impl Draw for ProgressBar {
+    fn size(&self) -> Size {
+        self.style.outer_size(Size::new(self.width, 1))
+    }
+
+    fn draw(&self, frame: &mut FrameBuffer, area: Rect) {
+        let content = self.style.content_area(frame, area);
+        let progress = self.progress.clamp(0.0, 1.0);
+
+        let track = content.width.saturating_sub(7) as usize;
+        let done = (track as f64 * progress).round() as usize;
+        let bar = format!(
+            "[{}{}] {:>3}%",
+            "#".repeat(done),
+            ".".repeat(track - done),
+            (progress * 100.0).round() as u32
+        );
+
+        frame.clipped(content, |frame| frame.text(content.x, content.y, &bar));
+    }
+}
+
+pub struct Column {
+    pub title: String,
+    pub width: u32,
+}
+
+// A header row, a rule under it and one row per entry in `rows`, with columns separated by `|`.
+// Cells that are too long for their column are cut off.
+pub struct Table {
+    pub columns: Vec<Column>,
+    pub rows: Vec<Vec<String>>,
+    pub style: Style,
+}
+
+impl Table {
+    fn draw_row(&self, frame: &mut FrameBuffer, content: Rect, y: u32, cells: &[String]) {
+        let mut x = content.x;
+
+        for (i, column) in self.columns.iter().enumerate() {
+            if i > 0 {
+                frame.put(x, y, '|');
+                x += 1;
+            }
+            let cell = Rect::new(x, y, column.width, 1);
+            if let Some(text) = cells.get(i) {
+                self.style.text(frame, cell.intersect(&content), y, text);
+            }
+            x += column.width;
+        }
+    }
+}
+
+impl Draw for Table {
+    fn size(&self) -> Size {
+        let separators = self.columns.len().saturating_sub(1) as u32;
+        let width = self.columns.iter().map(|c| c.width).sum::<u32>() + separators;
+        self.style.outer_size(Size::new(width, self.rows.len() as u32 + 2))
+    }
+
+    fn draw(&self, frame: &mut FrameBuffer, area: Rect) {
+        let content = self.style.content_area(frame, area);
+
+        frame.clipped(content, |frame| {
+            let titles: Vec<String> = self.columns.iter().map(|c| c.title.clone()).collect();
+            self.draw_row(frame, content, content.y, &titles);
+
+            let mut x = content.x;
+            for (i, column) in self.columns.iter().enumerate() {
+                if i > 0 {
+                    frame.put(x, content.y + 1, '+');
+                    x += 1;
+                }
+                frame.text(x, content.y + 1, &"-".repeat(column.width as usize));
+                x += column.width;
+            }
+
+            for (i, row) in self.rows.iter().enumerate() {
+                self.draw_row(frame, content, content.y + 2 + i as u32, row);
+            }
+        });
+    }
+}
+
+// A list that shows `height` items at a time. The selected item is marked with `>` and `offset`
+// is the first visible item. Moving the selection scrolls the list to keep it in view, and the
+// mouse wheel scrolls without moving the selection. `^` and `v` in the last column show there are
+// more items above or below.
+pub struct List {
+    pub width: u32,
+    pub height: u32,
+    pub items: Vec<String>,
+    pub selected: Option<usize>,
+    pub offset: usize,
+    pub style: Style,
+}
+
+impl List {
+    fn max_offset(&self) -> usize {
+        self.items.len().saturating_sub(self.height as usize)
+    }
+
+    fn select(&mut self, index: usize) {
+        let index = index.min(self.items.len().saturating_sub(1));
+        self.selected = Some(index);
+
+        if index < self.offset {
+            self.offset = index;
+        } else if index >= self.offset + self.height as usize {
+            self.offset = index + 1 - self.height as usize;
+        }
+    }
+}
+
+impl Draw for List {
+    fn size(&self) -> Size {
+        self.style.outer_size(Size::new(self.width, self.height))
+    }
+
+    fn draw(&self, frame: &mut FrameBuffer, area: Rect) {
+        let content = self.style.content_area(frame, area);
+        if content.width == 0 {
+            return;
+        }
+        let text_area = Rect::new(content.x + 2, content.y, content.width.saturating_sub(3), content.height);
+
+        let visible = self.items.iter().enumerate().skip(self.offset).take(content.height as usize);
+        for (row, (i, item)) in visible.enumerate() {
+            let y = content.y + row as u32;
+            if self.selected == Some(i) {
+                frame.put(content.x, y, '>');
+            }
+            self.style.text(frame, text_area, y, item);
+        }
+
+        let scrollbar = content.right() - 1;
+        if self.offset > 0 {
+            frame.put(scrollbar, content.y, '^');
+        }
+        if self.offset + (content.height as usize) < self.items.len() {
+            frame.put(scrollbar, content.bottom().saturating_sub(1), 'v');
+        }
+    }
+
+    fn interact(&mut self) -> Option<&mut dyn Interact> {
+        Some(self)
+    }
+}
+
+impl Interact for List {
+    fn handle_event(&mut self, event: &Event, area: Rect) -> bool {
+        if self.items.is_empty() {
+            return false;
+        }
+
+        match event {
+            Event::Key(Key::Up) => self.select(self.selected.map_or(0, |i| i.saturating_sub(1))),
+            Event::Key(Key::Down) => self.select(self.selected.map_or(0, |i| i + 1)),
+            Event::Key(Key::Home) => self.select(0),
+            Event::Key(Key::End) => self.select(self.items.len() - 1),
+            Event::Mouse { kind: MouseKind::ScrollUp, .. } => {
+                self.offset = self.offset.saturating_sub(1)
+            }
+            Event::Mouse { kind: MouseKind::ScrollDown, .. } => {
+                self.offset = (self.offset + 1).min(self.max_offset())
+            }
+            Event::Mouse { y, kind: MouseKind::Click, .. } => {
+                let content = if self.style.border { area.inner() } else { area };
+                if *y < content.y || *y >= content.bottom() {
+                    return false;
+                }
+                let index = self.offset + (y - content.y) as usize;
+                if index >= self.items.len() {
+                    return false;
+                }
+                self.select(index);
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object_oriented::events::Ui;
+    use crate::object_oriented::Screen;
+
+    fn render(component: impl Draw + 'static) -> String {
+        Screen {
+            components: vec![Box::new(component)],
+        }
+        .frame()
+        .to_string()
+    }
+
+    fn ui(component: impl Draw + 'static) -> Ui {
+        Ui::new(Screen {
+            components: vec![Box::new(component)],
+        })
+    }
+
+    fn items(n: usize) -> Vec<String> {
+        (1..=n).map(|i| format!("item {}", i)).collect()
+    }
+
+    #[test]
+    fn label_aligns_lines_inside_its_border() {
+        let label = Label {
+            text: String::from("Hello\nworld!!"),
+            style: Style {
+                border: true,
+                align: Align::Right,
+            },
+        };
+
+        assert_eq!(render(label), "+-------+\n|  Hello|\n|world!!|\n+-------+\n");
+    }
+
+    #[test]
+    fn text_input_edits_at_the_cursor_and_scrolls() {
+        let mut ui = ui(TextInput::new(6));
+
+        ui.play("type:hello left left backspace type:L end type:!!").unwrap();
+        assert_eq!(ui.frame().to_string(), "Llo!!_\n");
+
+        ui.play("left left").unwrap();
+        assert_eq!(ui.frame().to_string(), "heLlo_\n");
+
+        ui.play("home delete").unwrap();
+        assert_eq!(ui.frame().to_string(), "_eLlo!\n");
+        ui.play("right").unwrap();
+        assert_eq!(ui.frame().to_string(), "e_Llo!\n");
+
+        let mut input = TextInput::new(8);
+        input.placeholder = String::from("name");
+        assert_eq!(render(input), "_name\n");
+    }
+
+    #[test]
+    fn checkbox_toggles() {
+        let mut ui = ui(Checkbox {
+            label: String::from("Remember me"),
+            checked: false,
+            style: Style::default(),
+        });
+        assert_eq!(ui.frame().to_string(), "[ ] Remember me\n");
+
+        ui.play("space").unwrap();
+        assert_eq!(ui.frame().to_string(), "[x] Remember me\n");
+    }
+
+    #[test]
+    fn progress_bar_shows_the_fraction_done() {
+        let bar = |progress| ProgressBar {
+            width: 17,
+            progress,
+            style: Style::default(),
+        };
+
+        assert_eq!(render(bar(0.4)), "[####......]  40%\n");
+        assert_eq!(render(bar(1.5)), "[##########] 100%\n");
+    }
+
+    #[test]
+    fn table_lines_up_columns() {
+        let table = Table {
+            columns: vec![
+                Column {
+                    title: String::from("Name"),
+                    width: 6,
+                },
+                Column {
+                    title: String::from("Qty"),
+                    width: 3,
+                },
+            ],
+            rows: vec![
+                vec![String::from("Apples"), String::from("3")],
+                vec![String::from("Blueberries"), String::from("120")],
+            ],
+            style: Style::bordered(),
+        };
+
+        assert_eq!(
+            render(table),
+            "\
++----------+
+|Name  |Qty|
+|------+---|
+|Apples|3  |
+|Bluebe|120|
++----------+
+"
+        );
+    }
+
+    #[test]
+    fn list_scrolls_to_follow_the_selection() {
+        let mut ui = ui(List {
+            width: 10,
+            height: 3,
+            items: items(5),
+            selected: None,
+            offset: 0,
+            style: Style::default(),
+        });
+        assert_eq!(ui.frame().to_string(), "  item 1\n  item 2\n  item 3 v\n");
+
+        ui.play("down down down down").unwrap();
+        assert_eq!(ui.frame().to_string(), "  item 2 ^\n  item 3\n> item 4 v\n");
+
+        ui.play("scrolldown:0,0 scrolldown:0,0 click:0,0").unwrap();
+        assert_eq!(ui.frame().to_string(), "> item 3 ^\n  item 4\n  item 5\n");
+    }
+
+    #[test]
+    fn list_clicks_on_the_border_select_nothing() {
+        let mut ui = ui(List {
+            width: 10,
+            height: 3,
+            items: items(5),
+            selected: None,
+            offset: 1,
+            style: Style::bordered(),
+        });
+
+        ui.play("click:2,0 click:2,4").unwrap();
+        assert!(!ui.frame().to_string().contains('>'));
+
+        ui.play("click:2,3").unwrap();
+        assert_eq!(ui.frame().to_string().lines().nth(3), Some("|> item 4 v|"));
+    }
+}
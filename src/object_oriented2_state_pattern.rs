@@ -1,95 +1,407 @@
+pub mod markdown;
+pub mod store;
+
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub fn run(){
     let mut post = Post::new();
 
-    post.add_text("I ate a salad for lunch today");
+    post.add_text("I ate a salad for lunch today").unwrap();
+    assert_eq!("", post.content());
+
+    post.request_review("alice").unwrap();
     assert_eq!("", post.content());
 
-    post.request_review();
+    // A reviewer sends it back, and only Draft posts can be edited.
+    post.reject("bob").unwrap();
+    post.add_text(" and it was great").unwrap();
+    post.request_review("alice").unwrap();
+
+    post.approve("bob").unwrap();
     assert_eq!("", post.content());
 
-    post.approve();
-    assert_eq!("I ate a salad for lunch today", post.content());
+    post.approve("carol").unwrap();
+    assert_eq!("I ate a salad for lunch today and it was great", post.content());
+
+    if let Err(e) = post.add_text(" (edited)") {
+        println!("{}", e);
+    }
+    assert_eq!("I ate a salad for lunch today and it was great", post.content());
+
+    for entry in post.history() {
+        println!("{}", entry);
+    }
+}
+
+// How many different reviewers have to approve a post before it is published.
+pub const REQUIRED_APPROVALS: usize = 2;
+
+// One accepted action in the life of a post. Approvals that don't publish the post yet are
+// recorded too, with the same state before and after.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub actor: String,
+    pub action: &'static str,
+    pub from: &'static str,
+    pub to: &'static str,
 }
 
+impl std::fmt::Display for AuditEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {} {}: {} -> {}", self.timestamp, self.actor, self.action, self.from, self.to)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorkflowError {
+    /// The current state doesn't allow the action.
+    NotAllowed {
+        action: &'static str,
+        state: &'static str,
+    },
+    AlreadyApproved {
+        reviewer: String,
+    },
+}
+
+impl std::fmt::Display for WorkflowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            WorkflowError::NotAllowed { action, state } => {
+                let hint = match *state {
+                    "Draft" => "request a review first",
+                    "PendingReview" if *action == "edit" => "reject it back to Draft to edit it",
+                    "PendingReview" => "it is already waiting for review",
+                    _ => "it has already been published",
+                };
+                write!(f, "can't {} a post in {}: {}", action.replace('_', " "), state, hint)
+            }
+            WorkflowError::AlreadyApproved { reviewer } => {
+                write!(f, "{} has already approved this post; it needs another reviewer", reviewer)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WorkflowError {}
+
 pub struct Post{
     state: Option<Box<dyn State>>,
-    content: String
+    content: String,
+    history: Vec<AuditEntry>,
 }
 
 impl Post{
     pub fn new() -> Post{
         Post{
             state: Some(Box::new(Draft {})),
-            content: String::new()
+            content: String::new(),
+            history: vec![],
         }
     }
 
-    pub fn add_text(&mut self, text: &str){
-        self.content.push_str(text);
+    // The state decides whether the text may change; the post keeps the text.
+    pub fn add_text(&mut self, text: &str) -> Result<(), WorkflowError>{
+        let state = self.state.as_ref().unwrap();
+        if state.add_text(&mut self.content, text) {
+            Ok(())
+        } else {
+            Err(WorkflowError::NotAllowed { action: "edit", state: state.name() })
+        }
     }
 
+    /// What readers see: the text of a published post, and nothing before that.
     pub fn content(&self) -> &str {
         self.state.as_ref().unwrap().content(self)
     }
-    
-    pub fn request_review(&mut self){
-        if let Some(s) = self.state.take() {
-            self.state = Some(s.request_review())
+
+    /// The published text rendered from Markdown to HTML, and nothing before that.
+    pub fn html(&self) -> String {
+        markdown::to_html(self.content())
+    }
+
+    /// The published text as plain text of at most `max_chars` characters, for feeds.
+    pub fn summary(&self, max_chars: usize) -> String {
+        markdown::summary(self.content(), max_chars)
+    }
+
+    /// The text as written so far, whatever the state, for the author and reviewers.
+    pub fn text(&self) -> &str {
+        &self.content
+    }
+
+    /// The name of the current state: "Draft", "PendingReview" or "Published".
+    pub fn state(&self) -> &'static str {
+        self.state.as_ref().unwrap().name()
+    }
+
+    pub fn approvals(&self) -> usize {
+        self.approved_by().len()
+    }
+
+    /// The reviewers who have approved the post since it was last sent for review.
+    pub fn approved_by(&self) -> &[String] {
+        self.state.as_ref().unwrap().approved_by()
+    }
+
+    pub fn history(&self) -> &[AuditEntry] {
+        &self.history
+    }
+
+    pub fn request_review(&mut self, actor: &str) -> Result<(), WorkflowError>{
+        self.transition("request_review", actor, |s| s.request_review())
+    }
+
+    pub fn approve(&mut self, actor: &str) -> Result<(), WorkflowError>{
+        if self.approved_by().iter().any(|a| a == actor) {
+            return Err(WorkflowError::AlreadyApproved { reviewer: String::from(actor) });
         }
+        self.transition("approve", actor, |s| s.approve(actor))
+    }
+
+    pub fn reject(&mut self, actor: &str) -> Result<(), WorkflowError>{
+        self.transition("reject", actor, |s| s.reject())
     }
 
-    pub fn approve(&mut self){
-        if let Some(s) = self.state.take(){
-            self.state = Some(s.approve())
+    // Applies a transition and records it. A state that ignores the transition hands itself
+    // back unchanged, which is reported as an error.
+    fn transition<F>(&mut self, action: &'static str, actor: &str, f: F) -> Result<(), WorkflowError>
+    where
+        F: FnOnce(Box<dyn State>) -> Box<dyn State>,
+    {
+        let s = self.state.take().unwrap();
+        let (from, approvals) = (s.name(), s.approved_by().len());
+        let next = f(s);
+        let changed = next.name() != from || next.approved_by().len() != approvals;
+
+        if changed {
+            self.history.push(AuditEntry {
+                timestamp: now(),
+                actor: String::from(actor),
+                action,
+                from,
+                to: next.name(),
+            });
+        }
+        self.state = Some(next);
+
+        if changed {
+            Ok(())
+        } else {
+            Err(WorkflowError::NotAllowed { action, state: from })
         }
     }
 }
 
+impl Default for Post {
+    fn default() -> Post {
+        Post::new()
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
 trait State {
+    fn name(&self) -> &'static str;
     fn request_review(self: Box<Self>) -> Box<dyn State>;
-    fn approve(self: Box<Self>) -> Box<dyn State>;
-    fn content<'a>(&self, post: &'a Post) -> &'a str{
+    fn approve(self: Box<Self>, actor: &str) -> Box<dyn State>;
+    fn reject(self: Box<Self>) -> Box<dyn State>;
+    fn content<'a>(&self, _post: &'a Post) -> &'a str{
         ""
     }
+    fn add_text(&self, _content: &mut String, _text: &str) -> bool {
+        false
+    }
+    fn approved_by(&self) -> &[String] {
+        &[]
+    }
 }
 
 struct Draft {}
 
 impl State for Draft {
+    fn name(&self) -> &'static str {
+        "Draft"
+    }
+
     fn request_review(self: Box<Self>) -> Box<dyn State> {
-        Box::new(PendingReview {})
+        Box::new(PendingReview { approved_by: vec![] })
+    }
+
+    fn approve(self: Box<Self>, _actor: &str) -> Box<dyn State> {
+        self
     }
 
-    fn approve(self: Box<Self>) -> Box<dyn State> {
+    fn reject(self: Box<Self>) -> Box<dyn State> {
         self
     }
+
+    fn add_text(&self, content: &mut String, text: &str) -> bool {
+        content.push_str(text);
+        true
+    }
 }
 
-struct PendingReview{}
+struct PendingReview{
+    approved_by: Vec<String>,
+}
 
 impl State for PendingReview{
+    fn name(&self) -> &'static str {
+        "PendingReview"
+    }
+
     fn request_review(self: Box<Self>) -> Box<dyn State> {
         self
     }
 
-    fn approve(self: Box<Self>) -> Box<dyn State> {
-        Box::new(Published {})
+    // A second approval from the same reviewer doesn't count.
+    fn approve(mut self: Box<Self>, actor: &str) -> Box<dyn State> {
+        if !self.approved_by.iter().any(|a| a == actor) {
+            self.approved_by.push(String::from(actor));
+        }
+
+        if self.approved_by.len() >= REQUIRED_APPROVALS {
+            Box::new(Published {})
+        } else {
+            self
+        }
+    }
+
+    fn reject(self: Box<Self>) -> Box<dyn State> {
+        Box::new(Draft {})
+    }
+
+    fn approved_by(&self) -> &[String] {
+        &self.approved_by
     }
 }
 
 struct Published {}
 
 impl State for Published {
+    fn name(&self) -> &'static str {
+        "Published"
+    }
+
     fn request_review(self: Box<Self>) -> Box<dyn State> {
         self
     }
 
-    fn approve(self: Box<Self>) -> Box<dyn State> {
+    fn approve(self: Box<Self>, _actor: &str) -> Box<dyn State> {
+        self
+    }
+
+    fn reject(self: Box<Self>) -> Box<dyn State> {
         self
     }
 
     fn content<'a>(&self, post: &'a Post) -> &'a str {
         &post.content
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_different_reviewers_publish_a_post() {
+        let mut post = Post::new();
+        post.add_text("hello").unwrap();
+        post.request_review("alice").unwrap();
+
+        post.approve("bob").unwrap();
+        assert_eq!(
+            post.approve("bob"),
+            Err(WorkflowError::AlreadyApproved { reviewer: String::from("bob") })
+        );
+        assert_eq!((post.state(), post.approvals()), ("PendingReview", 1));
+
+        post.approve("carol").unwrap();
+        assert_eq!(post.state(), "Published");
+        assert_eq!(post.content(), "hello");
+    }
+
+    #[test]
+    fn rejecting_returns_to_draft_and_drops_approvals() {
+        let mut post = Post::new();
+        post.request_review("alice").unwrap();
+        post.approve("bob").unwrap();
+        post.reject("carol").unwrap();
+        assert_eq!((post.state(), post.approvals()), ("Draft", 0));
+
+        post.add_text("fixed").unwrap();
+        post.request_review("alice").unwrap();
+        post.approve("bob").unwrap();
+        assert_eq!(post.state(), "PendingReview");
+    }
+
+    #[test]
+    fn text_can_only_change_in_draft() {
+        let mut post = Post::new();
+        post.add_text("a").unwrap();
+        post.request_review("alice").unwrap();
+        assert!(post.add_text("b").is_err());
+        post.approve("bob").unwrap();
+        post.approve("carol").unwrap();
+
+        assert_eq!(
+            post.add_text("c").unwrap_err().to_string(),
+            "can't edit a post in Published: it has already been published"
+        );
+        assert_eq!(post.content(), "a");
+    }
+
+    #[test]
+    fn history_records_accepted_actions_with_their_actors() {
+        let mut post = Post::new();
+        assert_eq!(
+            post.approve("mallory").unwrap_err().to_string(),
+            "can't approve a post in Draft: request a review first"
+        );
+        post.request_review("alice").unwrap();
+        assert!(post.request_review("alice").is_err());
+        post.approve("bob").unwrap();
+        post.reject("carol").unwrap();
+
+        let steps: Vec<_> = post
+            .history()
+            .iter()
+            .map(|e| (e.actor.as_str(), e.action, e.from, e.to))
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                ("alice", "request_review", "Draft", "PendingReview"),
+                ("bob", "approve", "PendingReview", "PendingReview"),
+                ("carol", "reject", "PendingReview", "Draft"),
+            ]
+        );
+        assert!(post.history().windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+    }
+
+    #[test]
+    fn only_published_posts_render_as_html() {
+        let mut post = Post::new();
+        post.add_text("# Lunch\n\nA *salad* <b>today</b>").unwrap();
+        assert_eq!((post.html().as_str(), post.summary(40).as_str()), ("", ""));
+
+        post.request_review("alice").unwrap();
+        post.approve("bob").unwrap();
+        post.approve("carol").unwrap();
+        assert_eq!(
+            post.html(),
+            "<h1>Lunch</h1>\n<p>A <em>salad</em> &lt;b&gt;today&lt;/b&gt;</p>\n"
+        );
+        assert_eq!(post.summary(15), "Lunch A salad…");
+        assert_eq!(post.content(), "# Lunch\n\nA *salad* <b>today</b>");
+    }
+}
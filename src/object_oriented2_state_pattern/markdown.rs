@@ -0,0 +1,1180 @@
+// Markdown for published posts: `to_html` renders a post for the web and `summary` turns it into
+// a short line of plain text for feeds.
+//
+// It follows CommonMark for the parts a blog post needs:
+//
+// - ATX (`# Title`) and setext (underlined) headings, paragraphs and thematic breaks (`***`)
+// - `*emphasis*`, `**strong**` and their `_` forms, with CommonMark's flanking rules, so
+//   snake_case_words stay as they are
+// - bullet and ordered lists, nested by indentation, tight or loose
+// - block quotes, fenced and indented code blocks, and `code spans`
+// - inline links `[text](url "title")`, autolinks `<https://...>`, backslash escapes and hard
+//   line breaks
+//
+// Posts are written by users, so it departs from CommonMark in two ways: raw HTML is escaped
+// instead of passed through, and links to `javascript:`, `vbscript:` or `data:` URLs get an
+// empty href. Reference links, images and entity references aren't supported; they come out as
+// the text that was written.
+//
+// Text is parsed into blocks and inlines first, and both outputs are rendered from that tree.
+// Quotes and lists nested more than MAX_NESTING deep, and links and emphasis nested more than
+// MAX_NESTING deep inside a paragraph, are left as the text that was written, so no post can make
+// parsing or rendering recurse without limit.
+
+use std::collections::HashMap;
+
+const MAX_NESTING: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Block {
+    Heading(usize, Vec<Inline>),
+    Paragraph(Vec<Inline>),
+    Code { info: String, text: String },
+    List { start: Option<u64>, tight: bool, items: Vec<Vec<Block>> },
+    Quote(Vec<Block>),
+    Rule,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Inline {
+    Text(String),
+    Code(String),
+    Emphasis(Vec<Inline>),
+    Strong(Vec<Inline>),
+    Link { dest: String, title: Option<String>, children: Vec<Inline> },
+    LineBreak,
+    SoftBreak,
+}
+
+pub fn to_html(markdown: &str) -> String {
+    let mut html = String::new();
+    render_blocks(&mut html, &parse(markdown), false);
+    html
+}
+
+/// The text of the post without any markup, on one line, cut at a word boundary so it is at
+/// most `max_chars` characters long (including the `…` that marks a cut).
+pub fn summary(markdown: &str, max_chars: usize) -> String {
+    let mut text = String::new();
+    plain_blocks(&mut text, &parse(markdown));
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    if text.chars().count() <= max_chars {
+        return text;
+    }
+
+    let limit: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    let ends_a_word = text.chars().nth(max_chars.saturating_sub(1)) == Some(' ');
+    let cut = match limit.rfind(' ') {
+        Some(space) if !ends_a_word => &limit[..space],
+        _ => &limit,
+    };
+    format!("{}…", cut.trim_end_matches(|c: char| c.is_ascii_punctuation()))
+}
+
+fn parse(markdown: &str) -> Vec<Block> {
+    let lines: Vec<String> = markdown.lines().map(expand_tabs).collect();
+    let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+    parse_blocks(&lines, 0)
+}
+
+fn expand_tabs(line: &str) -> String {
+    let mut expanded = String::new();
+    for c in line.chars() {
+        if c == '\t' {
+            let width = 4 - expanded.chars().count() % 4;
+            expanded.extend(std::iter::repeat_n(' ', width));
+        } else {
+            expanded.push(c);
+        }
+    }
+    expanded
+}
+
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+// Removes up to `n` leading spaces.
+fn strip_indent(line: &str, n: usize) -> &str {
+    &line[indent(line).min(n)..]
+}
+
+// ---- blocks ----
+
+fn atx_heading(line: &str) -> Option<(usize, &str)> {
+    if indent(line) > 3 {
+        return None;
+    }
+    let line = line.trim_start();
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let rest = &line[level..];
+    if level == 0 || level > 6 || !(rest.is_empty() || rest.starts_with(' ')) {
+        return None;
+    }
+
+    // A closing run of #s is dropped when it is separated by a space (or is all there is).
+    let mut text = rest.trim();
+    let without_closing = text.trim_end_matches('#');
+    if without_closing.is_empty() || without_closing.ends_with(' ') {
+        text = without_closing.trim_end();
+    }
+    Some((level, text))
+}
+
+fn is_rule(line: &str) -> bool {
+    if indent(line) > 3 {
+        return false;
+    }
+    let chars: Vec<char> = line.chars().filter(|c| *c != ' ').collect();
+    chars.len() >= 3 && ['-', '*', '_'].iter().any(|m| chars.iter().all(|c| c == m))
+}
+
+fn fence_open(line: &str) -> Option<(char, usize, String)> {
+    if indent(line) > 3 {
+        return None;
+    }
+    let trimmed = line.trim_start();
+    let fence = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = trimmed.chars().take_while(|c| *c == fence).count();
+    let info = trimmed[len..].trim();
+    if len < 3 || (fence == '`' && info.contains('`')) {
+        return None;
+    }
+    let info = info.split_whitespace().next().unwrap_or("");
+    Some((fence, len, unescape(info)))
+}
+
+fn is_fence_close(line: &str, fence: char, len: usize) -> bool {
+    let trimmed = line.trim();
+    indent(line) <= 3
+        && trimmed.chars().count() >= len
+        && trimmed.chars().all(|c| c == fence)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Marker {
+    Bullet(char),
+    Ordered(char, u64),
+}
+
+// A list item's marker and the column its content starts at.
+fn list_item(line: &str) -> Option<(Marker, usize)> {
+    let spaces = indent(line);
+    if spaces > 3 {
+        return None;
+    }
+    let rest = &line[spaces..];
+
+    let (marker, marker_len) = match rest.chars().next()? {
+        c @ ('-' | '*' | '+') => (Marker::Bullet(c), 1),
+        _ => {
+            let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+            let delimiter = rest[digits..].chars().next()?;
+            if digits == 0 || digits > 9 || !(delimiter == '.' || delimiter == ')') {
+                return None;
+            }
+            (Marker::Ordered(delimiter, rest[..digits].parse().ok()?), digits + 1)
+        }
+    };
+
+    let after = &rest[marker_len..];
+    if after.is_empty() {
+        return Some((marker, spaces + marker_len + 1));
+    }
+    let gap = indent(after);
+    if gap == 0 {
+        return None;
+    }
+    // Content indented by five or more spaces is an indented code block one space in.
+    let gap = if gap > 4 || after.trim().is_empty() { 1 } else { gap };
+    Some((marker, spaces + marker_len + gap))
+}
+
+fn same_list(a: Marker, b: Marker) -> bool {
+    match (a, b) {
+        (Marker::Bullet(x), Marker::Bullet(y)) => x == y,
+        (Marker::Ordered(x, _), Marker::Ordered(y, _)) => x == y,
+        _ => false,
+    }
+}
+
+fn starts_quote(line: &str) -> bool {
+    indent(line) <= 3 && line.trim_start().starts_with('>')
+}
+
+// Whether `line` starts a block that ends a paragraph. Quotes and lists only count where they can
+// still be nested (`containers`).
+fn interrupts_paragraph(line: &str, containers: bool) -> bool {
+    atx_heading(line).is_some()
+        || fence_open(line).is_some()
+        || is_rule(line)
+        || (containers && starts_quote(line))
+        || match list_item(line).filter(|_| containers) {
+            // Only a non-empty item can, and an ordered list has to start at 1.
+            Some((Marker::Ordered(_, start), content)) => start == 1 && !is_blank(line.get(content..).unwrap_or("")),
+            Some((Marker::Bullet(_), content)) => !is_blank(line.get(content..).unwrap_or("")),
+            None => false,
+        }
+}
+
+// `depth` is how many quotes and list items the lines are inside of.
+fn parse_blocks(lines: &[&str], depth: usize) -> Vec<Block> {
+    let containers = depth < MAX_NESTING;
+    let mut blocks = vec![];
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+
+        if is_blank(line) {
+            i += 1;
+        } else if let Some((fence, len, info)) = fence_open(line) {
+            let fence_indent = indent(line);
+            let mut text = String::new();
+            i += 1;
+            while i < lines.len() && !is_fence_close(lines[i], fence, len) {
+                text.push_str(strip_indent(lines[i], fence_indent));
+                text.push('\n');
+                i += 1;
+            }
+            i += 1;
+            blocks.push(Block::Code { info, text });
+        } else if indent(line) >= 4 {
+            let mut code_lines = vec![];
+            while i < lines.len() && (indent(lines[i]) >= 4 || is_blank(lines[i])) {
+                code_lines.push(lines[i].get(4..).unwrap_or(""));
+                i += 1;
+            }
+            while code_lines.last().is_some_and(|l| is_blank(l)) {
+                code_lines.pop();
+            }
+            let text = code_lines.iter().map(|l| format!("{}\n", l)).collect();
+            blocks.push(Block::Code { info: String::new(), text });
+        } else if let Some((level, text)) = atx_heading(line) {
+            blocks.push(Block::Heading(level, parse_inlines(text, 0)));
+            i += 1;
+        } else if is_rule(line) {
+            blocks.push(Block::Rule);
+            i += 1;
+        } else if containers && starts_quote(line) {
+            let mut quoted = vec![];
+            while i < lines.len() && !is_blank(lines[i]) {
+                let trimmed = lines[i].trim_start();
+                match trimmed.strip_prefix('>') {
+                    Some(rest) if indent(lines[i]) <= 3 => {
+                        quoted.push(rest.strip_prefix(' ').unwrap_or(rest));
+                    }
+                    // A lazy continuation line of a paragraph inside the quote.
+                    _ if !interrupts_paragraph(lines[i], true) => quoted.push(lines[i]),
+                    _ => break,
+                }
+                i += 1;
+            }
+            blocks.push(Block::Quote(parse_blocks(&quoted, depth + 1)));
+        } else if let Some((marker, _)) = list_item(line).filter(|_| containers) {
+            let (block, next) = parse_list(lines, i, marker, depth);
+            blocks.push(block);
+            i = next;
+        } else {
+            let mut text = vec![line.trim_start()];
+            i += 1;
+            let mut level = None;
+            while i < lines.len() && !is_blank(lines[i]) {
+                let next = lines[i];
+                let underline = next.trim();
+                if indent(next) <= 3 && !underline.is_empty() {
+                    if underline.chars().all(|c| c == '=') {
+                        level = Some(1);
+                    } else if underline.chars().all(|c| c == '-') {
+                        level = Some(2);
+                    }
+                }
+                if level.is_some() {
+                    i += 1;
+                    break;
+                }
+                if interrupts_paragraph(next, containers) {
+                    break;
+                }
+                text.push(next.trim_start());
+                i += 1;
+            }
+
+            let inlines = parse_inlines(text.join("\n").trim_end(), 0);
+            blocks.push(match level {
+                Some(level) => Block::Heading(level, inlines),
+                None => Block::Paragraph(inlines),
+            });
+        }
+    }
+
+    blocks
+}
+
+// Parses the list starting at line `i`. Returns the list and the first line after it. The items'
+// lines are borrowed from `lines`, so each nested list costs a pass over its lines, not a copy.
+fn parse_list(lines: &[&str], mut i: usize, first: Marker, depth: usize) -> (Block, usize) {
+    let mut items = vec![];
+    let mut tight = true;
+    let mut blank_before_item = false;
+
+    while i < lines.len() {
+        let content = match list_item(lines[i]) {
+            Some((marker, content)) if same_list(marker, first) && !is_rule(lines[i]) => content,
+            _ => break,
+        };
+        if blank_before_item {
+            tight = false;
+        }
+
+        let mut item_lines = vec![lines[i].get(content..).unwrap_or("").trim_end()];
+        i += 1;
+
+        while i < lines.len() {
+            let line = lines[i];
+            if is_blank(line) {
+                item_lines.push("");
+            } else if indent(line) >= content {
+                item_lines.push(&line[content..]);
+            } else if !item_lines.last().is_some_and(|l| is_blank(l)) && !interrupts_paragraph(line, true) && list_item(line).is_none() {
+                // A lazy continuation line of the item's paragraph.
+                item_lines.push(line.trim_start());
+            } else {
+                break;
+            }
+            i += 1;
+        }
+
+        // Blank lines at the end belong between this item and the next, not inside it.
+        blank_before_item = false;
+        while item_lines.last().is_some_and(|l| is_blank(l)) {
+            item_lines.pop();
+            blank_before_item = true;
+        }
+        // A blank line between two blocks of the same item also makes the list loose.
+        let blocks = parse_blocks(&item_lines, depth + 1);
+        if blocks.len() > 1 && item_lines.iter().any(|l| is_blank(l)) && has_blank_between_blocks(&item_lines) {
+            tight = false;
+        }
+        items.push(blocks);
+    }
+
+    let start = match first {
+        Marker::Ordered(_, start) => Some(start),
+        Marker::Bullet(_) => None,
+    };
+    (Block::List { start, tight, items }, i)
+}
+
+// Whether a blank line separates two top level blocks of an item (a blank line inside a fenced
+// code block doesn't count).
+fn has_blank_between_blocks(lines: &[&str]) -> bool {
+    let mut fence: Option<(char, usize)> = None;
+    let mut seen_content = false;
+    let mut blank = false;
+
+    for line in lines {
+        match fence {
+            Some((c, len)) => {
+                if is_fence_close(line, c, len) {
+                    fence = None;
+                }
+            }
+            None if is_blank(line) => blank = seen_content,
+            None => {
+                if blank && indent(line) < 4 {
+                    return true;
+                }
+                blank = false;
+                seen_content = true;
+                if let Some((c, len, _)) = fence_open(line) {
+                    fence = Some((c, len));
+                }
+            }
+        }
+    }
+    false
+}
+
+// ---- inlines ----
+
+#[derive(Debug)]
+enum Piece {
+    Inline(Inline),
+    Delimiter { c: char, count: usize, original: usize, open: bool, close: bool },
+    /// Inlines that would have been emphasis nested too deep, with their delimiters as text.
+    Group(Vec<Inline>),
+}
+
+fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation() || (!c.is_ascii() && !c.is_alphanumeric() && !c.is_whitespace())
+}
+
+// `depth` is how many links the text is inside of.
+fn parse_inlines(text: &str, depth: usize) -> Vec<Inline> {
+    let chars: Vec<char> = text.chars().collect();
+    let brackets = match_brackets(&chars);
+    let mut pieces: Vec<Piece> = vec![];
+    let mut buffer = String::new();
+    let mut i = 0;
+
+    let flush = |buffer: &mut String, pieces: &mut Vec<Piece>| {
+        if !buffer.is_empty() {
+            pieces.push(Piece::Inline(Inline::Text(std::mem::take(buffer))));
+        }
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\\' if chars.get(i + 1) == Some(&'\n') => {
+                flush(&mut buffer, &mut pieces);
+                pieces.push(Piece::Inline(Inline::LineBreak));
+                i += 2;
+            }
+            '\\' if chars.get(i + 1).is_some_and(|c| c.is_ascii_punctuation()) => {
+                buffer.push(chars[i + 1]);
+                i += 2;
+            }
+            '`' => {
+                let run = chars[i..].iter().take_while(|c| **c == '`').count();
+                match find_closing_backticks(&chars, i + run, run) {
+                    Some(end) => {
+                        flush(&mut buffer, &mut pieces);
+                        let code: String = chars[i + run..end].iter().collect();
+                        pieces.push(Piece::Inline(Inline::Code(normalize_code(&code))));
+                        i = end + run;
+                    }
+                    None => {
+                        buffer.extend(&chars[i..i + run]);
+                        i += run;
+                    }
+                }
+            }
+            '*' | '_' => {
+                flush(&mut buffer, &mut pieces);
+                let count = chars[i..].iter().take_while(|x| **x == c).count();
+                let before = if i == 0 { ' ' } else { chars[i - 1] };
+                let after = chars.get(i + count).copied().unwrap_or(' ');
+
+                let left = !after.is_whitespace()
+                    && (!is_punctuation(after) || before.is_whitespace() || is_punctuation(before));
+                let right = !before.is_whitespace()
+                    && (!is_punctuation(before) || after.is_whitespace() || is_punctuation(after));
+                let (open, close) = if c == '*' {
+                    (left, right)
+                } else {
+                    (left && (!right || is_punctuation(before)), right && (!left || is_punctuation(after)))
+                };
+
+                pieces.push(Piece::Delimiter { c, count, original: count, open, close });
+                i += count;
+            }
+            '[' => match brackets[i].filter(|_| depth < MAX_NESTING).and_then(|close| parse_link(&chars, i, close, depth)) {
+                Some((link, end)) => {
+                    flush(&mut buffer, &mut pieces);
+                    pieces.push(Piece::Inline(link));
+                    i = end;
+                }
+                None => {
+                    buffer.push('[');
+                    i += 1;
+                }
+            },
+            '<' => match parse_autolink(&chars, i) {
+                Some((link, end)) => {
+                    flush(&mut buffer, &mut pieces);
+                    pieces.push(Piece::Inline(link));
+                    i = end;
+                }
+                None => {
+                    buffer.push('<');
+                    i += 1;
+                }
+            },
+            '\n' => {
+                let hard = buffer.ends_with("  ");
+                let trimmed = buffer.trim_end_matches(' ').len();
+                buffer.truncate(trimmed);
+                flush(&mut buffer, &mut pieces);
+                pieces.push(Piece::Inline(if hard { Inline::LineBreak } else { Inline::SoftBreak }));
+                i += 1;
+                while chars.get(i) == Some(&' ') {
+                    i += 1;
+                }
+            }
+            _ => {
+                buffer.push(c);
+                i += 1;
+            }
+        }
+    }
+    flush(&mut buffer, &mut pieces);
+
+    process_emphasis(pieces, depth)
+}
+
+// The `]` that closes each `[`, found in one pass. Escaped brackets and brackets in code spans
+// don't count.
+fn match_brackets(chars: &[char]) -> Vec<Option<usize>> {
+    let mut closes = vec![None; chars.len()];
+    let mut open = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '`' => {
+                let run = chars[i..].iter().take_while(|c| **c == '`').count();
+                i = find_closing_backticks(chars, i + run, run).map_or(i + run, |end| end + run);
+                continue;
+            }
+            '[' => open.push(i),
+            ']' => {
+                if let Some(start) = open.pop() {
+                    closes[start] = Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    closes
+}
+
+fn find_closing_backticks(chars: &[char], from: usize, run: usize) -> Option<usize> {
+    let mut i = from;
+    while i < chars.len() {
+        if chars[i] == '`' {
+            let len = chars[i..].iter().take_while(|c| **c == '`').count();
+            if len == run {
+                return Some(i);
+            }
+            i += len;
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+// Line endings become spaces, and one space is stripped from each side if both have one.
+fn normalize_code(code: &str) -> String {
+    let code = code.replace('\n', " ");
+    if code.len() >= 2 && code.starts_with(' ') && code.ends_with(' ') && !code.trim().is_empty() {
+        String::from(&code[1..code.len() - 1])
+    } else {
+        code
+    }
+}
+
+// Pairs up `*` and `_` runs into Emphasis and Strong, as in CommonMark's "process emphasis":
+// each closer is matched with the nearest opener of the same kind before it, and delimiters
+// left between the two become plain text.
+//
+// The pieces are kept in a linked list, so wrapping a match doesn't shift everything after it,
+// and `floors` remembers how far back a search for each kind of opener already came up empty, so
+// it isn't repeated. Emphasis that would end up nested more than MAX_NESTING deep (counting the
+// `depth` links around the text) keeps its delimiters as text.
+fn process_emphasis(pieces: Vec<Piece>, depth: usize) -> Vec<Inline> {
+    let len = pieces.len();
+    let mut nesting: Vec<usize> = pieces
+        .iter()
+        .map(|piece| match piece {
+            Piece::Inline(inline) => nesting_of(inline),
+            _ => 0,
+        })
+        .collect();
+    let mut slots: Vec<Option<Piece>> = pieces.into_iter().map(Some).collect();
+    let mut prev: Vec<Option<usize>> = (0..len).map(|i| i.checked_sub(1)).collect();
+    let mut next: Vec<Option<usize>> = (1..=len).map(|i| Some(i).filter(|i| *i < len)).collect();
+    let mut floors: HashMap<(char, bool, usize), usize> = HashMap::new();
+
+    let mut current = if len > 0 { Some(0) } else { None };
+    while let Some(closer) = current {
+        let (c, close_count, close_original, close_open) = match slots[closer] {
+            Some(Piece::Delimiter { c, count, original, open, close: true }) if count > 0 => (c, count, original, open),
+            _ => {
+                current = next[closer];
+                continue;
+            }
+        };
+
+        // The "multiple of 3" rule: a run that can both open and close only pairs with another if
+        // their lengths don't add up to a multiple of three.
+        let key = (c, close_open, close_original % 3);
+        let floor = floors.get(&key).copied().unwrap_or(0);
+        let mut candidate = prev[closer].filter(|o| *o >= floor);
+        let opener = loop {
+            let Some(o) = candidate else { break None };
+            if let Some(Piece::Delimiter { c: oc, count, original, open: true, close }) = slots[o] {
+                if oc == c
+                    && count > 0
+                    && !((close || close_open)
+                        && (original + close_original) % 3 == 0
+                        && !(original % 3 == 0 && close_original % 3 == 0))
+                {
+                    break Some((o, count));
+                }
+            }
+            candidate = prev[o].filter(|o| *o >= floor);
+        };
+
+        // Something always lies between two matching runs, since runs of one character merge.
+        let (opener, open_count, first_child) = match opener.and_then(|(o, count)| Some((o, count, next[o]?))) {
+            Some((opener, count, first_child)) if first_child != closer => (opener, count, first_child),
+            _ => {
+                floors.insert(key, closer);
+                current = next[closer];
+                continue;
+            }
+        };
+        let used = if open_count >= 2 && close_count >= 2 { 2 } else { 1 };
+
+        // Everything between the runs moves into the first child's slot.
+        let mut children = vec![];
+        let mut inner = 0;
+        let mut node = first_child;
+        while node != closer {
+            inner = inner.max(nesting[node]);
+            children.extend(slots[node].take());
+            node = next[node].expect("the closer follows its opener");
+        }
+        let children = into_inlines(children);
+        let (piece, piece_nesting) = if depth + inner + 1 > MAX_NESTING {
+            let delimiters: String = std::iter::repeat_n(c, used).collect();
+            let mut group = vec![Inline::Text(delimiters.clone())];
+            group.extend(children);
+            group.push(Inline::Text(delimiters));
+            (Piece::Group(group), inner)
+        } else if used == 2 {
+            (Piece::Inline(Inline::Strong(children)), inner + 1)
+        } else {
+            (Piece::Inline(Inline::Emphasis(children)), inner + 1)
+        };
+        slots[first_child] = Some(piece);
+        nesting[first_child] = piece_nesting;
+        next[first_child] = Some(closer);
+        prev[closer] = Some(first_child);
+
+        for index in [opener, closer] {
+            if let Some(Piece::Delimiter { count, .. }) = &mut slots[index] {
+                *count -= used;
+            }
+        }
+        if close_count == used {
+            current = next[closer];
+        }
+    }
+
+    let mut pieces = vec![];
+    let mut node = if len > 0 { Some(0) } else { None };
+    while let Some(index) = node {
+        pieces.extend(slots[index].take());
+        node = next[index];
+    }
+    into_inlines(pieces)
+}
+
+// How deeply emphasis and links are nested in `inline`.
+fn nesting_of(inline: &Inline) -> usize {
+    match inline {
+        Inline::Emphasis(children) | Inline::Strong(children) | Inline::Link { children, .. } => {
+            1 + children.iter().map(nesting_of).max().unwrap_or(0)
+        }
+        _ => 0,
+    }
+}
+
+fn into_inlines(pieces: Vec<Piece>) -> Vec<Inline> {
+    let mut inlines: Vec<Inline> = vec![];
+    let mut push = |inline: Inline| match (inlines.last_mut(), inline) {
+        (Some(Inline::Text(previous)), Inline::Text(text)) => previous.push_str(&text),
+        (_, inline) => inlines.push(inline),
+    };
+    for piece in pieces {
+        match piece {
+            Piece::Inline(inline) => push(inline),
+            Piece::Delimiter { count: 0, .. } => {}
+            Piece::Delimiter { c, count, .. } => push(Inline::Text(std::iter::repeat_n(c, count).collect())),
+            Piece::Group(group) => group.into_iter().for_each(&mut push),
+        }
+    }
+    inlines
+}
+
+// `[text](destination "title")`, from the `[` at `start` and the `]` at `close`. Returns the link
+// and the index after it.
+fn parse_link(chars: &[char], start: usize, close: usize, depth: usize) -> Option<(Inline, usize)> {
+    let mut i = close + 1;
+    if chars.get(i) != Some(&'(') {
+        return None;
+    }
+    i += 1;
+    let skip_spaces = |i: &mut usize| {
+        while chars.get(*i).is_some_and(|c| c.is_whitespace()) {
+            *i += 1;
+        }
+    };
+    skip_spaces(&mut i);
+
+    let mut dest = String::new();
+    if chars.get(i) == Some(&'<') {
+        i += 1;
+        loop {
+            match chars.get(i)? {
+                '>' => break,
+                '\n' | '<' => return None,
+                '\\' if chars.get(i + 1).is_some_and(|c| c.is_ascii_punctuation()) => {
+                    dest.push(chars[i + 1]);
+                    i += 1;
+                }
+                c => dest.push(*c),
+            }
+            i += 1;
+        }
+        i += 1;
+    } else {
+        let mut parens = 0;
+        while let Some(&c) = chars.get(i) {
+            if c.is_whitespace() || c.is_control() || (c == ')' && parens == 0) {
+                break;
+            }
+            match c {
+                // CommonMark lets implementations limit how deeply parentheses nest here, which
+                // keeps a run of unclosed ones from being scanned again for every `[`.
+                '(' if parens == MAX_NESTING => return None,
+                '(' => parens += 1,
+                ')' => parens -= 1,
+                _ => {}
+            }
+            if c == '\\' && chars.get(i + 1).is_some_and(|c| c.is_ascii_punctuation()) {
+                i += 1;
+                dest.push(chars[i]);
+            } else {
+                dest.push(c);
+            }
+            i += 1;
+        }
+        if parens != 0 {
+            return None;
+        }
+    }
+
+    let before_title = i;
+    skip_spaces(&mut i);
+    let mut title = None;
+    if let Some(&quote) = chars.get(i).filter(|c| matches!(c, '"' | '\'' | '(')) {
+        if i == before_title {
+            return None;
+        }
+        let end_quote = if quote == '(' { ')' } else { quote };
+        let mut text = String::new();
+        i += 1;
+        loop {
+            match chars.get(i)? {
+                c if *c == end_quote => break,
+                '\\' if chars.get(i + 1).is_some_and(|c| c.is_ascii_punctuation()) => {
+                    text.push(chars[i + 1]);
+                    i += 1;
+                }
+                c => text.push(*c),
+            }
+            i += 1;
+        }
+        i += 1;
+        title = Some(text);
+        skip_spaces(&mut i);
+    }
+
+    if chars.get(i) != Some(&')') {
+        return None;
+    }
+
+    let label: String = chars[start + 1..close].iter().collect();
+    let children = parse_inlines(&label, depth + 1);
+    Some((Inline::Link { dest, title, children }, i + 1))
+}
+
+// `<scheme:...>` or `<name@example.com>`, starting at the `<`. The scan stops at the next space
+// or `<`, which can't be part of one.
+fn parse_autolink(chars: &[char], start: usize) -> Option<(Inline, usize)> {
+    let mut end = start + 1;
+    loop {
+        match chars.get(end)? {
+            '>' => break,
+            c if c.is_whitespace() || *c == '<' => return None,
+            _ => end += 1,
+        }
+    }
+    let inner: String = chars[start + 1..end].iter().collect();
+    if inner.is_empty() {
+        return None;
+    }
+
+    let dest = match inner.split_once(':') {
+        Some((scheme, _))
+            if (2..=32).contains(&scheme.len())
+                && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+.-".contains(c)) =>
+        {
+            inner.clone()
+        }
+        _ => match inner.split_once('@') {
+            Some((user, domain))
+                if !user.is_empty()
+                    && domain.contains('.')
+                    && !domain.starts_with('.')
+                    && inner.chars().all(|c| c.is_ascii_alphanumeric() || ".!#$%&'*+/=?^_`{|}~-@".contains(c)) =>
+            {
+                format!("mailto:{}", inner)
+            }
+            _ => return None,
+        },
+    };
+
+    Some((
+        Inline::Link {
+            dest,
+            title: None,
+            children: vec![Inline::Text(inner)],
+        },
+        end + 1,
+    ))
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            Some(next) if c == '\\' && next.is_ascii_punctuation() => {
+                out.push(*next);
+                chars.next();
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// ---- output ----
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Percent-encodes what can't appear in a URL, then escapes it for an attribute.
+fn escape_url(url: &str) -> String {
+    let scheme = url.split(':').next().unwrap_or("").trim().to_lowercase();
+    if url.contains(':') && ["javascript", "vbscript", "data"].contains(&scheme.as_str()) {
+        return String::new();
+    }
+
+    let mut encoded = String::new();
+    for c in url.chars() {
+        if c.is_ascii_alphanumeric() || "-._~:/?#[]@!$&'()*+,;=%".contains(c) {
+            encoded.push(c);
+        } else {
+            let mut bytes = [0; 4];
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                encoded.push_str(&format!("%{:02X}", byte));
+            }
+        }
+    }
+    escape_html(&encoded)
+}
+
+// Starts a new line unless the output already ends with one.
+fn cr(html: &mut String) {
+    if !html.is_empty() && !html.ends_with('\n') {
+        html.push('\n');
+    }
+}
+
+fn render_blocks(html: &mut String, blocks: &[Block], tight: bool) {
+    for block in blocks {
+        match block {
+            Block::Paragraph(inlines) if tight => render_inlines(html, inlines),
+            Block::Paragraph(inlines) => {
+                cr(html);
+                html.push_str("<p>");
+                render_inlines(html, inlines);
+                html.push_str("</p>\n");
+            }
+            Block::Heading(level, inlines) => {
+                cr(html);
+                html.push_str(&format!("<h{}>", level));
+                render_inlines(html, inlines);
+                html.push_str(&format!("</h{}>\n", level));
+            }
+            Block::Code { info, text } => {
+                cr(html);
+                match info.as_str() {
+                    "" => html.push_str("<pre><code>"),
+                    info => html.push_str(&format!("<pre><code class=\"language-{}\">", escape_html(info))),
+                }
+                html.push_str(&escape_html(text));
+                html.push_str("</code></pre>\n");
+            }
+            Block::List { start, tight, items } => {
+                cr(html);
+                let tag = match start {
+                    Some(1) => {
+                        html.push_str("<ol>\n");
+                        "ol"
+                    }
+                    Some(n) => {
+                        html.push_str(&format!("<ol start=\"{}\">\n", n));
+                        "ol"
+                    }
+                    None => {
+                        html.push_str("<ul>\n");
+                        "ul"
+                    }
+                };
+                for item in items {
+                    html.push_str("<li>");
+                    render_blocks(html, item, *tight);
+                    if !*tight || matches!(item.last(), Some(block) if !matches!(block, Block::Paragraph(_))) {
+                        cr(html);
+                    }
+                    html.push_str("</li>\n");
+                }
+                html.push_str(&format!("</{}>\n", tag));
+            }
+            Block::Quote(blocks) => {
+                cr(html);
+                html.push_str("<blockquote>\n");
+                render_blocks(html, blocks, false);
+                html.push_str("</blockquote>\n");
+            }
+            Block::Rule => {
+                cr(html);
+                html.push_str("<hr />\n");
+            }
+        }
+    }
+}
+
+fn render_inlines(html: &mut String, inlines: &[Inline]) {
+    for inline in inlines {
+        match inline {
+            Inline::Text(text) => html.push_str(&escape_html(text)),
+            Inline::Code(code) => html.push_str(&format!("<code>{}</code>", escape_html(code))),
+            Inline::Emphasis(children) => {
+                html.push_str("<em>");
+                render_inlines(html, children);
+                html.push_str("</em>");
+            }
+            Inline::Strong(children) => {
+                html.push_str("<strong>");
+                render_inlines(html, children);
+                html.push_str("</strong>");
+            }
+            Inline::Link { dest, title, children } => {
+                html.push_str(&format!("<a href=\"{}\"", escape_url(dest)));
+                if let Some(title) = title {
+                    html.push_str(&format!(" title=\"{}\"", escape_html(title)));
+                }
+                html.push('>');
+                render_inlines(html, children);
+                html.push_str("</a>");
+            }
+            Inline::LineBreak => html.push_str("<br />\n"),
+            Inline::SoftBreak => html.push('\n'),
+        }
+    }
+}
+
+fn plain_blocks(text: &mut String, blocks: &[Block]) {
+    for block in blocks {
+        match block {
+            Block::Heading(_, inlines) | Block::Paragraph(inlines) => plain_inlines(text, inlines),
+            Block::Code { text: code, .. } => text.push_str(code),
+            Block::List { items, .. } => items.iter().for_each(|item| plain_blocks(text, item)),
+            Block::Quote(blocks) => plain_blocks(text, blocks),
+            Block::Rule => {}
+        }
+        text.push(' ');
+    }
+}
+
+fn plain_inlines(text: &mut String, inlines: &[Inline]) {
+    for inline in inlines {
+        match inline {
+            Inline::Text(t) | Inline::Code(t) => text.push_str(t),
+            Inline::Emphasis(children) | Inline::Strong(children) | Inline::Link { children, .. } => {
+                plain_inlines(text, children)
+            }
+            Inline::LineBreak | Inline::SoftBreak => text.push(' '),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Examples from the CommonMark 0.31 spec, with the HTML the reference implementation gives.
+    const COMMONMARK: &[(&str, &str)] = &[
+        // ATX headings
+        (
+            "# foo\n## foo\n### foo\n#### foo\n##### foo\n###### foo\n",
+            "<h1>foo</h1>\n<h2>foo</h2>\n<h3>foo</h3>\n<h4>foo</h4>\n<h5>foo</h5>\n<h6>foo</h6>\n",
+        ),
+        ("####### foo\n", "<p>####### foo</p>\n"),
+        ("#5 bolt\n\n#hashtag\n", "<p>#5 bolt</p>\n<p>#hashtag</p>\n"),
+        ("# foo *bar* \\*baz\\*\n", "<h1>foo <em>bar</em> *baz*</h1>\n"),
+        ("## foo ##\n  ###   bar    ###\n", "<h2>foo</h2>\n<h3>bar</h3>\n"),
+        // Setext headings
+        (
+            "Foo *bar*\n=========\n\nFoo *bar*\n---------\n",
+            "<h1>Foo <em>bar</em></h1>\n<h2>Foo <em>bar</em></h2>\n",
+        ),
+        // Thematic breaks
+        ("***\n---\n___\n", "<hr />\n<hr />\n<hr />\n"),
+        ("- - -\n", "<hr />\n"),
+        // Paragraphs
+        ("aaa\n\nbbb\n", "<p>aaa</p>\n<p>bbb</p>\n"),
+        ("  aaa\n bbb\n", "<p>aaa\nbbb</p>\n"),
+        // Code blocks
+        (
+            "    a simple\n      indented code block\n",
+            "<pre><code>a simple\n  indented code block\n</code></pre>\n",
+        ),
+        ("```\n<\n >\n```\n", "<pre><code>&lt;\n &gt;\n</code></pre>\n"),
+        (
+            "```ruby\ndef foo(x)\n  return 3\nend\n```\n",
+            "<pre><code class=\"language-ruby\">def foo(x)\n  return 3\nend\n</code></pre>\n",
+        ),
+        ("~~~\naaa\n```\n~~~\n", "<pre><code>aaa\n```\n</code></pre>\n"),
+        // Block quotes
+        (
+            "> # Foo\n> bar\n> baz\n",
+            "<blockquote>\n<h1>Foo</h1>\n<p>bar\nbaz</p>\n</blockquote>\n",
+        ),
+        ("> bar\nbaz\n", "<blockquote>\n<p>bar\nbaz</p>\n</blockquote>\n"),
+        // Lists
+        (
+            "- foo\n- bar\n- baz\n",
+            "<ul>\n<li>foo</li>\n<li>bar</li>\n<li>baz</li>\n</ul>\n",
+        ),
+        (
+            "1. foo\n2. bar\n3) baz\n",
+            "<ol>\n<li>foo</li>\n<li>bar</li>\n</ol>\n<ol start=\"3\">\n<li>baz</li>\n</ol>\n",
+        ),
+        (
+            "- foo\n\n- bar\n",
+            "<ul>\n<li>\n<p>foo</p>\n</li>\n<li>\n<p>bar</p>\n</li>\n</ul>\n",
+        ),
+        (
+            "- foo\n  - bar\n    - baz\n",
+            "<ul>\n<li>foo\n<ul>\n<li>bar\n<ul>\n<li>baz</li>\n</ul>\n</li>\n</ul>\n</li>\n</ul>\n",
+        ),
+        (
+            "1. a\n\n   b\n",
+            "<ol>\n<li>\n<p>a</p>\n<p>b</p>\n</li>\n</ol>\n",
+        ),
+        ("- a\n- b\n\n- c\n", "<ul>\n<li>\n<p>a</p>\n</li>\n<li>\n<p>b</p>\n</li>\n<li>\n<p>c</p>\n</li>\n</ul>\n"),
+        // Emphasis
+        ("*foo bar*\n", "<p><em>foo bar</em></p>\n"),
+        ("a * foo bar*\n", "<p>a * foo bar*</p>\n"),
+        ("foo*bar*\n", "<p>foo<em>bar</em></p>\n"),
+        ("_foo_bar\n", "<p>_foo_bar</p>\n"),
+        ("**foo bar**\n", "<p><strong>foo bar</strong></p>\n"),
+        ("*foo**bar**baz*\n", "<p><em>foo<strong>bar</strong>baz</em></p>\n"),
+        ("***strong emph***\n", "<p><em><strong>strong emph</strong></em></p>\n"),
+        ("*foo**bar*\n", "<p><em>foo**bar</em></p>\n"),
+        // Code spans
+        ("`foo`\n", "<p><code>foo</code></p>\n"),
+        ("`` foo ` bar ``\n", "<p><code>foo ` bar</code></p>\n"),
+        ("`<a>`\n", "<p><code>&lt;a&gt;</code></p>\n"),
+        ("`foo\\`bar`\n", "<p><code>foo\\</code>bar`</p>\n"),
+        // Links
+        ("[link](/uri \"title\")\n", "<p><a href=\"/uri\" title=\"title\">link</a></p>\n"),
+        ("[link](/uri)\n", "<p><a href=\"/uri\">link</a></p>\n"),
+        ("[link]()\n", "<p><a href=\"\">link</a></p>\n"),
+        ("[link](/my uri)\n", "<p>[link](/my uri)</p>\n"),
+        ("[link *foo **bar** `#`*](/uri)\n", "<p><a href=\"/uri\">link <em>foo <strong>bar</strong> <code>#</code></em></a></p>\n"),
+        ("<http://foo.bar.baz>\n", "<p><a href=\"http://foo.bar.baz\">http://foo.bar.baz</a></p>\n"),
+        ("<foo@bar.example.com>\n", "<p><a href=\"mailto:foo@bar.example.com\">foo@bar.example.com</a></p>\n"),
+        // Escapes and line breaks
+        ("\\*not emphasized*\n", "<p>*not emphasized*</p>\n"),
+        ("foo  \nbaz\n", "<p>foo<br />\nbaz</p>\n"),
+        ("foo\\\nbaz\n", "<p>foo<br />\nbaz</p>\n"),
+    ];
+
+    #[test]
+    fn commonmark_examples() {
+        for (markdown, html) in COMMONMARK {
+            assert_eq!(to_html(markdown), *html, "rendering {:?}", markdown);
+        }
+    }
+
+    #[test]
+    fn html_is_escaped_and_unsafe_links_are_dropped() {
+        assert_eq!(
+            to_html("a < b & \"c\" <script>alert(1)</script>"),
+            "<p>a &lt; b &amp; &quot;c&quot; &lt;script&gt;alert(1)&lt;/script&gt;</p>\n"
+        );
+        assert_eq!(
+            to_html("[click](javascript:alert(1)) [ok](https://example.com/a b)"),
+            "<p><a href=\"\">click</a> [ok](https://example.com/a b)</p>\n"
+        );
+        assert_eq!(to_html("[é](/caf\u{e9})"), "<p><a href=\"/caf%C3%A9\">é</a></p>\n");
+    }
+
+    #[test]
+    fn nesting_past_the_limit_is_left_as_text() {
+        let quotes = to_html(&">".repeat(50_000));
+        assert_eq!(quotes.matches("<blockquote>").count(), MAX_NESTING);
+        assert!(quotes.contains(&format!("<p>{}</p>", "&gt;".repeat(50_000 - MAX_NESTING))));
+
+        let list: String = (0..MAX_NESTING + 5).map(|k| format!("{}- x\n", "  ".repeat(k))).collect();
+        let list = to_html(&list);
+        assert_eq!(list.matches("<ul>").count(), MAX_NESTING);
+        assert!(list.contains("<li>x\n- x\n- x\n- x\n- x\n- x</li>"));
+
+        let links = format!("{}x{}", "[".repeat(MAX_NESTING + 5), "](/a)".repeat(MAX_NESTING + 5));
+        assert_eq!(to_html(&links).matches("<a ").count(), MAX_NESTING);
+
+        let emphasis = format!("{}x{}", "*a ".repeat(MAX_NESTING + 5), " a*".repeat(MAX_NESTING + 5));
+        assert_eq!(to_html(&emphasis).matches("<em>").count(), MAX_NESTING);
+    }
+
+    #[test]
+    fn long_runs_of_brackets_and_delimiters_render() {
+        let n = 20_000;
+        assert_eq!(to_html(&"[".repeat(n)), format!("<p>{}</p>\n", "[".repeat(n)));
+        assert_eq!(to_html(&"](".repeat(n)).len(), 2 * n + 8);
+        assert_eq!(to_html(&"*a* ".repeat(n)).matches("<em>").count(), n);
+        assert_eq!(to_html(&"a* ".repeat(n)).matches("<em>").count(), 0);
+        assert_eq!(to_html(&"[a](".repeat(n)).matches("<a ").count(), 0);
+    }
+
+    #[test]
+    fn summaries_are_plain_text_cut_at_a_word() {
+        let post = "# Lunch\n\nI ate a *salad* with [friends](/friends).\n\n- tomatoes\n- `olives`\n";
+
+        assert_eq!(summary(post, 100), "Lunch I ate a salad with friends. tomatoes olives");
+        assert_eq!(summary(post, 20), "Lunch I ate a salad…");
+        assert_eq!(summary(post, 26), "Lunch I ate a salad with…");
+        assert_eq!(summary("", 10), "");
+    }
+}
@@ -0,0 +1,347 @@
+// Keeps Posts on disk, one file per post in a directory, named after the post's id:
+//
+//     posts/1.post
+//     posts/2.post
+//
+// Each file is plain text. A few entries come first, one per line, and everything after the
+// `content` line is the post's text, exactly as it was written:
+//
+//     state PendingReview
+//     approved bob
+//     event 1700000000 request_review Draft PendingReview alice
+//     event 1700000100 approve PendingReview PendingReview bob
+//     content
+//     I ate a salad for lunch today
+//
+// The state object can't be written out as it is, so the file stores its name (plus the
+// reviewers who approved a pending post) and loading builds the matching State again. Saves go
+// through a temporary file and a rename, like QuotaStore's. A new post first claims its id by
+// creating the empty file with `create_new`, so two posts created at once never share an id.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use super::{AuditEntry, Draft, PendingReview, Post, Published, State};
+
+const EXTENSION: &str = "post";
+
+pub struct PostStore {
+    dir: PathBuf,
+}
+
+impl PostStore {
+    pub fn new<P: AsRef<Path>>(dir: P) -> PostStore {
+        PostStore {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{}.{}", id, EXTENSION))
+    }
+
+    /// Saves a new post under the next free id and returns the id.
+    pub fn create(&self, post: &Post) -> io::Result<u64> {
+        fs::create_dir_all(&self.dir)?;
+        let mut id = self.ids()?.last().map_or(1, |last| last + 1);
+
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(self.path(id)) {
+                Ok(_) => break,
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => id += 1,
+                Err(e) => return Err(e),
+            }
+        }
+
+        if let Err(e) = self.save(id, post) {
+            let _ = fs::remove_file(self.path(id));
+            return Err(e);
+        }
+        Ok(id)
+    }
+
+    pub fn save(&self, id: u64, post: &Post) -> io::Result<()> {
+        let mut contents = format!("state {}\n", post.state());
+        for reviewer in post.approved_by() {
+            contents.push_str(&format!("approved {}\n", single_line(reviewer)?));
+        }
+        for entry in post.history() {
+            contents.push_str(&format!(
+                "event {} {} {} {} {}\n",
+                entry.timestamp,
+                entry.action,
+                entry.from,
+                entry.to,
+                single_line(&entry.actor)?
+            ));
+        }
+        contents.push_str("content\n");
+        contents.push_str(&post.content);
+
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(id);
+        let tmp_path = path.with_extension("tmp");
+        let mut tmp = File::create(&tmp_path)?;
+        tmp.write_all(contents.as_bytes())?;
+        tmp.sync_all()?;
+
+        fs::rename(&tmp_path, &path)
+    }
+
+    pub fn load(&self, id: u64) -> io::Result<Post> {
+        let path = self.path(id);
+        let contents = fs::read_to_string(&path).map_err(|e| match e.kind() {
+            ErrorKind::NotFound => io::Error::new(ErrorKind::NotFound, format!("no post {}", id)),
+            _ => e,
+        })?;
+
+        parse(&contents).map_err(|(line, message)| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("{}: line {}: {}", path.display(), line, message),
+            )
+        })
+    }
+
+    /// The ids of every saved post, lowest first. A store that doesn't exist yet is empty.
+    pub fn ids(&self) -> io::Result<Vec<u64>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+
+        let mut ids = vec![];
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == EXTENSION) {
+                if let Some(id) = path.file_stem().and_then(|s| s.to_str()?.parse().ok()) {
+                    ids.push(id);
+                }
+            }
+        }
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    /// Every post in the given state ("Draft", "PendingReview" or "Published"), or every post
+    /// when `state` is None, with its id. A file that can't be loaded is listed with its error
+    /// whatever the state, so one bad file doesn't hide the rest. Empty files are skipped: they
+    /// are ids claimed by `create` whose post isn't saved yet, or never was if the process died.
+    pub fn list(&self, state: Option<&str>) -> io::Result<Vec<(u64, io::Result<Post>)>> {
+        let mut posts = vec![];
+        for id in self.ids()? {
+            if fs::metadata(self.path(id)).is_ok_and(|metadata| metadata.len() == 0) {
+                continue;
+            }
+            match self.load(id) {
+                Ok(post) if state.is_some_and(|state| post.state() != state) => {}
+                // Deleted since the directory was read.
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                loaded => posts.push((id, loaded)),
+            }
+        }
+        Ok(posts)
+    }
+}
+
+fn single_line(text: &str) -> io::Result<&str> {
+    if text.contains('\n') || text.trim().is_empty() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("`{}` can't be stored on a single line", text.escape_debug()),
+        ));
+    }
+    Ok(text)
+}
+
+// Errors carry the line number they were found on.
+fn parse(contents: &str) -> Result<Post, (usize, String)> {
+    let mut state = None;
+    let mut approved_by = vec![];
+    let mut first_approval = None;
+    let mut history = vec![];
+    let mut rest = contents;
+    let mut line_number = 0;
+
+    loop {
+        line_number += 1;
+        let (line, remainder) = match rest.split_once('\n') {
+            Some(split) => split,
+            None => return Err((line_number, String::from("missing `content` line"))),
+        };
+        rest = remainder;
+
+        let unknown = |what: &str, name: &str| (line_number, format!("unknown {} `{}`", what, name));
+        let (keyword, value) = line.split_once(' ').unwrap_or((line, ""));
+
+        match (keyword, value.splitn(5, ' ').collect::<Vec<_>>().as_slice()) {
+            ("content", [""]) => break,
+            ("state", [name]) => {
+                state = Some(state_name(name).ok_or_else(|| unknown("state", name))?);
+            }
+            ("approved", _) if value.trim().is_empty() => {
+                return Err((line_number, String::from("`approved` needs the reviewer's name")));
+            }
+            ("approved", _) => {
+                first_approval.get_or_insert(line_number);
+                approved_by.push(String::from(value));
+            }
+            ("event", [timestamp, action, from, to, actor]) => history.push(AuditEntry {
+                timestamp: timestamp
+                    .parse()
+                    .map_err(|_| (line_number, format!("invalid timestamp `{}`", timestamp)))?,
+                actor: String::from(*actor),
+                action: action_name(action).ok_or_else(|| unknown("action", action))?,
+                from: state_name(from).ok_or_else(|| unknown("state", from))?,
+                to: state_name(to).ok_or_else(|| unknown("state", to))?,
+            }),
+            _ => return Err((line_number, format!("unexpected entry `{}`", line))),
+        }
+    }
+
+    let state: Box<dyn State> = match state {
+        Some("Draft") if approved_by.is_empty() => Box::new(Draft {}),
+        Some("PendingReview") => Box::new(PendingReview { approved_by }),
+        Some("Published") if approved_by.is_empty() => Box::new(Published {}),
+        Some(name) => {
+            let line = first_approval.unwrap_or(line_number);
+            return Err((line, format!("a {} post can't have approvals", name)));
+        }
+        None => return Err((line_number, String::from("missing `state` entry"))),
+    };
+
+    Ok(Post {
+        state: Some(state),
+        content: String::from(rest),
+        history,
+    })
+}
+
+fn state_name(name: &str) -> Option<&'static str> {
+    ["Draft", "PendingReview", "Published"].into_iter().find(|n| *n == name)
+}
+
+fn action_name(name: &str) -> Option<&'static str> {
+    ["request_review", "approve", "reject"].into_iter().find(|n| *n == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store(name: &str) -> PostStore {
+        let dir = std::env::temp_dir().join(format!("post_store_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        PostStore::new(dir)
+    }
+
+    #[test]
+    fn posts_come_back_in_the_state_they_were_saved_in() {
+        let store = temp_store("round_trip");
+
+        let mut post = Post::new();
+        post.add_text("line one\ncontent\nline three\n").unwrap();
+        post.request_review("alice").unwrap();
+        post.approve("bob smith").unwrap();
+        let id = store.create(&post).unwrap();
+
+        let mut loaded = store.load(id).unwrap();
+        assert_eq!(loaded.state(), "PendingReview");
+        assert_eq!(loaded.approved_by(), ["bob smith"]);
+        assert_eq!(loaded.history(), post.history());
+
+        // The reloaded post carries on where the saved one left off.
+        loaded.approve("carol").unwrap();
+        assert_eq!(loaded.content(), "line one\ncontent\nline three\n");
+
+        fs::remove_dir_all(store.dir()).unwrap();
+    }
+
+    #[test]
+    fn posts_are_listed_by_state() {
+        let store = temp_store("list");
+        assert!(store.list(None).unwrap().is_empty());
+
+        let draft = Post::new();
+        let mut pending = Post::new();
+        pending.request_review("alice").unwrap();
+
+        assert_eq!(store.create(&draft).unwrap(), 1);
+        assert_eq!(store.create(&pending).unwrap(), 2);
+        assert_eq!(store.create(&draft).unwrap(), 3);
+
+        let ids = |state| -> Vec<u64> {
+            store.list(state).unwrap().into_iter().map(|(id, _)| id).collect()
+        };
+        assert_eq!(ids(Some("Draft")), vec![1, 3]);
+        assert_eq!(ids(Some("PendingReview")), vec![2]);
+        assert_eq!(ids(Some("Published")), Vec::<u64>::new());
+        assert_eq!(ids(None), vec![1, 2, 3]);
+
+        fs::remove_dir_all(store.dir()).unwrap();
+    }
+
+    #[test]
+    fn listing_skips_claimed_ids_and_reports_broken_files() {
+        let store = temp_store("list_broken");
+        store.create(&Post::new()).unwrap();
+        // What's left when the process dies between claiming an id and saving the post.
+        File::create(store.path(2)).unwrap();
+        fs::write(store.path(3), "state Archived\ncontent\n").unwrap();
+        store.create(&Post::new()).unwrap();
+
+        let listed = store.list(Some("Draft")).unwrap();
+        let ids: Vec<u64> = listed.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![1, 3, 4]);
+        let error = listed[1].1.as_ref().err().unwrap();
+        assert!(error.to_string().ends_with("line 1: unknown state `Archived`"));
+        assert_eq!(store.list(Some("Draft")).unwrap().iter().filter(|(_, post)| post.is_ok()).count(), 2);
+
+        fs::remove_dir_all(store.dir()).unwrap();
+    }
+
+    #[test]
+    fn broken_files_are_reported_with_their_line() {
+        let store = temp_store("broken");
+        fs::create_dir_all(store.dir()).unwrap();
+        fs::write(store.path(1), "state Draft\nevent soon approve Draft Draft bob\ncontent\n").unwrap();
+        fs::write(store.path(2), "state Archived\ncontent\n").unwrap();
+        let published = "state Published\nevent 1 approve PendingReview Published bob\napproved bob\ncontent\n";
+        fs::write(store.path(3), published).unwrap();
+        fs::write(store.path(4), "state PendingReview\napproved \ncontent\n").unwrap();
+
+        let message = |id| store.load(id).err().unwrap().to_string();
+        assert!(message(1).ends_with("1.post: line 2: invalid timestamp `soon`"));
+        assert!(message(2).ends_with("line 1: unknown state `Archived`"));
+        assert!(message(3).ends_with("line 3: a Published post can't have approvals"));
+        assert!(message(4).ends_with("line 2: `approved` needs the reviewer's name"));
+        assert_eq!(store.load(5).err().unwrap().kind(), ErrorKind::NotFound);
+
+        fs::remove_dir_all(store.dir()).unwrap();
+    }
+
+    #[test]
+    fn posts_created_at_the_same_time_get_their_own_ids() {
+        let store = temp_store("concurrent");
+
+        let mut ids: Vec<u64> = std::thread::scope(|scope| {
+            let create_five = || -> Vec<u64> {
+                (0..5).map(|_| store.create(&Post::new()).unwrap()).collect()
+            };
+            let creators: Vec<_> = (0..8).map(|_| scope.spawn(create_five)).collect();
+            creators.into_iter().flat_map(|c| c.join().unwrap()).collect()
+        });
+        ids.sort_unstable();
+
+        assert_eq!(ids, (1..=40).collect::<Vec<u64>>());
+        assert_eq!(store.list(Some("Draft")).unwrap().len(), 40);
+
+        fs::remove_dir_all(store.dir()).unwrap();
+    }
+}
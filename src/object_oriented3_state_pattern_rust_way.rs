@@ -1,3 +1,19 @@
+// Each state of a post is its own type, and every transition consumes the old value and returns
+// a new one. Calling a method that doesn't exist in the current state (approving a draft,
+// reading a post under review, editing a published post) is a compile error rather than a
+// no-op; the `compile_fail` examples below check exactly that.
+//
+//   DraftPost --request_review--> PendingReviewPost --approve--> PartlyApprovedPost
+//       ^                              |                              |      |
+//       +------------reject------------+-------------reject-----------+   approve (another
+//                                                                     |    reviewer)
+//                          Post <--publish-- ApprovedPost <-----------+
+//                           |                  |     ^
+//                           |           schedule |     | cancel
+//                        archive               v     |
+//                           |              ScheduledPost --publish_due--> Post
+//                           v
+//                      ArchivedPost --restore--> Post
 
 pub fn run(){
     let mut post = Post::new();
@@ -6,22 +22,81 @@ pub fn run(){
 
     let post = post.request_review();
 
-    let post = post.approve();
+    let mut post = post.reject();
+    post.add_text(" and it was great");
 
-    assert_eq!("I ate a salad for lunch today", post.content());
+    let post = post.request_review();
+
+    let post = post.approve("bob");
+
+    let post = match post.approve("carol") {
+        Ok(post) => post,
+        Err(_) => panic!("carol and bob are different reviewers"),
+    };
+
+    let post = post.publish();
+
+    assert_eq!("I ate a salad for lunch today and it was great", post.content());
+
+    let archived = post.archive();
+    let post = archived.restore();
+    assert_eq!("I ate a salad for lunch today and it was great", post.content());
 }
 
+/// A published post, the only state whose content can be read.
+///
+/// ```compile_fail
+/// use rust_concepts::object_oriented3_state_pattern_rust_way::Post;
+///
+/// let draft = Post::new();
+/// draft.content();
+/// ```
+///
+/// Published posts can't be edited:
+///
+/// ```compile_fail
+/// use rust_concepts::object_oriented3_state_pattern_rust_way::Post;
+///
+/// let mut draft = Post::new();
+/// draft.add_text("salad");
+/// let approved = draft.request_review().approve("bob").approve("carol").ok().unwrap();
+/// let mut post = approved.publish();
+/// post.add_text("more salad");
+/// ```
 pub struct Post{
     content: String
 }
 
+/// A post that is still being written.
+///
+/// Drafts can't skip the review:
+///
+/// ```compile_fail
+/// use rust_concepts::object_oriented3_state_pattern_rust_way::Post;
+///
+/// let draft = Post::new();
+/// let post = draft.approve("bob");
+/// ```
+///
+/// and a draft that has been sent for review is gone:
+///
+/// ```compile_fail
+/// use rust_concepts::object_oriented3_state_pattern_rust_way::Post;
+///
+/// let mut draft = Post::new();
+/// let pending = draft.request_review();
+/// draft.add_text("too late");
+/// ```
 pub struct DraftPost{
     content: String
 }
 
 impl Post {
+    // `new` hands out a draft on purpose: there is no way to make a published Post without going
+    // through the review.
+    #[allow(clippy::new_ret_no_self)]
     pub fn new() -> DraftPost{
-        DrafPost{
+        DraftPost{
             content: String::new()
         }
     }
@@ -29,6 +104,12 @@ impl Post {
     pub fn content(&self) -> &str{
         &self.content
     }
+
+    pub fn archive(self) -> ArchivedPost{
+        ArchivedPost{
+            content: self.content
+        }
+    }
 }
 
 impl DraftPost{
@@ -43,14 +124,208 @@ impl DraftPost{
     }
 }
 
+/// A post waiting for its first approval. It can't be edited or published yet:
+///
+/// ```compile_fail
+/// use rust_concepts::object_oriented3_state_pattern_rust_way::Post;
+///
+/// let pending = Post::new().request_review();
+/// let post = pending.publish();
+/// ```
 pub struct PendingReviewPost{
     content: String
 }
 
 impl PendingReviewPost{
-    pub fn approve(self) -> Post{
+    pub fn approve(self, reviewer: &str) -> PartlyApprovedPost{
+        PartlyApprovedPost{
+            content: self.content,
+            first_reviewer: String::from(reviewer)
+        }
+    }
+
+    pub fn reject(self) -> DraftPost{
+        DraftPost{
+            content: self.content
+        }
+    }
+}
+
+/// A post with one approval. One more, from a different reviewer, is needed:
+///
+/// ```compile_fail
+/// use rust_concepts::object_oriented3_state_pattern_rust_way::Post;
+///
+/// let approved_once = Post::new().request_review().approve("bob");
+/// let post = approved_once.publish();
+/// ```
+pub struct PartlyApprovedPost{
+    content: String,
+    first_reviewer: String
+}
+
+impl PartlyApprovedPost{
+    /// A second approval from the first reviewer doesn't count and gives the post back
+    /// unchanged.
+    pub fn approve(self, reviewer: &str) -> Result<ApprovedPost, PartlyApprovedPost>{
+        if reviewer == self.first_reviewer {
+            return Err(self);
+        }
+
+        Ok(ApprovedPost{
+            content: self.content
+        })
+    }
+
+    pub fn reject(self) -> DraftPost{
+        DraftPost{
+            content: self.content
+        }
+    }
+}
+
+/// A post that has passed review and can be published now or later.
+pub struct ApprovedPost{
+    content: String
+}
+
+impl ApprovedPost{
+    pub fn publish(self) -> Post{
         Post{
             content: self.content
         }
     }
-}
\ No newline at end of file
+
+    /// `at` is in seconds since the Unix epoch.
+    pub fn schedule(self, at: u64) -> ScheduledPost{
+        ScheduledPost{
+            content: self.content,
+            at
+        }
+    }
+}
+
+/// An approved post waiting for its publication time. It can't be read early:
+///
+/// ```compile_fail
+/// use rust_concepts::object_oriented3_state_pattern_rust_way::Post;
+///
+/// let approved = Post::new().request_review().approve("bob").approve("carol").ok().unwrap();
+/// let scheduled = approved.schedule(1_700_000_000);
+/// scheduled.content();
+/// ```
+pub struct ScheduledPost{
+    content: String,
+    at: u64
+}
+
+impl ScheduledPost{
+    pub fn publish_at(&self) -> u64{
+        self.at
+    }
+
+    /// Publishes the post if `now` (seconds since the Unix epoch) has reached its time, and
+    /// hands it back still scheduled otherwise.
+    pub fn publish_due(self, now: u64) -> Result<Post, ScheduledPost>{
+        if now < self.at {
+            return Err(self);
+        }
+
+        Ok(Post{
+            content: self.content
+        })
+    }
+
+    pub fn cancel(self) -> ApprovedPost{
+        ApprovedPost{
+            content: self.content
+        }
+    }
+}
+
+/// A post taken down after publication. It is no longer readable until it is restored:
+///
+/// ```compile_fail
+/// use rust_concepts::object_oriented3_state_pattern_rust_way::Post;
+///
+/// let approved = Post::new().request_review().approve("bob").approve("carol").ok().unwrap();
+/// let archived = approved.publish().archive();
+/// archived.content();
+/// ```
+pub struct ArchivedPost{
+    content: String
+}
+
+impl ArchivedPost{
+    pub fn restore(self) -> Post{
+        Post{
+            content: self.content
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approved(text: &str) -> ApprovedPost {
+        let mut draft = Post::new();
+        draft.add_text(text);
+        match draft.request_review().approve("bob").approve("carol") {
+            Ok(post) => post,
+            Err(_) => panic!("two reviewers approved"),
+        }
+    }
+
+    #[test]
+    fn rejected_posts_can_be_edited_again() {
+        let mut draft = Post::new();
+        draft.add_text("first");
+
+        let mut draft = draft.request_review().reject();
+        draft.add_text(" second");
+        let mut draft = draft.request_review().approve("bob").reject();
+        draft.add_text(" third");
+
+        let post = match draft.request_review().approve("bob").approve("carol") {
+            Ok(post) => post.publish(),
+            Err(_) => panic!("two reviewers approved"),
+        };
+        assert_eq!(post.content(), "first second third");
+    }
+
+    #[test]
+    fn the_same_reviewer_cannot_approve_twice() {
+        let once = Post::new().request_review().approve("bob");
+
+        let once = match once.approve("bob") {
+            Ok(_) => panic!("bob approved twice"),
+            Err(once) => once,
+        };
+        assert!(once.approve("carol").is_ok());
+    }
+
+    #[test]
+    fn scheduled_posts_publish_when_due() {
+        let scheduled = approved("later").schedule(1_000);
+        assert_eq!(scheduled.publish_at(), 1_000);
+
+        let scheduled = match scheduled.publish_due(999) {
+            Ok(_) => panic!("published early"),
+            Err(scheduled) => scheduled,
+        };
+        match scheduled.publish_due(1_000) {
+            Ok(post) => assert_eq!(post.content(), "later"),
+            Err(_) => panic!("not published when due"),
+        }
+
+        let post = approved("again").schedule(5).cancel().publish();
+        assert_eq!(post.content(), "again");
+    }
+
+    #[test]
+    fn archived_posts_can_be_restored() {
+        let post = approved("old news").publish().archive().restore();
+        assert_eq!(post.content(), "old news");
+    }
+}
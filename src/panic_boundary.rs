@@ -0,0 +1,286 @@
+// Runs lessons that panic on purpose (Guess::new(200), the `expect` on bye.txt in error_handling,
+// `largest` on an empty slice) without taking the whole program down with them.
+//
+// `catch` runs a lesson inside panic::catch_unwind and turns a panic into a CrashReport: the
+// panic message, where it happened, the thread and a backtrace. A Runner runs lessons one after
+// another through `catch`, writes a report for each one that panicked into a crash log
+// directory, and carries on with the next lesson.
+//
+// catch_unwind only hands back the panic payload, so the location and backtrace are picked up by
+// a panic hook. The hook is installed once for the process; it records panics on threads that are
+// inside `catch` (instead of printing them) and passes every other panic on to the hook that was
+// there before.
+
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error_handling::{self, Guess};
+use crate::generics::{self, largest};
+
+pub fn run() {
+    let mut runner = Runner::new("crash_logs");
+
+    runner.run("generics", generics::run);
+    runner.run("guess_out_of_range", || {
+        Guess::new(200);
+    });
+    runner.run("error_handling", error_handling::run);
+    runner.run("largest_of_nothing", || {
+        largest::<i32>(&[]);
+    });
+
+    println!(
+        "{} lessons ran, {} panicked; reports are in {}",
+        runner.ran(),
+        runner.crashes().len(),
+        runner.log_dir().display()
+    );
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashReport {
+    pub lesson: String,
+    pub message: String,
+    /// `file:line:column`, when the panic hook was told.
+    pub location: Option<String>,
+    pub thread: String,
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub backtrace: String,
+}
+
+impl CrashReport {
+    /// The report as it is written to the crash log.
+    pub fn to_log(&self) -> String {
+        format!(
+            "lesson: {}\nmessage: {}\nlocation: {}\nthread: {}\ntime: {}\n\nbacktrace:\n{}\n",
+            self.lesson,
+            self.message,
+            self.location.as_deref().unwrap_or("unknown"),
+            self.thread,
+            self.timestamp,
+            self.backtrace
+        )
+    }
+}
+
+impl fmt::Display for CrashReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "lesson `{}` panicked", self.lesson)?;
+        if let Some(location) = &self.location {
+            write!(f, " at {}", location)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl std::error::Error for CrashReport {}
+
+// What the hook saw of the last panic on this thread.
+struct Captured {
+    location: Option<String>,
+    backtrace: String,
+}
+
+thread_local! {
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+    static CAPTURED: RefCell<Option<Captured>> = const { RefCell::new(None) };
+}
+
+static INSTALL_HOOK: Once = Once::new();
+
+fn install_hook() {
+    INSTALL_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if DEPTH.with(Cell::get) == 0 {
+                return previous(info);
+            }
+            let captured = Captured {
+                location: info
+                    .location()
+                    .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column())),
+                backtrace: Backtrace::force_capture().to_string(),
+            };
+            CAPTURED.with(|c| *c.borrow_mut() = Some(captured));
+        }));
+    });
+}
+
+/// Runs `f`, turning a panic into a CrashReport for `lesson`.
+pub fn catch<F, T>(lesson: &str, f: F) -> Result<T, Box<CrashReport>>
+where
+    F: FnOnce() -> T,
+{
+    install_hook();
+
+    DEPTH.with(|d| d.set(d.get() + 1));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    DEPTH.with(|d| d.set(d.get() - 1));
+
+    let payload = match result {
+        Ok(value) => return Ok(value),
+        Err(payload) => payload,
+    };
+    let message = match payload.downcast_ref::<&str>() {
+        Some(s) => String::from(*s),
+        None => match payload.downcast_ref::<String>() {
+            Some(s) => s.clone(),
+            None => String::from("(a panic payload that isn't a string)"),
+        },
+    };
+    let captured = CAPTURED.with(|c| c.borrow_mut().take());
+
+    Err(Box::new(CrashReport {
+        lesson: String::from(lesson),
+        message,
+        location: captured.as_ref().and_then(|c| c.location.clone()),
+        thread: String::from(thread::current().name().unwrap_or("unnamed")),
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        backtrace: captured.map(|c| c.backtrace).unwrap_or_default(),
+    }))
+}
+
+/// Writes the report to `<dir>/<timestamp>-<lesson>.log` (with a number added if that is taken)
+/// and returns the path.
+pub fn write_report<P: AsRef<Path>>(dir: P, report: &CrashReport) -> io::Result<PathBuf> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+
+    let lesson: String = report
+        .lesson
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect();
+
+    for n in 1.. {
+        let name = match n {
+            1 => format!("{}-{}.log", report.timestamp, lesson),
+            n => format!("{}-{}-{}.log", report.timestamp, lesson, n),
+        };
+        let path = dir.join(name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(report.to_log().as_bytes())?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!()
+}
+
+/// Runs lessons one after another, logging the ones that panic.
+pub struct Runner {
+    log_dir: PathBuf,
+    ran: usize,
+    crashes: Vec<(CrashReport, Option<PathBuf>)>,
+}
+
+impl Runner {
+    pub fn new<P: AsRef<Path>>(log_dir: P) -> Runner {
+        Runner {
+            log_dir: log_dir.as_ref().to_path_buf(),
+            ran: 0,
+            crashes: vec![],
+        }
+    }
+
+    pub fn log_dir(&self) -> &Path {
+        &self.log_dir
+    }
+
+    /// Runs the lesson and returns whether it finished. A crash is reported on stderr and
+    /// written to the log directory; a log that can't be written doesn't stop the runner either.
+    pub fn run<F: FnOnce()>(&mut self, lesson: &str, f: F) -> bool {
+        self.ran += 1;
+        let report = match catch(lesson, f) {
+            Ok(()) => return true,
+            Err(report) => *report,
+        };
+
+        eprintln!("{}", report);
+        let log = match write_report(&self.log_dir, &report) {
+            Ok(path) => Some(path),
+            Err(e) => {
+                eprintln!("couldn't write the crash report: {}", e);
+                None
+            }
+        };
+        self.crashes.push((report, log));
+        false
+    }
+
+    pub fn ran(&self) -> usize {
+        self.ran
+    }
+
+    /// The reports of the lessons that panicked, in the order they ran.
+    pub fn crashes(&self) -> Vec<&CrashReport> {
+        self.crashes.iter().map(|(report, _)| report).collect()
+    }
+
+    /// The log files that were written.
+    pub fn logs(&self) -> Vec<&Path> {
+        self.crashes.iter().filter_map(|(_, log)| log.as_deref()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panics_become_reports_with_their_location() {
+        let report = catch("guess", || Guess::new(200).value()).unwrap_err();
+        assert_eq!(report.message, "Guess value must be between 1 and 100, got 200.");
+        assert!(report.location.as_deref().unwrap().starts_with("src/error_handling.rs:"));
+        assert!(!report.backtrace.is_empty());
+        assert!(report.to_string().starts_with("lesson `guess` panicked at src/error_handling.rs:"));
+
+        let report = catch("largest", || largest::<char>(&[])).unwrap_err();
+        assert_eq!(report.message, "index out of bounds: the len is 0 but the index is 0");
+        assert!(report.location.unwrap().starts_with("src/generics.rs:"));
+
+        assert_eq!(catch("fine", || Guess::new(50).value()), Ok(50));
+    }
+
+    #[test]
+    fn the_runner_carries_on_and_logs_each_crash() {
+        let dir = std::env::temp_dir().join(format!("panic_boundary_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut runner = Runner::new(&dir);
+        assert!(!runner.run("first", || panic!("first lesson broke")));
+        assert!(runner.run("second", || {}));
+        assert!(!runner.run("first", || {
+            Guess::new(0);
+        }));
+
+        assert_eq!(runner.ran(), 3);
+        let messages: Vec<_> = runner.crashes().iter().map(|r| r.message.as_str()).collect();
+        assert_eq!(messages, ["first lesson broke", "Guess value must be between 1 and 100, got 0."]);
+
+        // Both crashes of `first` get their own file, even within the same second.
+        let logs = runner.logs();
+        assert_eq!(logs.len(), 2);
+        assert_ne!(logs[0], logs[1]);
+        let log = fs::read_to_string(logs[1]).unwrap();
+        assert!(log.starts_with("lesson: first\nmessage: Guess value must be between 1 and 100, got 0.\n"));
+        assert!(log.contains("\nbacktrace:\n"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -14,10 +14,15 @@
 // The RefCell<T> type is useful when you’re sure your code follows the borrowing rules but the
 // compiler is unable to understand and guarantee that.
 
+pub mod quota_store;
+
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use crate::smart_pointers3::List::{Cons, Nil};
+use crate::smart_pointers3::quota_store::QuotaStore;
 
+#[allow(dead_code)] // the fields are only ever read through the Debug output in run()
 #[derive(Debug)]
 enum List {
     Cons(Rc<RefCell<i32>>, Rc<List>),
@@ -44,16 +49,71 @@ pub fn run(){
     println!("b after = {:?}", b);
     println!("c after = {:?}", c);
 
+    // The quota and its history are reloaded from the last run, so usage keeps adding up.
+    let store = QuotaStore::new("quota.txt");
+    let messenger = PrintMessenger;
+    let mut tracker = match store.load() {
+        Ok(Some(state)) => LimitTracker::from_state(&messenger, state),
+        Ok(None) => LimitTracker::new(&messenger, 100),
+        Err(e) => {
+            println!("couldn't load {}, starting over: {}", store.path().display(), e);
+            LimitTracker::new(&messenger, 100)
+        }
+    };
+
+    tracker.set_value(tracker.value() + 10);
+    println!("used {} of {} after {} updates", tracker.value(), tracker.max(), tracker.history().len());
+
+    if let Err(e) = store.save(&tracker.state()) {
+        println!("couldn't save {}: {}", store.path().display(), e);
+    }
 }
 
 pub trait Messenger {
     fn send(&self, msg: &str);
 }
 
+// Sends messages to the terminal, for run().
+struct PrintMessenger;
+
+impl Messenger for PrintMessenger {
+    fn send(&self, msg: &str) {
+        println!("{}", msg);
+    }
+}
+
+// The alert levels a LimitTracker can raise, ordered from least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Warning,
+    Urgent,
+    Error,
+}
+
+impl Severity {
+    pub fn message(&self) -> &'static str {
+        match self {
+            Severity::Warning => "Warning: You've used up over 75% of your quota!",
+            Severity::Urgent => "Urgent warning: You've used up over 90% of your quota!",
+            Severity::Error => "Error: You are over your quota!",
+        }
+    }
+}
+
+// One call to set_value: when it happened (seconds since the Unix epoch), the value that was set
+// and the alert it fired, if any.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageRecord {
+    pub timestamp: u64,
+    pub value: usize,
+    pub alert: Option<Severity>,
+}
+
 pub struct LimitTracker<'a, T: Messenger>{
     messenger: &'a T,
     value: usize,
-    max: usize
+    max: usize,
+    history: Vec<UsageRecord>,
 }
 
 impl<'a, T> LimitTracker<'a, T> where T: Messenger,{
@@ -62,22 +122,68 @@ impl<'a, T> LimitTracker<'a, T> where T: Messenger,{
             messenger,
             value: 0,
             max,
+            history: vec![],
+        }
+    }
+
+    // Picks up where a previous run left off, see quota_store::QuotaStore::load.
+    pub fn from_state(messenger: &'a T, state: quota_store::QuotaState) -> LimitTracker<'a, T> {
+        LimitTracker {
+            messenger,
+            value: state.value,
+            max: state.max,
+            history: state.history,
         }
     }
 
+    pub fn value(&self) -> usize {
+        self.value
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    pub fn history(&self) -> &[UsageRecord] {
+        &self.history
+    }
+
     pub fn set_value(&mut self, value: usize) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        self.set_value_at(value, now);
+    }
+
+    pub fn set_value_at(&mut self, value: usize, timestamp: u64) {
         self.value = value;
 
         let percentage_of_max = self.value as f64 / self.max as f64;
 
-        if percentage_of_max >= 1.0 {
-            self.messenger.send("Error: You are over your quota!");
+        let alert = if percentage_of_max >= 1.0 {
+            Some(Severity::Error)
         } else if percentage_of_max >= 0.9 {
-            self.messenger
-                .send("Urgent warning: You've used up over 90% of your quota!");
+            Some(Severity::Urgent)
         } else if percentage_of_max >= 0.75 {
-            self.messenger
-                .send("Warning: You've used up over 75% of your quota!");
+            Some(Severity::Warning)
+        } else {
+            None
+        };
+
+        if let Some(severity) = alert {
+            self.messenger.send(severity.message());
+        }
+
+        self.history.push(UsageRecord { timestamp, value, alert });
+    }
+
+    pub fn state(&self) -> quota_store::QuotaState {
+        quota_store::QuotaState {
+            value: self.value,
+            max: self.max,
+            history: self.history.clone(),
         }
     }
 }
@@ -133,12 +239,13 @@ mod tests{
     use std::cell::RefCell;
     use super::*;
 
-    struct MockMessenger{
-        sent_messages: RefCell<Vec<String>>
+    // Also used by the quota_store tests.
+    pub(super) struct MockMessenger{
+        pub(super) sent_messages: RefCell<Vec<String>>
     }
 
     impl MockMessenger{
-        fn new() -> MockMessenger{
+        pub(super) fn new() -> MockMessenger{
             MockMessenger{
                 sent_messages: RefCell::new(vec![])
             }
@@ -0,0 +1,246 @@
+// Keeps a LimitTracker's quota and usage history on disk so they survive a restart.
+//
+// The file is plain text, one entry per line:
+//
+//     max 100
+//     value 95
+//     record 1700000000 80 warning
+//     record 1700000100 95 urgent
+//
+// Writes go to a temporary file next to the real one, which is flushed to disk and then renamed
+// over it. A rename within the same directory is atomic, so a crash mid-save leaves either the
+// old file or the new one, never half of each.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use crate::smart_pointers3::{Severity, UsageRecord};
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotaState {
+    pub value: usize,
+    pub max: usize,
+    pub history: Vec<UsageRecord>,
+}
+
+pub struct QuotaStore {
+    path: PathBuf,
+}
+
+impl QuotaStore {
+    pub fn new<P: AsRef<Path>>(path: P) -> QuotaStore {
+        QuotaStore {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn save(&self, state: &QuotaState) -> io::Result<()> {
+        let mut contents = format!("max {}\nvalue {}\n", state.max, state.value);
+        for record in &state.history {
+            contents.push_str(&format!(
+                "record {} {} {}\n",
+                record.timestamp,
+                record.value,
+                severity_name(record.alert)
+            ));
+        }
+
+        let tmp_path = self.tmp_path();
+        let mut tmp = File::create(&tmp_path)?;
+        tmp.write_all(contents.as_bytes())?;
+        tmp.sync_all()?;
+
+        fs::rename(&tmp_path, &self.path)
+    }
+
+    // Returns Ok(None) when nothing has been saved yet, so the caller can start a fresh tracker.
+    pub fn load(&self) -> io::Result<Option<QuotaState>> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let mut max = None;
+        let mut value = None;
+        let mut history = vec![];
+
+        for (index, line) in contents.lines().enumerate() {
+            let line_number = index + 1;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+
+            match fields.as_slice() {
+                [] => {}
+                ["max", n] => max = Some(parse_number(n, line_number)?),
+                ["value", n] => value = Some(parse_number(n, line_number)?),
+                ["record", timestamp, n, alert] => history.push(UsageRecord {
+                    timestamp: parse_number(timestamp, line_number)?,
+                    value: parse_number(n, line_number)?,
+                    alert: parse_severity(alert, line_number)?,
+                }),
+                _ => return Err(invalid_data(line_number, &format!("unexpected entry `{}`", line))),
+            }
+        }
+
+        match (max, value) {
+            (Some(max), Some(value)) => Ok(Some(QuotaState { value, max, history })),
+            _ => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("{}: missing `max` or `value` entry", self.path.display()),
+            )),
+        }
+    }
+
+    fn tmp_path(&self) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(".tmp");
+        self.path.with_file_name(name)
+    }
+}
+
+// Highest value set on each day, keyed by days since the Unix epoch.
+pub fn peak_usage_per_day(history: &[UsageRecord]) -> BTreeMap<u64, usize> {
+    let mut peaks = BTreeMap::new();
+
+    for record in history {
+        let peak = peaks.entry(record.timestamp / SECONDS_PER_DAY).or_insert(0);
+        if record.value > *peak {
+            *peak = record.value;
+        }
+    }
+
+    peaks
+}
+
+pub fn alerts_per_severity(history: &[UsageRecord]) -> BTreeMap<Severity, usize> {
+    let mut counts = BTreeMap::new();
+
+    for severity in history.iter().filter_map(|record| record.alert) {
+        *counts.entry(severity).or_insert(0) += 1;
+    }
+
+    counts
+}
+
+fn severity_name(alert: Option<Severity>) -> &'static str {
+    match alert {
+        None => "none",
+        Some(Severity::Warning) => "warning",
+        Some(Severity::Urgent) => "urgent",
+        Some(Severity::Error) => "error",
+    }
+}
+
+fn parse_severity(name: &str, line_number: usize) -> io::Result<Option<Severity>> {
+    match name {
+        "none" => Ok(None),
+        "warning" => Ok(Some(Severity::Warning)),
+        "urgent" => Ok(Some(Severity::Urgent)),
+        "error" => Ok(Some(Severity::Error)),
+        other => Err(invalid_data(line_number, &format!("unknown severity `{}`", other))),
+    }
+}
+
+fn parse_number<N: std::str::FromStr>(text: &str, line_number: usize) -> io::Result<N> {
+    text.parse()
+        .map_err(|_| invalid_data(line_number, &format!("`{}` is not a number", text)))
+}
+
+fn invalid_data(line_number: usize, message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, format!("line {}: {}", line_number, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::smart_pointers3::LimitTracker;
+    use crate::smart_pointers3::tests::MockMessenger;
+
+    // A store in its own temporary directory, which is removed when the test is done with it.
+    struct TempStore {
+        dir: PathBuf,
+        store: QuotaStore,
+    }
+
+    impl Drop for TempStore {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn temp_store(name: &str) -> TempStore {
+        let dir = std::env::temp_dir().join(format!("quota_store_{}_{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let store = QuotaStore::new(dir.join("quota.txt"));
+        TempStore { dir, store }
+    }
+
+    #[test]
+    fn state_survives_a_restart() {
+        let temp = temp_store("restart");
+        let store = &temp.store;
+        let messenger = MockMessenger::new();
+
+        let mut tracker = LimitTracker::new(&messenger, 100);
+        tracker.set_value_at(80, 1_000);
+        tracker.set_value_at(95, 2_000);
+        store.save(&tracker.state()).unwrap();
+
+        let reloaded = LimitTracker::from_state(&messenger, store.load().unwrap().unwrap());
+
+        assert_eq!(reloaded.value(), 95);
+        assert_eq!(reloaded.max(), 100);
+        assert_eq!(reloaded.history(), tracker.history());
+        assert!(!store.tmp_path().exists());
+    }
+
+    #[test]
+    fn missing_file_loads_as_none() {
+        let temp = temp_store("missing");
+        let store = &temp.store;
+
+        assert_eq!(store.load().unwrap(), None);
+    }
+
+    #[test]
+    fn corrupt_file_reports_the_line() {
+        let temp = temp_store("corrupt");
+        let store = &temp.store;
+        fs::write(store.path(), "max 100\nvalue 5\nrecord 10 five none\n").unwrap();
+
+        let error = store.load().unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert!(error.to_string().contains("line 3"));
+    }
+
+    #[test]
+    fn reports_peaks_and_alert_counts() {
+        let messenger = MockMessenger::new();
+        let mut tracker = LimitTracker::new(&messenger, 100);
+
+        tracker.set_value_at(80, 10);
+        tracker.set_value_at(92, 20);
+        tracker.set_value_at(40, SECONDS_PER_DAY + 10);
+        tracker.set_value_at(120, SECONDS_PER_DAY + 20);
+        tracker.set_value_at(78, SECONDS_PER_DAY + 30);
+
+        let peaks = peak_usage_per_day(tracker.history());
+        assert_eq!(peaks.get(&0), Some(&92));
+        assert_eq!(peaks.get(&1), Some(&120));
+
+        let alerts = alerts_per_severity(tracker.history());
+        assert_eq!(alerts.get(&Severity::Warning), Some(&2));
+        assert_eq!(alerts.get(&Severity::Urgent), Some(&1));
+        assert_eq!(alerts.get(&Severity::Error), Some(&1));
+        assert_eq!(messenger.sent_messages.borrow().len(), 4);
+    }
+}
@@ -0,0 +1,432 @@
+// A small state-machine library pulled out of the two blog Post lessons.
+//
+// object_oriented2_state_pattern keeps the state in a Box<dyn State> and decides at runtime
+// what each method does; object_oriented3_state_pattern_rust_way makes every state a type so
+// wrong transitions don't compile. Both spell the workflow out by hand. Here a workflow is
+// declared once with `state_machine!`:
+//
+//     state_machine! {
+//         pub mod door for Lock {
+//             initial Closed;
+//             states { Open, Closed, Locked }
+//             events { Push, Pull, Turn }
+//             transitions {
+//                 Closed: Pull => Open;
+//                 Open: Push => Closed;
+//                 Closed: Turn => Locked if has_key;
+//             }
+//         }
+//     }
+//
+// `Lock` is the context: the data the machine carries along and the guards look at. `has_key`
+// is a `fn(&Lock) -> bool` in the surrounding module. Each state and event may have at most one
+// transition; a guard that says no leaves the machine where it was.
+//
+// The macro generates a module with:
+//
+// - a unit struct per state, implementing State, and an `Event` enum, for the runtime mode:
+//   `door::machine(lock)` returns a Machine holding a Box<dyn State> that `fire` replaces.
+// - the same structs as markers for the type-state mode, with a marker struct per event in
+//   `door::events`: `door::typed(lock)` returns a Typed<Closed, Lock>, and
+//   `.fire(events::Pull)` only compiles where the declaration has that transition.
+//   Guarded transitions go through `try_fire`, which hands the machine back if the guard fails.
+// - STATES and TRANSITIONS tables, and `to_dot` for a Graphviz picture of the graph.
+
+pub mod blog;
+
+use std::fmt;
+use std::marker::PhantomData;
+
+pub fn run() {
+    let mut post = blog::Post::new();
+    post.add_text("I ate a salad for lunch today");
+
+    if let Err(e) = post.approve("bob") {
+        println!("{}", e);
+    }
+
+    post.request_review().unwrap();
+    post.approve("bob").unwrap();
+    post.approve("carol").unwrap();
+    println!("{}: {}", post.state(), post.content());
+
+    print!("{}", blog::workflow::to_dot());
+}
+
+pub trait EventName {
+    fn name(&self) -> &'static str;
+}
+
+/// A state in the runtime mode. `next` says where `event` leads from here.
+pub trait State<E, C> {
+    fn name(&self) -> &'static str;
+    fn next(&self, event: E, context: &C) -> Result<Box<dyn State<E, C>>, TransitionError>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransitionError {
+    /// The state has no transition for the event.
+    NotAllowed {
+        state: &'static str,
+        event: &'static str,
+    },
+    /// There is a transition, but its guard returned false.
+    GuardFailed {
+        state: &'static str,
+        event: &'static str,
+        guard: &'static str,
+    },
+}
+
+impl fmt::Display for TransitionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransitionError::NotAllowed { state, event } => {
+                write!(f, "{} is not allowed in state {}", event, state)
+            }
+            TransitionError::GuardFailed { state, event, guard } => {
+                write!(f, "{} from state {} was refused by {}", event, state, guard)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TransitionError {}
+
+pub struct Machine<E, C> {
+    state: Box<dyn State<E, C>>,
+    context: C,
+}
+
+impl<E: EventName, C> Machine<E, C> {
+    pub fn new(initial: Box<dyn State<E, C>>, context: C) -> Machine<E, C> {
+        Machine {
+            state: initial,
+            context,
+        }
+    }
+
+    pub fn state(&self) -> &'static str {
+        self.state.name()
+    }
+
+    pub fn context(&self) -> &C {
+        &self.context
+    }
+
+    pub fn context_mut(&mut self) -> &mut C {
+        &mut self.context
+    }
+
+    /// Moves to the next state and returns its name. On error the state doesn't change.
+    pub fn fire(&mut self, event: E) -> Result<&'static str, TransitionError> {
+        self.state = self.state.next(event, &self.context)?;
+        Ok(self.state.name())
+    }
+}
+
+/// Implemented for the initial state, which is the only one a Typed machine can start in.
+pub trait Initial {}
+
+/// An unguarded transition from `Self` on event `E`.
+pub trait Transition<E> {
+    type To;
+}
+
+/// A transition from `Self` on event `E` that only happens if `allowed` says so.
+pub trait GuardedTransition<E, C> {
+    type To;
+    const GUARD: &'static str;
+    fn allowed(context: &C) -> bool;
+}
+
+/// A machine in the type-state mode: the state is part of the type.
+pub struct Typed<S, C> {
+    pub context: C,
+    state: PhantomData<S>,
+}
+
+impl<S: Initial, C> Typed<S, C> {
+    pub fn new(context: C) -> Typed<S, C> {
+        Typed {
+            context,
+            state: PhantomData,
+        }
+    }
+}
+
+impl<S, C> Typed<S, C> {
+    pub fn fire<E>(self, _event: E) -> Typed<S::To, C>
+    where
+        S: Transition<E>,
+    {
+        Typed {
+            context: self.context,
+            state: PhantomData,
+        }
+    }
+
+    pub fn try_fire<E>(self, _event: E) -> Result<Typed<S::To, C>, Typed<S, C>>
+    where
+        S: GuardedTransition<E, C>,
+    {
+        if !S::allowed(&self.context) {
+            return Err(self);
+        }
+
+        Ok(Typed {
+            context: self.context,
+            state: PhantomData,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransitionInfo {
+    pub from: &'static str,
+    pub event: &'static str,
+    pub to: &'static str,
+    pub guard: Option<&'static str>,
+}
+
+/// The transition graph in Graphviz DOT, with guards in square brackets after the event.
+/// `dot -Tsvg` turns it into a picture.
+pub fn to_dot(name: &str, initial: &str, states: &[&str], transitions: &[TransitionInfo]) -> String {
+    let mut dot = format!("digraph {} {{\n    rankdir=LR;\n    start [shape=point];\n", name);
+
+    for state in states {
+        dot += &format!("    {} [shape=box];\n", state);
+    }
+    dot += &format!("    start -> {};\n", initial);
+
+    for t in transitions {
+        let label = match t.guard {
+            Some(guard) => format!("{} [{}]", t.event, guard),
+            None => String::from(t.event),
+        };
+        dot += &format!("    {} -> {} [label=\"{}\"];\n", t.from, t.to, label);
+    }
+
+    dot + "}\n"
+}
+
+#[macro_export]
+macro_rules! state_machine {
+    (
+        $vis:vis mod $name:ident for $context:ty {
+            initial $initial:ident;
+            states { $($state:ident),+ $(,)? }
+            events { $($event:ident),+ $(,)? }
+            transitions {
+                $($from:ident : $on:ident => $to:ident $(if $guard:ident)?;)+
+            }
+        }
+    ) => {
+        $vis mod $name {
+            #![allow(dead_code)]
+            use super::*;
+            use $crate::state_machine as sm;
+
+            #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+            pub enum Event {
+                $($event),+
+            }
+
+            impl sm::EventName for Event {
+                fn name(&self) -> &'static str {
+                    match self {
+                        $(Event::$event => stringify!($event)),+
+                    }
+                }
+            }
+
+            pub mod events {
+                $(
+                    #[derive(Debug, Clone, Copy)]
+                    pub struct $event;
+                )+
+            }
+
+            $(
+                #[derive(Debug, Clone, Copy, Default)]
+                pub struct $state;
+
+                impl sm::State<Event, $context> for $state {
+                    fn name(&self) -> &'static str {
+                        stringify!($state)
+                    }
+
+                    fn next(
+                        &self,
+                        event: Event,
+                        context: &$context,
+                    ) -> Result<Box<dyn sm::State<Event, $context>>, sm::TransitionError> {
+                        step(stringify!($state), event, context)
+                    }
+                }
+            )+
+
+            impl sm::Initial for $initial {}
+
+            $($crate::state_machine!(@typed $context, $from, $on, $to $(, $guard)?);)+
+
+            pub const STATES: &[&str] = &[$(stringify!($state)),+];
+
+            pub const TRANSITIONS: &[sm::TransitionInfo] = &[
+                $(sm::TransitionInfo {
+                    from: stringify!($from),
+                    event: stringify!($on),
+                    to: stringify!($to),
+                    guard: $crate::state_machine!(@guard_name $($guard)?),
+                }),+
+            ];
+
+            fn step(
+                from: &'static str,
+                event: Event,
+                context: &$context,
+            ) -> Result<Box<dyn sm::State<Event, $context>>, sm::TransitionError> {
+                $(
+                    if from == stringify!($from) && event == Event::$on {
+                        $(
+                            if !$guard(context) {
+                                return Err(sm::TransitionError::GuardFailed {
+                                    state: from,
+                                    event: stringify!($on),
+                                    guard: stringify!($guard),
+                                });
+                            }
+                        )?
+                        return Ok(Box::new($to));
+                    }
+                )+
+
+                let _ = context;
+                Err(sm::TransitionError::NotAllowed {
+                    state: from,
+                    event: sm::EventName::name(&event),
+                })
+            }
+
+            pub fn machine(context: $context) -> sm::Machine<Event, $context> {
+                sm::Machine::new(Box::new($initial), context)
+            }
+
+            pub fn typed(context: $context) -> sm::Typed<$initial, $context> {
+                sm::Typed::new(context)
+            }
+
+            pub fn to_dot() -> String {
+                sm::to_dot(stringify!($name), stringify!($initial), STATES, TRANSITIONS)
+            }
+        }
+    };
+
+    (@typed $context:ty, $from:ident, $on:ident, $to:ident) => {
+        impl sm::Transition<events::$on> for $from {
+            type To = $to;
+        }
+    };
+
+    (@typed $context:ty, $from:ident, $on:ident, $to:ident, $guard:ident) => {
+        impl sm::GuardedTransition<events::$on, $context> for $from {
+            type To = $to;
+            const GUARD: &'static str = stringify!($guard);
+
+            fn allowed(context: &$context) -> bool {
+                $guard(context)
+            }
+        }
+    };
+
+    (@guard_name) => {
+        None
+    };
+
+    (@guard_name $guard:ident) => {
+        Some(stringify!($guard))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub struct Lock {
+        has_key: bool,
+    }
+
+    fn has_key(lock: &Lock) -> bool {
+        lock.has_key
+    }
+
+    crate::state_machine! {
+        mod door for Lock {
+            initial Closed;
+            states { Open, Closed, Locked }
+            events { Push, Pull, Turn }
+            transitions {
+                Closed: Pull => Open;
+                Open: Push => Closed;
+                Closed: Turn => Locked if has_key;
+            }
+        }
+    }
+
+    #[test]
+    fn runtime_machine_follows_the_declared_transitions() {
+        let mut machine = door::machine(Lock { has_key: false });
+
+        assert_eq!(machine.fire(door::Event::Pull), Ok("Open"));
+        assert_eq!(
+            machine.fire(door::Event::Turn),
+            Err(TransitionError::NotAllowed {
+                state: "Open",
+                event: "Turn",
+            })
+        );
+        assert_eq!(machine.fire(door::Event::Push), Ok("Closed"));
+        assert_eq!(
+            machine.fire(door::Event::Turn).unwrap_err().to_string(),
+            "Turn from state Closed was refused by has_key"
+        );
+        assert_eq!(machine.state(), "Closed");
+
+        machine.context_mut().has_key = true;
+        assert_eq!(machine.fire(door::Event::Turn), Ok("Locked"));
+    }
+
+    #[test]
+    fn typed_machine_checks_guards_at_runtime() {
+        let closed = door::typed(Lock { has_key: false });
+        let closed = closed.fire(door::events::Pull).fire(door::events::Push);
+
+        let mut closed = match closed.try_fire(door::events::Turn) {
+            Ok(_) => panic!("locked without a key"),
+            Err(closed) => closed,
+        };
+        closed.context.has_key = true;
+        let locked: Typed<door::Locked, Lock> = closed.try_fire(door::events::Turn).ok().unwrap();
+        assert!(locked.context.has_key);
+    }
+
+    #[test]
+    fn dot_export_lists_states_and_labelled_edges() {
+        assert_eq!(
+            door::to_dot(),
+            "\
+digraph door {
+    rankdir=LR;
+    start [shape=point];
+    Open [shape=box];
+    Closed [shape=box];
+    Locked [shape=box];
+    start -> Closed;
+    Closed -> Open [label=\"Pull\"];
+    Open -> Closed [label=\"Push\"];
+    Closed -> Locked [label=\"Turn [has_key]\"];
+}
+"
+        );
+    }
+}
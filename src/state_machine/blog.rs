@@ -0,0 +1,167 @@
+// The blog Post from object_oriented2_state_pattern, on top of `state_machine!`. The workflow
+// is the same (two different reviewers publish a post, a rejection sends it back to Draft and
+// only drafts can be edited), but a transition that isn't allowed comes back as an error instead
+// of being ignored.
+
+use crate::state_machine::{Machine, TransitionError};
+
+pub const REQUIRED_APPROVALS: usize = 2;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PostData {
+    pub content: String,
+    pub approved_by: Vec<String>,
+}
+
+fn enough_approvals(data: &PostData) -> bool {
+    data.approved_by.len() >= REQUIRED_APPROVALS
+}
+
+crate::state_machine! {
+    pub mod workflow for PostData {
+        initial Draft;
+        states { Draft, PendingReview, Published }
+        events { RequestReview, Approve, Reject }
+        transitions {
+            Draft: RequestReview => PendingReview;
+            PendingReview: Approve => Published if enough_approvals;
+            PendingReview: Reject => Draft;
+        }
+    }
+}
+
+/// The runtime version of the workflow. The type-state version comes from the same
+/// declaration, and there approving a draft doesn't compile:
+///
+/// ```compile_fail
+/// use rust_concepts::state_machine::blog::{workflow, PostData};
+///
+/// let draft = workflow::typed(PostData::default());
+/// let published = draft.try_fire(workflow::events::Approve);
+/// ```
+pub struct Post {
+    machine: Machine<workflow::Event, PostData>,
+}
+
+impl Post {
+    pub fn new() -> Post {
+        Post {
+            machine: workflow::machine(PostData::default()),
+        }
+    }
+
+    /// Text can only be added in Draft; returns whether it was.
+    pub fn add_text(&mut self, text: &str) -> bool {
+        if self.state() != "Draft" {
+            return false;
+        }
+        self.machine.context_mut().content.push_str(text);
+        true
+    }
+
+    /// The text of a published post, and nothing before that.
+    pub fn content(&self) -> &str {
+        match self.state() {
+            "Published" => &self.machine.context().content,
+            _ => "",
+        }
+    }
+
+    pub fn state(&self) -> &'static str {
+        self.machine.state()
+    }
+
+    pub fn approvals(&self) -> usize {
+        self.machine.context().approved_by.len()
+    }
+
+    pub fn request_review(&mut self) -> Result<&'static str, TransitionError> {
+        self.machine.fire(workflow::Event::RequestReview)
+    }
+
+    /// Records the approval and publishes the post once enough reviewers have approved it.
+    /// A reviewer approving twice only counts once.
+    pub fn approve(&mut self, reviewer: &str) -> Result<&'static str, TransitionError> {
+        if self.state() != "PendingReview" {
+            return self.machine.fire(workflow::Event::Approve);
+        }
+
+        let approved_by = &mut self.machine.context_mut().approved_by;
+        if !approved_by.iter().any(|r| r == reviewer) {
+            approved_by.push(String::from(reviewer));
+        }
+
+        match self.machine.fire(workflow::Event::Approve) {
+            Err(TransitionError::GuardFailed { state, .. }) => Ok(state),
+            result => result,
+        }
+    }
+
+    pub fn reject(&mut self) -> Result<&'static str, TransitionError> {
+        let state = self.machine.fire(workflow::Event::Reject)?;
+        self.machine.context_mut().approved_by.clear();
+        Ok(state)
+    }
+}
+
+impl Default for Post {
+    fn default() -> Post {
+        Post::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state_machine::Typed;
+
+    #[test]
+    fn post_follows_the_review_workflow() {
+        let mut post = Post::new();
+        assert!(post.add_text("salad"));
+
+        assert_eq!(post.request_review(), Ok("PendingReview"));
+        assert!(!post.add_text("more"));
+        assert_eq!(post.approve("bob"), Ok("PendingReview"));
+        assert_eq!(post.approve("bob"), Ok("PendingReview"));
+        assert_eq!(post.content(), "");
+
+        assert_eq!(post.approve("carol"), Ok("Published"));
+        assert_eq!(post.content(), "salad");
+    }
+
+    #[test]
+    fn illegal_transitions_are_errors() {
+        let mut post = Post::new();
+
+        assert_eq!(
+            post.approve("bob"),
+            Err(TransitionError::NotAllowed {
+                state: "Draft",
+                event: "Approve",
+            })
+        );
+        assert!(post.reject().is_err());
+
+        post.request_review().unwrap();
+        post.approve("bob").unwrap();
+        assert_eq!(post.reject(), Ok("Draft"));
+        assert_eq!(post.approvals(), 0);
+    }
+
+    #[test]
+    fn typed_post_needs_two_approvals() {
+        let mut pending = workflow::typed(PostData::default()).fire(workflow::events::RequestReview);
+        pending.context.approved_by.push(String::from("bob"));
+
+        let mut pending = match pending.try_fire(workflow::events::Approve) {
+            Ok(_) => panic!("published with one approval"),
+            Err(pending) => pending,
+        };
+        pending.context.approved_by.push(String::from("carol"));
+
+        let published: Typed<workflow::Published, PostData> =
+            pending.try_fire(workflow::events::Approve).ok().unwrap();
+        assert_eq!(published.context.approved_by.len(), 2);
+    }
+}
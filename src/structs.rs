@@ -1,9 +1,10 @@
-#[derive(Debug)]
-struct User{
-    active: bool,
-    username: String,
-    email: String,
-    sign_in_count: u64
+// error_handling::profile reads and writes Users from profile files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct User{
+    pub active: bool,
+    pub username: String,
+    pub email: String,
+    pub sign_in_count: u64
 }
 
 // Tuple Struct without named fields
@@ -34,12 +35,14 @@ pub fn run(){
     //--------------------------------------------------------------------
     let black = Color(0, 0, 0);
     let origin = Point(0, 0, 0);
+    println!("black is ({}, {}, {}), origin is ({}, {}, {})", black.0, black.1, black.2, origin.0, origin.1, origin.2);
 
     // --------------------------------------------------------------------
     let subject = AlwaysEqual;
+    println!("a unit-like struct takes {} bytes", std::mem::size_of_val(&subject));
 }
 
-fn build_user(email: String, username: String) -> User {
+pub fn build_user(email: String, username: String) -> User {
     User{
         email,
         username,
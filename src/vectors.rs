@@ -1,14 +1,16 @@
-use std::ptr::null;
+pub mod spreadsheet;
 
-#[derive(Debug)]
-enum SpreadsheetCell{
+use spreadsheet::Sheet;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpreadsheetCell{
     Int(i32),
     Float(f64),
     Text(String)
 }
 
 pub fn run(){
-    let v: Vec<i32> = Vec::new();
+    let _v: Vec<i32> = Vec::new();
     let mut vector_values = vec![1, 2, 3];
 
     vector_values.push(4);
@@ -58,4 +60,16 @@ pub fn run(){
     ];
 
     println!("{:?}", row);
-}
\ No newline at end of file
+
+    // The same cells in a sheet, with a formula over them
+    let mut sheet = Sheet::new();
+    for (at, cell) in ["A1", "B1", "C1"].into_iter().zip(row) {
+        sheet.set_value(at, cell).unwrap();
+    }
+    sheet.set("D1", "=A1 * C1 & \" \" & B1").unwrap();
+    println!("D1 = {}", sheet.display("D1"));
+
+    if let Err(e) = sheet.set("A1", "=D1") {
+        println!("{}", e);
+    }
+}
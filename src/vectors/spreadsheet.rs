@@ -0,0 +1,993 @@
+// A spreadsheet made of SpreadsheetCells. Cells are addressed like `A1` (column letters, then the
+// row counting from 1) and hold either a value or a formula:
+//
+//     A1: 3          Int
+//     A2: 2.5        Float
+//     A3: blue       Text
+//     B1: =SUM(A1:A2) * 2 & " units"
+//
+// Formulas have numbers, "text", cell references, ranges (only as function arguments), `+ - * /`,
+// unary minus, `&` to join text, parentheses, and the functions SUM, MIN, MAX and AVERAGE. A
+// formula nested more than MAX_DEPTH levels deep is a Syntax error.
+//
+// Every formula's references are its dependencies. Setting a cell recalculates it and then every
+// cell that depends on it, directly or not, in dependency order. A formula that would depend on
+// itself is refused with a Cycle error and the sheet is left as it was.
+//
+// Values are coerced between the three variants by these rules:
+//
+// - Arithmetic on two Ints gives an Int (an overflow is an error), except for a division that
+//   doesn't come out even, which gives a Float. Anything with a Float gives a Float.
+// - Text used as a number is parsed as one ("42" is Int(42), "1.5" is Float(1.5)); text that
+//   isn't a number is a Type error.
+// - `&` turns both sides into text.
+// - An empty cell is Int(0) in arithmetic and "" in text.
+// - Functions skip text and empty cells in ranges, but coerce text passed to them directly.
+//
+// A cell whose formula fails holds the error, and cells that use it get the same error.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+
+use super::SpreadsheetCell;
+
+// The most cells a range may cover.
+const MAX_RANGE: usize = 10_000;
+// How deep a formula may nest, both as written (parentheses, unary minuses and function calls) and
+// as the tree it's parsed into, which is how deep it's evaluated: every operator is a level above
+// its operands, so `1 + 2 + 3` is three levels deep and `(1 + 2) * (3 + 4)` is three.
+const MAX_DEPTH: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CellRef {
+    /// Counting from 0, so A1 is row 0, column 0.
+    pub row: u32,
+    pub column: u32,
+}
+
+impl CellRef {
+    pub fn parse(reference: &str) -> Result<CellRef, SheetError> {
+        let invalid = || SheetError::InvalidReference(String::from(reference));
+
+        let letters = reference.chars().take_while(|c| c.is_ascii_alphabetic()).count();
+        let (column, row) = reference.split_at(letters);
+        if letters == 0 || letters > 3 || row.is_empty() || !row.chars().all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+
+        let column = column
+            .chars()
+            .fold(0, |n, c| n * 26 + (c.to_ascii_uppercase() as u32 - 'A' as u32 + 1));
+        let row: u32 = row.parse().map_err(|_| invalid())?;
+        if row == 0 {
+            return Err(invalid());
+        }
+        Ok(CellRef {
+            row: row - 1,
+            column: column - 1,
+        })
+    }
+}
+
+impl fmt::Display for CellRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut letters = vec![];
+        let mut n = self.column + 1;
+        while n > 0 {
+            letters.push((b'A' + ((n - 1) % 26) as u8) as char);
+            n = (n - 1) / 26;
+        }
+        let column: String = letters.iter().rev().collect();
+        write!(f, "{}{}", column, self.row + 1)
+    }
+}
+
+impl fmt::Display for SpreadsheetCell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpreadsheetCell::Int(n) => write!(f, "{}", n),
+            SpreadsheetCell::Float(x) => write!(f, "{}", x),
+            SpreadsheetCell::Text(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SheetError {
+    InvalidReference(String),
+    /// The formula in `cell` can't be parsed; `column` counts characters from 1, after the `=`.
+    Syntax {
+        cell: CellRef,
+        column: usize,
+        message: String,
+    },
+    /// The cells that would have formed a loop, starting and ending with the same one.
+    Cycle(Vec<CellRef>),
+    DivisionByZero { cell: CellRef },
+    Overflow { cell: CellRef },
+    /// A value of the wrong kind, such as text that isn't a number in arithmetic.
+    Type { cell: CellRef, message: String },
+}
+
+impl SheetError {
+    /// How a cell holding this error is shown, as spreadsheets do.
+    pub fn code(&self) -> &'static str {
+        match self {
+            SheetError::InvalidReference(_) => "#REF!",
+            SheetError::Syntax { .. } => "#ERROR!",
+            SheetError::Cycle(_) => "#CYCLE!",
+            SheetError::DivisionByZero { .. } => "#DIV/0!",
+            SheetError::Overflow { .. } => "#NUM!",
+            SheetError::Type { .. } => "#VALUE!",
+        }
+    }
+}
+
+impl fmt::Display for SheetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SheetError::InvalidReference(r) => write!(f, "`{}` isn't a cell reference", r),
+            SheetError::Syntax { cell, column, message } => {
+                write!(f, "{}: column {}: {}", cell, column, message)
+            }
+            SheetError::Cycle(cells) => {
+                let path: Vec<String> = cells.iter().map(|c| c.to_string()).collect();
+                write!(f, "circular reference: {}", path.join(" -> "))
+            }
+            SheetError::DivisionByZero { cell } => write!(f, "{}: division by zero", cell),
+            SheetError::Overflow { cell } => write!(f, "{}: the result doesn't fit in an Int", cell),
+            SheetError::Type { cell, message } => write!(f, "{}: {}", cell, message),
+        }
+    }
+}
+
+impl std::error::Error for SheetError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Function {
+    Sum,
+    Min,
+    Max,
+    Average,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Value(SpreadsheetCell),
+    Ref(CellRef),
+    Negate(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Arg>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Arg {
+    Expr(Expr),
+    Range(CellRef, CellRef),
+}
+
+impl Expr {
+    fn references(&self, out: &mut BTreeSet<CellRef>) {
+        match self {
+            Expr::Value(_) => {}
+            Expr::Ref(r) => {
+                out.insert(*r);
+            }
+            Expr::Negate(e) => e.references(out),
+            Expr::Binary(_, a, b) => {
+                a.references(out);
+                b.references(out);
+            }
+            Expr::Call(_, args) => {
+                for arg in args {
+                    match arg {
+                        Arg::Expr(e) => e.references(out),
+                        Arg::Range(from, to) => out.extend(range(*from, *to)),
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn range(from: CellRef, to: CellRef) -> impl Iterator<Item = CellRef> {
+    let rows = from.row.min(to.row)..=from.row.max(to.row);
+    let columns = from.column.min(to.column)..=from.column.max(to.column);
+    rows.flat_map(move |row| columns.clone().map(move |column| CellRef { row, column }))
+}
+
+fn range_size(from: CellRef, to: CellRef) -> usize {
+    (from.row.abs_diff(to.row) as usize + 1) * (from.column.abs_diff(to.column) as usize + 1)
+}
+
+// ---- parsing ----
+
+struct Parser<'a> {
+    cell: CellRef,
+    chars: Vec<char>,
+    position: usize,
+    nesting: usize,
+    source: &'a str,
+}
+
+// An expression and how deep its tree is; a value or a reference is 1 deep.
+type Parsed = Result<(Expr, usize), SheetError>;
+
+impl Parser<'_> {
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, SheetError> {
+        Err(SheetError::Syntax {
+            cell: self.cell,
+            column: self.position + 1,
+            message: message.into(),
+        })
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.chars.get(self.position).is_some_and(|c| c.is_whitespace()) {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_spaces();
+        self.chars.get(self.position).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn too_deep<T>(&self) -> Result<T, SheetError> {
+        self.error(format!("the formula is nested more than {} levels deep", MAX_DEPTH))
+    }
+
+    // The depth of a node whose deepest child is `below`.
+    fn node(&self, below: usize) -> Result<usize, SheetError> {
+        if below >= MAX_DEPTH {
+            return self.too_deep();
+        }
+        Ok(below + 1)
+    }
+
+    fn parse(mut self) -> Result<Expr, SheetError> {
+        if self.source.trim().is_empty() {
+            return self.error("the formula is empty");
+        }
+        let (expr, _) = self.concatenation()?;
+        match self.peek() {
+            None => Ok(expr),
+            Some(c) => self.error(format!("unexpected `{}`", c)),
+        }
+    }
+
+    fn concatenation(&mut self) -> Parsed {
+        let (mut expr, mut depth) = self.additive()?;
+        while self.eat('&') {
+            let (right, right_depth) = self.additive()?;
+            depth = self.node(depth.max(right_depth))?;
+            expr = Expr::Binary('&', Box::new(expr), Box::new(right));
+        }
+        Ok((expr, depth))
+    }
+
+    fn additive(&mut self) -> Parsed {
+        let (mut expr, mut depth) = self.term()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.position += 1;
+            let (right, right_depth) = self.term()?;
+            depth = self.node(depth.max(right_depth))?;
+            expr = Expr::Binary(op, Box::new(expr), Box::new(right));
+        }
+        Ok((expr, depth))
+    }
+
+    fn term(&mut self) -> Parsed {
+        let (mut expr, mut depth) = self.unary()?;
+        while let Some(op @ ('*' | '/')) = self.peek() {
+            self.position += 1;
+            let (right, right_depth) = self.unary()?;
+            depth = self.node(depth.max(right_depth))?;
+            expr = Expr::Binary(op, Box::new(expr), Box::new(right));
+        }
+        Ok((expr, depth))
+    }
+
+    // Parentheses and function arguments are parsed through here too, so this is where the
+    // nesting of the formula as written is kept.
+    fn unary(&mut self) -> Parsed {
+        if self.nesting == MAX_DEPTH {
+            return self.too_deep();
+        }
+        self.nesting += 1;
+        let parsed = if self.eat('-') {
+            let (expr, depth) = self.unary()?;
+            (Expr::Negate(Box::new(expr)), self.node(depth)?)
+        } else {
+            self.primary()?
+        };
+        self.nesting -= 1;
+        Ok(parsed)
+    }
+
+    fn primary(&mut self) -> Parsed {
+        let expr = match self.peek() {
+            None => return self.error("expected a value"),
+            Some('(') => {
+                self.position += 1;
+                let parsed = self.concatenation()?;
+                if !self.eat(')') {
+                    return self.error("expected `)`");
+                }
+                return Ok(parsed);
+            }
+            Some('"') => self.string()?,
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number()?,
+            Some(c) if c.is_ascii_alphabetic() => {
+                let start = self.position;
+                let word = self.word();
+                if self.peek() == Some('(') {
+                    return self.call(start, &word);
+                }
+                self.reference(start, &word)?
+            }
+            Some(c) => return self.error(format!("unexpected `{}`", c)),
+        };
+        Ok((expr, 1))
+    }
+
+    fn word(&mut self) -> String {
+        let start = self.position;
+        while self.chars.get(self.position).is_some_and(|c| c.is_ascii_alphanumeric()) {
+            self.position += 1;
+        }
+        self.chars[start..self.position].iter().collect()
+    }
+
+    fn reference(&mut self, start: usize, word: &str) -> Result<Expr, SheetError> {
+        match CellRef::parse(word) {
+            Ok(r) => Ok(Expr::Ref(r)),
+            Err(_) => {
+                self.position = start;
+                self.error(format!("`{}` isn't a cell reference", word))
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Expr, SheetError> {
+        let start = self.position;
+        while self.chars.get(self.position).is_some_and(|c| c.is_ascii_digit() || *c == '.') {
+            self.position += 1;
+        }
+        let text: String = self.chars[start..self.position].iter().collect();
+        match parse_number(&text) {
+            Some(value) => Ok(Expr::Value(value)),
+            None => {
+                self.position = start;
+                self.error(format!("`{}` isn't a number", text))
+            }
+        }
+    }
+
+    // "text", with "" standing for a quote.
+    fn string(&mut self) -> Result<Expr, SheetError> {
+        let start = self.position;
+        self.position += 1;
+        let mut text = String::new();
+        loop {
+            match self.chars.get(self.position) {
+                None => {
+                    self.position = start;
+                    return self.error("this text is never closed");
+                }
+                Some('"') if self.chars.get(self.position + 1) == Some(&'"') => {
+                    text.push('"');
+                    self.position += 2;
+                }
+                Some('"') => {
+                    self.position += 1;
+                    return Ok(Expr::Value(SpreadsheetCell::Text(text)));
+                }
+                Some(c) => {
+                    text.push(*c);
+                    self.position += 1;
+                }
+            }
+        }
+    }
+
+    fn call(&mut self, start: usize, name: &str) -> Parsed {
+        let function = match name.to_ascii_uppercase().as_str() {
+            "SUM" => Function::Sum,
+            "MIN" => Function::Min,
+            "MAX" => Function::Max,
+            "AVERAGE" => Function::Average,
+            _ => {
+                self.position = start;
+                return self.error(format!("unknown function `{}`", name));
+            }
+        };
+        self.eat('(');
+
+        let mut args = vec![];
+        let mut depth = 0;
+        if !self.eat(')') {
+            loop {
+                let (arg, arg_depth) = self.argument()?;
+                args.push(arg);
+                depth = depth.max(arg_depth);
+                if self.eat(')') {
+                    break;
+                }
+                if !self.eat(',') {
+                    return self.error("expected `,` or `)`");
+                }
+            }
+        }
+        Ok((Expr::Call(function, args), self.node(depth)?))
+    }
+
+    fn argument(&mut self) -> Result<(Arg, usize), SheetError> {
+        let (expr, depth) = self.concatenation()?;
+        let Expr::Ref(from) = expr else {
+            return Ok((Arg::Expr(expr), depth));
+        };
+        if !self.eat(':') {
+            return Ok((Arg::Expr(expr), depth));
+        }
+
+        self.skip_spaces();
+        let start = self.position;
+        let word = self.word();
+        let Ok(to) = CellRef::parse(&word) else {
+            self.position = start;
+            return self.error("expected the cell at the end of the range");
+        };
+        if range_size(from, to) > MAX_RANGE {
+            self.position = start;
+            return self.error(format!("a range can cover at most {} cells", MAX_RANGE));
+        }
+        Ok((Arg::Range(from, to), 1))
+    }
+}
+
+fn parse_number(text: &str) -> Option<SpreadsheetCell> {
+    let text = text.trim();
+    if let Ok(n) = text.parse::<i32>() {
+        return Some(SpreadsheetCell::Int(n));
+    }
+    match text.parse::<f64>() {
+        Ok(x) if x.is_finite() && !text.contains(|c: char| c.is_alphabetic()) => {
+            Some(SpreadsheetCell::Float(x))
+        }
+        _ => None,
+    }
+}
+
+// ---- evaluation ----
+
+#[derive(Debug, Clone, Copy)]
+enum Number {
+    Int(i32),
+    Float(f64),
+}
+
+impl Number {
+    fn as_f64(self) -> f64 {
+        match self {
+            Number::Int(n) => n as f64,
+            Number::Float(x) => x,
+        }
+    }
+
+    fn into_cell(self) -> SpreadsheetCell {
+        match self {
+            Number::Int(n) => SpreadsheetCell::Int(n),
+            Number::Float(x) => SpreadsheetCell::Float(x),
+        }
+    }
+}
+
+type Value = Result<SpreadsheetCell, SheetError>;
+
+struct Evaluation<'a> {
+    cell: CellRef,
+    values: &'a BTreeMap<CellRef, Value>,
+}
+
+impl Evaluation<'_> {
+    // The value of another cell; None if it's empty.
+    fn lookup(&self, r: CellRef) -> Result<Option<SpreadsheetCell>, SheetError> {
+        match self.values.get(&r) {
+            Some(Ok(value)) => Ok(Some(value.clone())),
+            Some(Err(e)) => Err(e.clone()),
+            None => Ok(None),
+        }
+    }
+
+    fn eval(&self, expr: &Expr) -> Value {
+        match expr {
+            Expr::Value(v) => Ok(v.clone()),
+            Expr::Ref(r) => Ok(self.lookup(*r)?.unwrap_or(SpreadsheetCell::Int(0))),
+            Expr::Negate(e) => {
+                let n = self.number(&self.eval(e)?)?;
+                self.checked(n, Number::Int(-1), '*').map(Number::into_cell)
+            }
+            Expr::Binary('&', a, b) => {
+                let text = format!("{}{}", self.eval(a)?, self.eval(b)?);
+                Ok(SpreadsheetCell::Text(text))
+            }
+            Expr::Binary(op, a, b) => {
+                let a = self.number(&self.eval(a)?)?;
+                let b = self.number(&self.eval(b)?)?;
+                self.checked(a, b, *op).map(Number::into_cell)
+            }
+            Expr::Call(function, args) => self.call(*function, args),
+        }
+    }
+
+    fn number(&self, value: &SpreadsheetCell) -> Result<Number, SheetError> {
+        match value {
+            SpreadsheetCell::Int(n) => Ok(Number::Int(*n)),
+            SpreadsheetCell::Float(x) => Ok(Number::Float(*x)),
+            SpreadsheetCell::Text(s) => match parse_number(s) {
+                Some(SpreadsheetCell::Int(n)) => Ok(Number::Int(n)),
+                Some(SpreadsheetCell::Float(x)) => Ok(Number::Float(x)),
+                _ => Err(SheetError::Type {
+                    cell: self.cell,
+                    message: format!("\"{}\" isn't a number", s),
+                }),
+            },
+        }
+    }
+
+    fn checked(&self, a: Number, b: Number, op: char) -> Result<Number, SheetError> {
+        let overflow = SheetError::Overflow { cell: self.cell };
+        let result = match (a, b) {
+            (Number::Int(_), Number::Int(0)) if op == '/' => {
+                return Err(SheetError::DivisionByZero { cell: self.cell })
+            }
+            // i32::MIN / -1 is the one division that overflows.
+            (Number::Int(x), Number::Int(y)) if op == '/' => match x.checked_rem(y) {
+                None => return Err(overflow),
+                Some(0) => Number::Int(x / y),
+                Some(_) => Number::Float(x as f64 / y as f64),
+            },
+            (Number::Int(x), Number::Int(y)) => Number::Int(
+                match op {
+                    '+' => x.checked_add(y),
+                    '-' => x.checked_sub(y),
+                    _ => x.checked_mul(y),
+                }
+                .ok_or(overflow.clone())?,
+            ),
+            (a, b) => {
+                let (x, y) = (a.as_f64(), b.as_f64());
+                if op == '/' && y == 0.0 {
+                    return Err(SheetError::DivisionByZero { cell: self.cell });
+                }
+                Number::Float(match op {
+                    '+' => x + y,
+                    '-' => x - y,
+                    '*' => x * y,
+                    _ => x / y,
+                })
+            }
+        };
+        match result {
+            Number::Float(x) if !x.is_finite() => Err(overflow),
+            result => Ok(result),
+        }
+    }
+
+    fn call(&self, function: Function, args: &[Arg]) -> Value {
+        let mut numbers = vec![];
+        for arg in args {
+            match arg {
+                Arg::Expr(e) => numbers.push(self.number(&self.eval(e)?)?),
+                Arg::Range(from, to) => {
+                    for r in range(*from, *to) {
+                        match self.lookup(r)? {
+                            Some(SpreadsheetCell::Int(n)) => numbers.push(Number::Int(n)),
+                            Some(SpreadsheetCell::Float(x)) => numbers.push(Number::Float(x)),
+                            Some(SpreadsheetCell::Text(_)) | None => {}
+                        }
+                    }
+                }
+            }
+        }
+
+        let sum = || {
+            numbers
+                .iter()
+                .try_fold(Number::Int(0), |total, n| self.checked(total, *n, '+'))
+        };
+        let pick = |better: fn(f64, f64) -> bool| {
+            numbers
+                .iter()
+                .copied()
+                .reduce(|a, b| if better(b.as_f64(), a.as_f64()) { b } else { a })
+                .unwrap_or(Number::Int(0))
+        };
+
+        match function {
+            Function::Sum => Ok(sum()?.into_cell()),
+            Function::Min => Ok(pick(|a, b| a < b).into_cell()),
+            Function::Max => Ok(pick(|a, b| a > b).into_cell()),
+            Function::Average => match numbers.len() {
+                0 => Err(SheetError::DivisionByZero { cell: self.cell }),
+                len => Ok(self.checked(sum()?, Number::Int(len as i32), '/')?.into_cell()),
+            },
+        }
+    }
+}
+
+// ---- the sheet ----
+
+enum Content {
+    Value(SpreadsheetCell),
+    Formula { source: String, expr: Expr },
+}
+
+#[derive(Default)]
+pub struct Sheet {
+    contents: BTreeMap<CellRef, Content>,
+    values: BTreeMap<CellRef, Value>,
+    /// The cells each formula reads.
+    dependencies: BTreeMap<CellRef, BTreeSet<CellRef>>,
+    /// The formulas that read each cell.
+    dependents: BTreeMap<CellRef, BTreeSet<CellRef>>,
+}
+
+impl Sheet {
+    pub fn new() -> Sheet {
+        Sheet::default()
+    }
+
+    /// Sets a cell from what would be typed into it: `=` starts a formula, and anything else is
+    /// an Int, a Float or, failing those, Text. An empty input clears the cell.
+    pub fn set(&mut self, at: &str, input: &str) -> Result<(), SheetError> {
+        let cell = CellRef::parse(at)?;
+        if input.is_empty() {
+            return self.put(cell, None);
+        }
+        let content = match input.strip_prefix('=') {
+            Some(source) => Content::Formula {
+                source: String::from(source),
+                expr: Parser {
+                    cell,
+                    chars: source.chars().collect(),
+                    position: 0,
+                    nesting: 0,
+                    source,
+                }
+                .parse()?,
+            },
+            None => Content::Value(
+                parse_number(input).unwrap_or_else(|| SpreadsheetCell::Text(String::from(input))),
+            ),
+        };
+        self.put(cell, Some(content))
+    }
+
+    pub fn set_value(&mut self, at: &str, value: SpreadsheetCell) -> Result<(), SheetError> {
+        let cell = CellRef::parse(at)?;
+        self.put(cell, Some(Content::Value(value)))
+    }
+
+    /// The value of a cell; None if it is empty, and the error if its formula failed.
+    pub fn value(&self, at: &str) -> Result<Option<SpreadsheetCell>, SheetError> {
+        let cell = CellRef::parse(at)?;
+        match self.values.get(&cell) {
+            Some(value) => value.clone().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// The value as the sheet would show it, with errors as codes like `#DIV/0!`.
+    pub fn display(&self, at: &str) -> String {
+        match self.value(at) {
+            Ok(Some(value)) => value.to_string(),
+            Ok(None) => String::new(),
+            Err(e) => String::from(e.code()),
+        }
+    }
+
+    /// The formula in a cell, with its `=`.
+    pub fn formula(&self, at: &str) -> Option<String> {
+        match self.contents.get(&CellRef::parse(at).ok()?)? {
+            Content::Formula { source, .. } => Some(format!("={}", source)),
+            Content::Value(_) => None,
+        }
+    }
+
+    /// The cells whose formulas read `at`, directly.
+    pub fn dependents(&self, at: &str) -> Vec<CellRef> {
+        CellRef::parse(at)
+            .ok()
+            .and_then(|cell| self.dependents.get(&cell))
+            .map_or(vec![], |cells| cells.iter().copied().collect())
+    }
+
+    fn put(&mut self, cell: CellRef, content: Option<Content>) -> Result<(), SheetError> {
+        let mut dependencies = BTreeSet::new();
+        if let Some(Content::Formula { expr, .. }) = &content {
+            expr.references(&mut dependencies);
+        }
+        if let Some(cycle) = self.find_cycle(cell, &dependencies) {
+            return Err(SheetError::Cycle(cycle));
+        }
+
+        for old in self.dependencies.remove(&cell).unwrap_or_default() {
+            if let Some(dependents) = self.dependents.get_mut(&old) {
+                dependents.remove(&cell);
+                if dependents.is_empty() {
+                    self.dependents.remove(&old);
+                }
+            }
+        }
+        for new in &dependencies {
+            self.dependents.entry(*new).or_default().insert(cell);
+        }
+        if !dependencies.is_empty() {
+            self.dependencies.insert(cell, dependencies);
+        }
+
+        match content {
+            Some(content) => {
+                self.contents.insert(cell, content);
+            }
+            None => {
+                self.contents.remove(&cell);
+            }
+        }
+        self.recalculate(cell);
+        Ok(())
+    }
+
+    // A path from `cell` back to itself, if it read `dependencies`. The search goes downstream
+    // from `cell`, through the formulas that already read it, looking for one of `dependencies`;
+    // a new cell has no dependents, so filling in a sheet doesn't walk what it reads.
+    fn find_cycle(&self, cell: CellRef, dependencies: &BTreeSet<CellRef>) -> Option<Vec<CellRef>> {
+        let mut came_from = BTreeMap::new();
+        let mut queue = VecDeque::from([cell]);
+        while let Some(c) = queue.pop_front() {
+            if dependencies.contains(&c) {
+                let mut cycle = vec![cell, c];
+                let mut at = c;
+                while at != cell {
+                    at = came_from[&at];
+                    cycle.push(at);
+                }
+                return Some(cycle);
+            }
+            for dependent in self.dependents.get(&c).into_iter().flatten() {
+                if *dependent != cell && !came_from.contains_key(dependent) {
+                    came_from.insert(*dependent, c);
+                    queue.push_back(*dependent);
+                }
+            }
+        }
+        None
+    }
+
+    // Recalculates `cell` and everything that depends on it, each after what it reads.
+    fn recalculate(&mut self, cell: CellRef) {
+        let mut affected = BTreeSet::from([cell]);
+        let mut queue = vec![cell];
+        while let Some(c) = queue.pop() {
+            for dependent in self.dependents.get(&c).into_iter().flatten() {
+                if affected.insert(*dependent) {
+                    queue.push(*dependent);
+                }
+            }
+        }
+
+        for c in self.order(&affected) {
+            let value = match self.contents.get(&c) {
+                None => {
+                    self.values.remove(&c);
+                    continue;
+                }
+                Some(Content::Value(v)) => Ok(v.clone()),
+                Some(Content::Formula { expr, .. }) => Evaluation {
+                    cell: c,
+                    values: &self.values,
+                }
+                .eval(expr),
+            };
+            self.values.insert(c, value);
+        }
+    }
+
+    // Depth first, so a cell comes after the affected cells it reads. A cell goes on the stack
+    // once to push what it reads and again, below those, to be put in the order after them.
+    fn order(&self, affected: &BTreeSet<CellRef>) -> Vec<CellRef> {
+        let mut order = vec![];
+        let mut done = BTreeSet::new();
+        let mut stack: Vec<(CellRef, bool)> = affected.iter().rev().map(|c| (*c, false)).collect();
+        while let Some((cell, read)) = stack.pop() {
+            if read {
+                order.push(cell);
+                continue;
+            }
+            if !done.insert(cell) {
+                continue;
+            }
+            stack.push((cell, true));
+            for dependency in self.dependencies.get(&cell).into_iter().flatten().rev() {
+                if affected.contains(dependency) && !done.contains(dependency) {
+                    stack.push((*dependency, false));
+                }
+            }
+        }
+        order
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use SpreadsheetCell::{Float, Int, Text};
+
+    fn value(sheet: &Sheet, at: &str) -> SpreadsheetCell {
+        sheet.value(at).unwrap().unwrap()
+    }
+
+    #[test]
+    fn references_round_trip() {
+        for (text, row, column) in [("A1", 0, 0), ("Z9", 8, 25), ("AA10", 9, 26), ("AZ1", 0, 51), ("BA1", 0, 52)] {
+            let r = CellRef::parse(text).unwrap();
+            assert_eq!((r.row, r.column), (row, column));
+            assert_eq!(r.to_string(), text);
+        }
+        assert_eq!(CellRef::parse("b2").unwrap().to_string(), "B2");
+        for bad in ["", "A", "1", "A0", "A-1", "ABCD1", "A1B"] {
+            assert!(CellRef::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn formulas_recalculate_when_their_inputs_change() {
+        let mut sheet = Sheet::new();
+        sheet.set("A1", "1").unwrap();
+        sheet.set("A2", "2").unwrap();
+        sheet.set("A3", "3").unwrap();
+        sheet.set("B1", "=SUM(A1:A3)").unwrap();
+        sheet.set("C1", "=B1 * 2 + A1").unwrap();
+        assert_eq!((value(&sheet, "B1"), value(&sheet, "C1")), (Int(6), Int(13)));
+
+        sheet.set("A2", "2.5").unwrap();
+        assert_eq!((value(&sheet, "B1"), value(&sheet, "C1")), (Float(6.5), Float(14.0)));
+
+        // B1 no longer reads A3, so changing A3 leaves it alone.
+        sheet.set("B1", "=A1 + A2").unwrap();
+        assert_eq!(sheet.dependents("A3"), vec![]);
+        assert_eq!(sheet.dependents("B1"), vec![CellRef::parse("C1").unwrap()]);
+        sheet.set("A3", "100").unwrap();
+        assert_eq!(value(&sheet, "B1"), Float(3.5));
+
+        // Clearing a cell makes it 0 to the formulas that read it.
+        sheet.set("A2", "").unwrap();
+        assert_eq!(sheet.value("A2"), Ok(None));
+        assert_eq!(value(&sheet, "C1"), Int(3));
+        assert_eq!(sheet.formula("C1").as_deref(), Some("=B1 * 2 + A1"));
+    }
+
+    #[test]
+    fn cycles_are_refused_and_leave_the_sheet_as_it_was() {
+        let mut sheet = Sheet::new();
+        sheet.set("A1", "=B1 + 1").unwrap();
+        sheet.set("B1", "=C1 + 1").unwrap();
+
+        let error = sheet.set("C1", "=A1").unwrap_err();
+        assert_eq!(error.to_string(), "circular reference: C1 -> A1 -> B1 -> C1");
+        assert_eq!(sheet.value("C1"), Ok(None));
+        assert_eq!(value(&sheet, "A1"), Int(2));
+
+        assert!(matches!(sheet.set("D1", "=SUM(D1:D3)"), Err(SheetError::Cycle(_))));
+        sheet.set("C1", "5").unwrap();
+        assert_eq!(value(&sheet, "A1"), Int(7));
+    }
+
+    #[test]
+    fn long_chains_of_formulas_recalculate() {
+        let mut sheet = Sheet::new();
+        sheet.set("A1", "1").unwrap();
+        for row in 2..=10_000 {
+            sheet.set(&format!("A{}", row), &format!("=A{} + 1", row - 1)).unwrap();
+        }
+        assert_eq!(value(&sheet, "A10000"), Int(10_000));
+
+        sheet.set("A1", "5").unwrap();
+        assert_eq!(value(&sheet, "A10000"), Int(10_004));
+        let error = sheet.set("A1", "=A10000").unwrap_err().to_string();
+        assert!(error.starts_with("circular reference: A1 -> A10000 -> A9999 -> "), "{}", error);
+        assert!(error.ends_with(" -> A2 -> A1"), "{}", error);
+    }
+
+    #[test]
+    fn values_are_coerced_by_type() {
+        let mut sheet = Sheet::new();
+        sheet.set("A1", "7").unwrap();
+        sheet.set("A2", "42").unwrap();
+        sheet.set_value("A3", Text(String::from("blue"))).unwrap();
+        sheet.set_value("A4", Text(String::from(" 1.5 "))).unwrap();
+
+        let formulas = [
+            ("=A1 / 2", Float(3.5)),
+            ("=A2 / 2", Int(21)),
+            ("=A1 * 1.0", Float(7.0)),
+            ("=A4 + 1", Float(2.5)),
+            ("=A1 & \" \" & A3", Text(String::from("7 blue"))),
+            ("=\"say \"\"hi\"\"\"", Text(String::from("say \"hi\""))),
+            ("=-(A1 - 10) * 2", Int(6)),
+            ("=SUM(A1:A4)", Int(49)),
+            ("=SUM(A4, 1)", Float(2.5)),
+            ("=MAX(A1:A3) - MIN(A1:A2)", Int(35)),
+            ("=AVERAGE(A1:A2, 1)", Float(50.0 / 3.0)),
+            ("=A9 + 1", Int(1)),
+        ];
+        for (formula, expected) in formulas {
+            sheet.set("B1", formula).unwrap();
+            assert_eq!(value(&sheet, "B1"), expected, "{}", formula);
+        }
+    }
+
+    #[test]
+    fn errors_are_held_by_cells_and_spread_to_their_dependents() {
+        let mut sheet = Sheet::new();
+        sheet.set("A1", "blue").unwrap();
+        sheet.set("B1", "=A1 * 2").unwrap();
+        sheet.set("C1", "=B1 + 1").unwrap();
+        assert_eq!(sheet.display("C1"), "#VALUE!");
+        assert_eq!(sheet.value("C1").unwrap_err().to_string(), "B1: \"blue\" isn't a number");
+
+        sheet.set("A1", "0").unwrap();
+        sheet.set("B1", "=1 / A1").unwrap();
+        assert_eq!(sheet.display("C1"), "#DIV/0!");
+        sheet.set("B1", "=2147483647 + 1").unwrap();
+        assert_eq!(sheet.display("C1"), "#NUM!");
+        sheet.set("A1", "-2147483648").unwrap();
+        sheet.set("B1", "=A1 / -1").unwrap();
+        assert_eq!(sheet.display("C1"), "#NUM!");
+        sheet.set("A1", "0").unwrap();
+        sheet.set("B1", "=A1").unwrap();
+        assert_eq!(sheet.display("C1"), "1");
+
+        let syntax = |formula: &str| Sheet::new().set("D1", formula).unwrap_err().to_string();
+        assert_eq!(syntax("=1 +"), "D1: column 4: expected a value");
+        assert_eq!(syntax("=SUM(A1:)"), "D1: column 8: expected the cell at the end of the range");
+        assert_eq!(syntax("=FOO(1)"), "D1: column 1: unknown function `FOO`");
+        assert_eq!(syntax("=A1 A2"), "D1: column 4: unexpected `A`");
+        assert_eq!(syntax("=SUM(A1:Z1000)"), "D1: column 8: a range can cover at most 10000 cells");
+        assert!(sheet.set("1A", "1").is_err());
+
+        let too_deep = format!(
+            "D1: column {}: the formula is nested more than {} levels deep",
+            MAX_DEPTH + 1,
+            MAX_DEPTH
+        );
+        assert_eq!(syntax(&format!("={}1", "(".repeat(100_000))), too_deep);
+        assert_eq!(syntax(&format!("={}1", "-".repeat(100_000))), too_deep);
+        for formula in [vec!["SUM(1"; 100_000].join(","), vec!["1"; 100_000].join("+")] {
+            assert!(syntax(&format!("={}", formula)).ends_with("levels deep"));
+        }
+
+        // Each run of `+` is written one level deep but sits on top of the runs inside it.
+        let runs = (0..60).fold(String::from("1"), |inner, _| format!("({}{})", inner, "+1".repeat(60)));
+        assert!(syntax(&format!("={}", runs)).ends_with("levels deep"));
+        let runs = (0..4).fold(String::from("1"), |inner, _| format!("({}{})", inner, "+1".repeat(49)));
+        assert_eq!(Sheet::new().set("D1", &format!("={}", runs)), Ok(()));
+
+        let mut deep = Sheet::new();
+        let parens = format!("={}1{}", "(".repeat(MAX_DEPTH - 1), ")".repeat(MAX_DEPTH - 1));
+        deep.set("D1", &parens).unwrap();
+        deep.set("D2", &format!("={}", vec!["1"; MAX_DEPTH].join("+"))).unwrap();
+        assert_eq!((value(&deep, "D1"), value(&deep, "D2")), (Int(1), Int(MAX_DEPTH as i32)));
+    }
+}